@@ -0,0 +1,47 @@
+@echo off
+chcp 65001 >nul
+setlocal
+
+echo.
+echo ╔══════════════════════════════════════════════════════════╗
+echo ║  Trie Layout Benchmark - HashMap / sorted Vec / pool    ║
+echo ╚══════════════════════════════════════════════════════════╝
+echo.
+
+REM Iterations over test_sentences.txt (override: benchmark_trie.bat 5000)
+set "ITERATIONS=%~1"
+if "%ITERATIONS%"=="" set "ITERATIONS=1000"
+
+echo Compiling all layouts...
+rustc -O jpn_to_phoneme.rs -o jpn_to_phoneme_hashmap.exe
+if errorlevel 1 goto :error
+rustc -O --cfg "feature=\"vec_trie\"" jpn_to_phoneme.rs -o jpn_to_phoneme_vec.exe
+if errorlevel 1 goto :error
+rustc -O --cfg "feature=\"pool_trie\"" jpn_to_phoneme.rs -o jpn_to_phoneme_pool.exe
+if errorlevel 1 goto :error
+echo.
+
+echo ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+echo [1/3] HashMap children (default)
+echo ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+jpn_to_phoneme_hashmap.exe bench-trie test_sentences.txt %ITERATIONS%
+echo.
+
+echo ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+echo [2/3] Sorted Vec children (vec_trie)
+echo ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+jpn_to_phoneme_vec.exe bench-trie test_sentences.txt %ITERATIONS%
+echo.
+
+echo ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+echo [3/3] Inline node pool per parent (pool_trie)
+echo ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+jpn_to_phoneme_pool.exe bench-trie test_sentences.txt %ITERATIONS%
+echo.
+
+echo Checksums must match - all layouts produce identical output.
+goto :eof
+
+:error
+echo ❌ Compilation failed
+exit /b 1
@@ -2,14 +2,34 @@
 // Blazing fast IPA phoneme conversion using optimized trie structure
 // Compile: rustc -O jpn_to_phoneme.rs
 // Or with Cargo: cargo build --release
+// As a library for other Rust projects: see lib.rs
 // Usage: ./jpn_to_phoneme "日本語テキスト"
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::io::{self, Write, BufRead, BufReader, Read};
+use std::io::{self, Write, BufRead, BufReader, BufWriter, Read};
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Instant;
 
+// Optional legacy encoding support (Shift-JIS, EUC-JP)
+// Compile: rustc -O --cfg 'feature="encoding"' --extern encoding_rs=libencoding_rs.rlib jpn_to_phoneme.rs
+#[cfg(feature = "encoding")]
+extern crate encoding_rs;
+
+// Optional parallel batch conversion (convert_batch)
+// Compile: rustc -O --cfg 'feature="parallel"' --extern rayon=librayon.rlib jpn_to_phoneme.rs
+#[cfg(feature = "parallel")]
+extern crate rayon;
+
+// Optional gzip-compressed dictionaries (japanese.trie.gz, ja_phonemes.json.gz)
+// Compile: rustc -O --cfg 'feature="gzip"' --extern flate2=libflate2.rlib jpn_to_phoneme.rs
+#[cfg(feature = "gzip")]
+extern crate flate2;
+
 // ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
 // CONFIGURATION
 // ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
@@ -18,37 +38,898 @@ use std::time::Instant;
 // Uses ja_words.txt for Japanese word boundaries
 const USE_WORD_SEGMENTATION: bool = true;
 
+// Silence the banner and loading messages so stdout carries only results
+// (set by machine-readable output modes such as --tsv)
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Flags that switch to machine-readable output (sets QUIET)
+const QUIET_FLAGS: [&str; 6] = ["--tsv", "--ssml", "--mfa", "--bilingual", "--jsonl", "--stdin"];
+
+/// println! for banner/loading status messages - silent when QUIET is set
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Silence (or restore) the banner and loading messages printed to stdout
+/// For library callers whose stdout carries their own output; the CLI sets
+/// this itself for the QUIET_FLAGS output modes.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Runtime conversion options (all off by default = original behavior)
+#[derive(Debug, Clone, Default)]
+pub struct ConversionOptions {
+    // How digit runs are read before lookup: as Japanese numbers (1000 → せん,
+    // ¥1000 → せんえん), digit by digit for codes (0120 → ぜろ いち に ぜろ),
+    // or left alone (default)
+    pub number_mode: NumberMode,
+    
+    // Clean up list markers at the start of a line: bullets (・ • ● ...) are
+    // dropped, enumerators (１）, 2., (3), ④) are read as numbers plus a 、
+    // pause - see read_list_markers()
+    pub list_markers: bool,
+    
+    // Read symbols between/after numbers as words: 50% → 50パーセント,
+    // 3:2 → 3たい2, 5〜10 → 5から10 - see NUMERIC_OPERATORS
+    pub numeric_patterns: bool,
+    
+    // Read exponents and chemical subscripts in technical text: 2² / 10^3 →
+    // 2のに乗 / 10のさん乗, H₂O → HツーO - see read_scientific_notation().
+    // Only the exponent is read here; number_mode reads the base as usual
+    pub scientific_notation: bool,
+    
+    // Write affricates with the IPA tie bar U+0361 (ʨ → t͡ɕ, ts → t͡s)
+    pub add_tie_bars: bool,
+    
+    // Read obsolete kana the way modern speakers do (ゐ → い, ゑ → え, ゟ → より)
+    // and small ゎ after く/ぐ as labialized onsets (くゎし → kʷaɕi);
+    // off keeps the dictionary's historical readings (ゐ → ɰᵝi)
+    pub classical_kana: bool,
+    
+    // Write doubled vowels inside one word as long vowels (おおきい → oːkiː).
+    // A ー after converted kana is written ː either way (ごーる → goːɾɯ), and
+    // long_vowel_style picks how both come out
+    pub merge_long_vowels: bool,
+    
+    // Extra words whose doubled vowels span a morpheme boundary and must not
+    // merge (added to LONG_VOWEL_EXCEPTIONS)
+    pub long_vowel_exceptions: Vec<String>,
+    
+    // Pick readings of context-sensitive kanji from what follows
+    // (何時 → naɴʥi, 何色 → naniiɾo) - see CONTEXT_RULES
+    pub context_readings: bool,
+    
+    // Casual-text elongation (すごーーい, ながあああい, あ゛ー): normalize runs
+    // before segmentation and write them as ː (implies merge_long_vowels).
+    // Pair with WordSegmenter::skip_elongation_marks so ながーい stays one word
+    pub elongation: Option<ElongationMode>,
+    
+    // Delimiter for inline IPA overrides in segmented conversion: with '/',
+    // 東京/toːkʲoː/ writes toːkʲoː verbatim for 東京 (None = off)
+    pub ipa_override_delimiter: Option<char>,
+    
+    // Delimiter for interlinear base|reading annotations in segmented
+    // conversion: with '|', 日本語|にほんご converts the reading like the
+    // furigana hint 日本語「にほんご」 (None = off, so a literal | is kept)
+    pub reading_delimiter: Option<char>,
+    
+    // Rewrite written forms to their spoken contractions before segmentation
+    // (食べている → 食べてる, という → っていう) - see CONTRACTIONS
+    pub colloquial_contractions: bool,
+    
+    // Write each input whitespace run as this token (e.g. "<pause>") for
+    // deliberate pacing, instead of dropping it (segmented) or passing it
+    // through (unsegmented). Separated from the phonemes by spaces
+    pub explicit_space_token: Option<String>,
+    
+    // Reverse convert() / convert_with_segmentation() output mora by mora,
+    // for phonetic palindrome checks - see reverse_morae()
+    pub reverse_morae: bool,
+    
+    // Case of ASCII letters that pass through unconverted (Preserve keeps them)
+    pub ascii_case: AsciiCase,
+    
+    // Remove zero-width and control characters from scraped text before
+    // lookup, so 日\u{200B}本 still matches 日本 - see is_invisible_char()
+    pub strip_control_chars: bool,
+    
+    // Write the ideographic space U+3000 as an ASCII space before conversion.
+    // Segmentation already treats it as a word boundary either way; this
+    // keeps convert() from passing a wide space through to the phonemes, and
+    // explicit_space_token then replaces it the same as any other space
+    pub normalize_ideographic_space: bool,
+    
+    // Spell a ー ending a katakana loanword consistently, since sources mix
+    // コンピューター and コンピュータ - see LoanwordVowelPolicy
+    pub loanword_final_vowel: LoanwordVowelPolicy,
+    
+    // Fold katakana to hiragana before lookup (カタカナ → かたかな), for
+    // dictionaries keyed in hiragana only - see normalize_kana(). Runs after
+    // the loanword pass, which needs to see the katakana
+    pub fold_katakana: bool,
+    
+    // How length is written: geminates (kː / kk) and long vowels (oː / oo /
+    // ō) - see apply_length_styles(). Runs after merge_long_vowels, so pair
+    // that with these when the dictionary spells a long vowel as oo
+    pub geminate_style: GeminateStyle,
+    pub long_vowel_style: LongVowelStyle,
+    
+    // Symbol set of the output: full IPA, or a smaller ASCII-leaning set for
+    // constrained TTS models - see PhonemeInventory
+    pub phoneme_inventory: PhonemeInventory,
+    
+    // What are_homophones() ignores when comparing two conversions
+    pub phoneme_comparison: PhonemeComparison,
+    
+    // Reading of a standalone を/ヲ (particle included) - see WoReading.
+    // を inside a longer dictionary key (をば, ヲタク) keeps the key's reading
+    pub wo_reading: WoReading,
+    
+    // Casing of each word's phonemes in segmented output, for tools that mark
+    // word starts with a capital (Preserve leaves them as converted)
+    pub word_case: WordCase,
+    
+    // Placeholder written for a kanji the dictionary has no reading for
+    // (e.g. "ə" or "<unk>"), so the output never carries a raw glyph.
+    // Only CJK ideographs - see is_kanji(); punctuation and other unmatched
+    // characters pass through as before (None = pass kanji through too)
+    pub unknown_kanji_phoneme: Option<String>,
+}
+
+/// Casing of segmented words' phonemes (ConversionOptions::word_case)
+/// IPA letters with no capital form (ɕ, ɴ, ʨ) stay as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WordCase {
+    #[default]
+    Preserve,      // As converted (original behavior)
+    Lower,         // Every phoneme lowercase: passed-through iPhone → iphone
+    UpperInitial,  // First phoneme of each word capitalized: ɰᵝatai wa → ɰᵝatai Wa
+}
+
+impl WordCase {
+    /// Apply to one word's phonemes
+    fn apply(self, phonemes: String) -> String {
+        match self {
+            WordCase::Preserve => phonemes,
+            WordCase::Lower => phonemes.to_lowercase(),
+            WordCase::UpperInitial => {
+                let mut chars = phonemes.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => phonemes,
+                }
+            }
+        }
+    }
+}
+
+/// How a standalone を/ヲ is read (ConversionOptions::wo_reading)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WoReading {
+    #[default]
+    O,   // Modern standard pronunciation, as the dictionary has it: 本を → hoɴ o
+    Wo,  // Careful/sung or historical pronunciation: 本を → hoɴ ɰᵝo
+}
+
+/// Differences are_homophones() ignores (ConversionOptions::phoneme_comparison)
+/// Word boundaries (whitespace) are always ignored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhonemeComparison {
+    pub ignore_accent: bool,     // Pitch/stress marks (ꜛ ꜜ ˈ ˌ), e.g. from IPA overrides: haꜜɕi = haɕiꜜ (default on)
+    pub ignore_length: bool,     // Long vowels and geminates: おばさん = おばあさん, かた = かった
+    pub ignore_devoicing: bool,  // Devoiced vowels (U+0325 ring below): sɯ̥ki = sɯki
+}
+
+impl Default for PhonemeComparison {
+    fn default() -> Self {
+        PhonemeComparison { ignore_accent: true, ignore_length: false, ignore_devoicing: false }
+    }
+}
+
+impl PhonemeComparison {
+    /// Phonemes with everything this comparison ignores removed
+    pub fn normalize(&self, phonemes: &str) -> String {
+        let phonemes = if self.ignore_length { merge_long_vowels(phonemes) } else { phonemes.to_string() };
+        
+        phonemes.chars()
+            .filter(|&ch| !ch.is_whitespace())
+            .filter(|&ch| !(self.ignore_accent && matches!(ch, 'ꜛ' | 'ꜜ' | 'ˈ' | 'ˌ' | '↑' | '↓')))
+            .filter(|&ch| !(self.ignore_length && ch == 'ː'))
+            .filter(|&ch| !(self.ignore_devoicing && ch == '\u{0325}'))
+            .collect()
+    }
+}
+
+/// How a ー ending a katakana word is spelled (ConversionOptions::loanword_final_vowel)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LoanwordVowelPolicy {
+    #[default]
+    Keep,   // As written (original behavior)
+    Long,   // Add the ー when the dictionary knows that spelling: コンピュータ → コンピューター
+    Short,  // JIS Z 8301 style: words of 3+ morae drop it (サーバー → サーバ), shorter ones keep it (キー)
+}
+
+/// Phoneme symbol set of the output (ConversionOptions::phoneme_inventory)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PhonemeInventory {
+    #[default]
+    FullIpa,     // Dictionary IPA as is (original behavior)
+    Simplified,  // Collapsed set for small models: ɕi → shi, saɴ → saN (see SIMPLIFIED_INVENTORY)
+}
+
+/// Spelling of geminate consonants (ConversionOptions::geminate_style)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GeminateStyle {
+    #[default]
+    Length,   // Length mark after the consonant: gakːoɯ (dictionary spelling)
+    Doubled,  // Consonant written twice: gakkoɯ; affricates double their stop (tʨ, tt͡ɕ)
+}
+
+/// Spelling of long vowels (ConversionOptions::long_vowel_style)
+/// A held moraic nasal (ɴː) doubles with Doubled and is otherwise left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LongVowelStyle {
+    #[default]
+    Length,   // Length mark: koːçiː (dictionary spelling)
+    Doubled,  // Vowel written twice: kooçii
+    Macron,   // Macron over the vowel: kōçī (ɯ takes a combining macron: ɯ̄)
+}
+
+/// Case of unmatched ASCII letters in the output (ConversionOptions::ascii_case)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AsciiCase {
+    #[default]
+    Preserve,  // As written (original behavior)
+    Lower,     // iPhone → iphone
+    Upper,     // iPhone → IPHONE
+}
+
+impl AsciiCase {
+    /// Apply to one passed-through char (non-ASCII is never changed)
+    fn apply(self, ch: char) -> char {
+        match self {
+            AsciiCase::Preserve => ch,
+            AsciiCase::Lower => ch.to_ascii_lowercase(),
+            AsciiCase::Upper => ch.to_ascii_uppercase(),
+        }
+    }
+}
+
+/// How digit runs are read (ConversionOptions::number_mode)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NumberMode {
+    Cardinal,      // Whole numbers with currency: 1000 → せん, ¥1000 → せんえん
+    DigitByDigit,  // One reading per digit for phone numbers and IDs: 0120 → ぜろ いち に ぜろ
+    #[default]
+    Passthrough,   // Digits stay as they are (original behavior)
+}
+
+/// How elongation runs are written (ConversionOptions::elongation)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ElongationMode {
+    Collapse,      // Any run is one long vowel: すごーーーい → sɯgoːi
+    Proportional,  // One ː per mark: すごーーーい → sɯgoːːːi
+}
+
+/// Modern readings for obsolete kana, applied by the classical_kana pass.
+/// ゐ/ゑ merged with い/え in pronunciation centuries ago, so "i"/"e" is what a
+/// reader says today; the ligatures ゟ (より) and ヿ (コト) are spelled out.
+/// ヷヸヹヺ are left to the dictionary (va/vi/ve/vo), which already reads them.
+const CLASSICAL_KANA: [(char, &str); 6] = [
+    ('ゐ', "い"), ('ゑ', "え"), ('ヰ', "イ"), ('ヱ', "エ"),
+    ('ゟ', "より"), ('ヿ', "コト"),
+];
+
+/// Labialized readings for small ゎ/ヮ after く/ぐ (historical くゎし, Okinawan
+/// グヮー), read by the classical_kana option. The dictionary only knows ゎ on
+/// its own (ɰa), which would split くゎ into kɯɰa.
+const LABIALIZED_KANA: [(&str, &str); 4] = [
+    ("くゎ", "kʷa"), ("ぐゎ", "gʷa"), ("クヮ", "kʷa"), ("グヮ", "gʷa"),
+];
+
+/// Presentation-form punctuation from vertical and typeset text, mapped to
+/// the standard characters it displays as (always applied before lookup)
+/// 
+/// - U+FE10-FE19 vertical forms: ︐ → ，, ︑ → 、, ︒ → 。, ︙ → …
+/// - U+FE30-FE4F CJK compatibility forms: ︵︶ → （）, ﹁﹂ → 「」, ︰ → ‥;
+///   the sesame-dot emphasis marks ﹅﹆ have no sound and are dropped
+/// - U+FE50-FE5A small forms: ﹐ → ，, ﹑ → 、, ﹒ → ．
+/// - U+22EF midline ellipsis ⋯ → …
+const PRESENTATION_PUNCTUATION: [(char, &str); 49] = [
+    ('\u{FE10}', "，"), ('\u{FE11}', "、"), ('\u{FE12}', "。"), ('\u{FE13}', "："),
+    ('\u{FE14}', "；"), ('\u{FE15}', "！"), ('\u{FE16}', "？"), ('\u{FE17}', "〖"),
+    ('\u{FE18}', "〗"), ('\u{FE19}', "…"),
+    ('\u{FE30}', "‥"), ('\u{FE31}', "—"), ('\u{FE32}', "–"), ('\u{FE33}', "＿"),
+    ('\u{FE34}', "＿"), ('\u{FE35}', "（"), ('\u{FE36}', "）"), ('\u{FE37}', "｛"),
+    ('\u{FE38}', "｝"), ('\u{FE39}', "〔"), ('\u{FE3A}', "〕"), ('\u{FE3B}', "【"),
+    ('\u{FE3C}', "】"), ('\u{FE3D}', "《"), ('\u{FE3E}', "》"), ('\u{FE3F}', "〈"),
+    ('\u{FE40}', "〉"), ('\u{FE41}', "「"), ('\u{FE42}', "」"), ('\u{FE43}', "『"),
+    ('\u{FE44}', "』"), ('\u{FE45}', ""), ('\u{FE46}', ""), ('\u{FE47}', "［"),
+    ('\u{FE48}', "］"), ('\u{FE49}', "￣"), ('\u{FE4D}', "＿"), ('\u{FE4F}', "＿"),
+    ('\u{FE50}', "，"), ('\u{FE51}', "、"), ('\u{FE52}', "．"), ('\u{FE54}', "；"),
+    ('\u{FE55}', "："), ('\u{FE56}', "？"), ('\u{FE57}', "！"), ('\u{FE58}', "—"),
+    ('\u{FE59}', "（"), ('\u{FE5A}', "）"),
+    ('\u{22EF}', "…"),
+];
+
+/// Non-printing characters removed by the strip_control_chars pass
+/// 
+/// - C0 controls except tab, newline and carriage return; DEL; C1 controls
+/// - Zero-width space/joiners: U+200B-U+200D, word joiner U+2060, BOM U+FEFF
+/// - Soft hyphen U+00AD, Mongolian vowel separator U+180E
+/// - Bidi marks and controls: U+200E-U+200F, U+202A-U+202E, U+2066-U+2069
+/// - Variation selectors U+FE00-U+FE0F and U+E0100-U+E01EF (kanji IVS),
+///   which otherwise keep a kanji from matching its dictionary entry
+fn is_invisible_char(ch: char) -> bool {
+    let cp = ch as u32;
+    (cp < 0x20 && !matches!(ch, '\t' | '\n' | '\r')) ||
+    (cp >= 0x7F && cp <= 0x9F) ||
+    matches!(cp, 0x00AD | 0x180E | 0x2060 | 0xFEFF) ||
+    (cp >= 0x200B && cp <= 0x200F) ||
+    (cp >= 0x202A && cp <= 0x202E) ||
+    (cp >= 0x2066 && cp <= 0x2069) ||
+    (cp >= 0xFE00 && cp <= 0xFE0F) ||
+    (cp >= 0xE0100 && cp <= 0xE01EF)
+}
+
+/// Replace each whitespace run with ` token ` (explicit_space_token option)
+/// Leading/trailing runs become the token without the outer space.
+fn replace_whitespace_runs(text: &str, token: &str) -> String {
+    let mut result = String::with_capacity(text.len() + token.len());
+    let mut in_run = false;
+    
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            if !in_run {
+                if !result.is_empty() {
+                    result.push(' ');
+                }
+                result.push_str(token);
+                in_run = true;
+            }
+        } else {
+            if in_run {
+                result.push(' ');
+                in_run = false;
+            }
+            result.push(ch);
+        }
+    }
+    
+    result
+}
+
+/// Replace obsolete kana with their modern equivalents (see CLASSICAL_KANA)
+fn modernize_kana(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    
+    for ch in text.chars() {
+        match CLASSICAL_KANA.iter().find(|&&(k, _)| k == ch) {
+            Some(&(_, modern)) => result.push_str(modern),
+            None => result.push(ch),
+        }
+    }
+    
+    result
+}
+
+/// True for presentation-form punctuation (see PRESENTATION_PUNCTUATION)
+fn is_presentation_punctuation(ch: char) -> bool {
+    PRESENTATION_PUNCTUATION.iter().any(|&(form, _)| form == ch)
+}
+
+/// Replace presentation-form punctuation with its standard equivalent
+fn normalize_presentation_punctuation(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    
+    for ch in text.chars() {
+        match PRESENTATION_PUNCTUATION.iter().find(|&&(form, _)| form == ch) {
+            Some(&(_, standard)) => result.push_str(standard),
+            None => result.push(ch),
+        }
+    }
+    
+    result
+}
+
+/// Affricates rewritten by the tie-bar pass: dictionary ligatures and
+/// two-letter spellings both become the tied form. Vowel sequences are left
+/// alone - Japanese "diphthongs" are two moras, not one tied segment.
+const TIE_BAR_AFFRICATES: [(&str, &str); 10] = [
+    ("ʦ", "t͡s"), ("ʨ", "t͡ɕ"), ("ʥ", "d͡ʑ"), ("ʣ", "d͡z"),
+    ("ts", "t͡s"), ("tɕ", "t͡ɕ"), ("dʑ", "d͡ʑ"), ("dz", "d͡z"),
+    ("tʃ", "t͡ʃ"), ("dʒ", "d͡ʒ"),
+];
+
+/// Rewrites of the simplified phoneme inventory, applied in order (j → y
+/// runs before ʥ → j). The moraic nasal ɴ and its assimilated ŋ collapse to
+/// N; onset n and m stay apart since they tell な from ま. Tied affricates
+/// from the tie-bar pass are covered too.
+const SIMPLIFIED_INVENTORY: [(&str, &str); 24] = [
+    ("j", "y"),
+    ("t͡ɕ", "ch"), ("ʨ", "ch"), ("d͡ʑ", "j"), ("ʥ", "j"),
+    ("t͡s", "ts"), ("ʦ", "ts"), ("d͡z", "z"), ("ʣ", "z"),
+    ("ɕ", "sh"), ("ɰᵝ", "w"), ("ɰ", "w"), ("ᵝ", ""), ("ʲ", "y"), ("ʷ", "w"),
+    ("ɯ", "u"), ("ɾ", "r"), ("ɸ", "f"), ("ç", "h"),
+    ("ɴ", "N"), ("ŋ", "N"), ("ɲ", "n"), ("ʔ", "Q"), ("ɡ", "g"),
+];
+
+/// Rewrite IPA phonemes into the simplified inventory (see SIMPLIFIED_INVENTORY)
+fn simplify_phonemes(phonemes: &str) -> String {
+    let mut phonemes = phonemes.to_string();
+    
+    for &(full, simple) in &SIMPLIFIED_INVENTORY {
+        if phonemes.contains(full) {
+            phonemes = phonemes.replace(full, simple);
+        }
+    }
+    
+    phonemes
+}
+
+/// Hepburn-style romaji from simplified-inventory phonemes, for reviewers
+/// who don't read IPA (--bilingual)
+/// 
+/// - N is n, or n' before a vowel or y (kaN'i, not kani)
+/// - A geminate doubles its consonant: kːa → kka, shːi → sshi, chːa → tcha
+/// - A long vowel takes a macron: koːhiː → kōhī
+/// - Q (a っ with nothing to double) is dropped
+fn simplified_to_romaji(simple: &str) -> String {
+    let chars: Vec<char> = simple.chars().collect();
+    let mut romaji = String::with_capacity(simple.len());
+    
+    for (i, &ch) in chars.iter().enumerate() {
+        match ch {
+            'N' => match chars.get(i + 1) {
+                Some(&next) if "aiueoy".contains(next) => romaji.push_str("n'"),
+                _ => romaji.push('n'),
+            },
+            'Q' => {}
+            'ː' => {
+                let cluster_len = romaji.chars().rev()
+                    .take_while(|c| c.is_ascii_lowercase() && !"aiueo".contains(*c))
+                    .count();
+                
+                if cluster_len > 0 {
+                    let start = romaji.len() - cluster_len;
+                    let doubled = if romaji[start..].starts_with("ch") { 't' } else { romaji[start..].chars().next().unwrap_or(' ') };
+                    romaji.insert(start, doubled);
+                } else if let Some(last) = romaji.pop() {
+                    romaji.push(match last {
+                        'a' => 'ā', 'i' => 'ī', 'u' => 'ū', 'e' => 'ē', 'o' => 'ō',
+                        other => other,
+                    });
+                }
+            }
+            _ => romaji.push(ch),
+        }
+    }
+    
+    romaji
+}
+
+/// Child storage of a trie node
+/// Default: HashMap for O(1) character access.
+/// Experimental, compared with `jpn_to_phoneme bench-trie` (see benchmark_trie.bat):
+/// - --cfg 'feature="vec_trie"': sorted Vec of boxed children, binary search
+/// - --cfg 'feature="pool_trie"': sorted Vec of inline children (node pool per
+///   parent - no Box allocation per node)
+#[cfg(not(any(feature = "vec_trie", feature = "pool_trie")))]
+type TrieChildren = HashMap<char, Box<TrieNode>>;
+#[cfg(feature = "vec_trie")]
+type TrieChildren = SortedChildren;
+#[cfg(feature = "pool_trie")]
+type TrieChildren = PooledChildren;
+
+#[cfg(all(feature = "vec_trie", feature = "pool_trie"))]
+compile_error!("features \"vec_trie\" and \"pool_trie\" select different trie layouts - enable only one");
+
 /// High-performance trie node for phoneme lookup
-/// Uses HashMap for O(1) character access
 #[derive(Default)]
 struct TrieNode {
     // Map Unicode chars to child nodes for instant lookup
-    children: HashMap<char, Box<TrieNode>>,
+    children: TrieChildren,
+    
+    // True if a complete dictionary key ends at this node (word boundary)
+    is_terminal: bool,
     
-    // Phoneme value if this node represents end of a word
+    // Phoneme value for the key ending here (None for word-only entries,
+    // Some("") for keys that convert to nothing)
     phoneme: Option<String>,
 }
 
+impl TrieNode {
+    /// Insert `key` below this node with `value` as its phonemes
+    fn insert(&mut self, key: &str, value: &str) {
+        let mut current = self;
+        
+        // Traverse/build trie using Unicode characters
+        for ch in normalize_key_whitespace(key).chars() {
+            current = current.child_or_insert(ch);
+        }
+        
+        // Mark end of word with phoneme value
+        current.is_terminal = true;
+        current.phoneme = Some(value.to_string());
+    }
+    
+    /// Child node for `ch`, created empty if missing (used while building)
+    #[cfg(not(feature = "pool_trie"))]
+    fn child_or_insert(&mut self, ch: char) -> &mut TrieNode {
+        self.children.entry(ch).or_insert_with(|| Box::new(TrieNode::default()))
+    }
+    
+    /// Child node for `ch`, created empty if missing (used while building)
+    #[cfg(feature = "pool_trie")]
+    fn child_or_insert(&mut self, ch: char) -> &mut TrieNode {
+        self.children.get_or_insert(ch)
+    }
+    
+    /// Child node for `ch` to modify (used when removing entries)
+    #[cfg(not(feature = "pool_trie"))]
+    fn child_mut(&mut self, ch: char) -> Option<&mut TrieNode> {
+        self.children.get_mut(&ch).map(|child| &mut **child)
+    }
+    
+    /// Child node for `ch` to modify (used when removing entries)
+    #[cfg(feature = "pool_trie")]
+    fn child_mut(&mut self, ch: char) -> Option<&mut TrieNode> {
+        self.children.get_mut(&ch)
+    }
+    
+    /// Clear the entry for `key` below this node, pruning child nodes left
+    /// with no key. Returns whether the key had phonemes.
+    fn remove_key(&mut self, key: &[char]) -> bool {
+        let (&first, rest) = match key.split_first() {
+            Some(split) => split,
+            None => {
+                let existed = self.phoneme.take().is_some();
+                if existed {
+                    self.is_terminal = false;
+                }
+                return existed;
+            }
+        };
+        
+        let (removed, prune) = match self.child_mut(first) {
+            Some(child) => {
+                let removed = child.remove_key(rest);
+                (removed, child.children.is_empty() && !child.is_terminal)
+            }
+            None => (false, false),
+        };
+        
+        if prune {
+            self.children.remove(&first);
+        }
+        removed
+    }
+}
+
+/// Trie children as a Vec sorted by char (experimental "vec_trie" layout)
+/// 
+/// Kana tries branch narrowly below the first level, so a short contiguous
+/// array searched with binary search can beat hashing on cache behavior and
+/// saves the HashMap's per-table overhead. Mirrors the slice of the HashMap
+/// API the trie code uses (get / entry().or_insert_with / iteration).
+#[cfg(feature = "vec_trie")]
+#[derive(Default)]
+struct SortedChildren {
+    entries: Vec<(char, Box<TrieNode>)>,
+}
+
+#[cfg(feature = "vec_trie")]
+impl SortedChildren {
+    fn get(&self, ch: &char) -> Option<&Box<TrieNode>> {
+        self.entries.binary_search_by_key(ch, |entry| entry.0)
+            .ok()
+            .map(|i| &self.entries[i].1)
+    }
+    
+    fn contains_key(&self, ch: &char) -> bool {
+        self.entries.binary_search_by_key(ch, |entry| entry.0).is_ok()
+    }
+    
+    fn get_mut(&mut self, ch: &char) -> Option<&mut Box<TrieNode>> {
+        match self.entries.binary_search_by_key(ch, |entry| entry.0) {
+            Ok(i) => Some(&mut self.entries[i].1),
+            Err(_) => None,
+        }
+    }
+    
+    fn entry(&mut self, ch: char) -> SortedEntry<'_> {
+        SortedEntry { children: self, ch }
+    }
+    
+    fn remove(&mut self, ch: &char) -> Option<Box<TrieNode>> {
+        let index = self.entries.binary_search_by_key(ch, |entry| entry.0).ok()?;
+        Some(self.entries.remove(index).1)
+    }
+    
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    fn values(&self) -> impl Iterator<Item = &Box<TrieNode>> {
+        self.entries.iter().map(|entry| &entry.1)
+    }
+}
+
+/// Pending lookup/insert into SortedChildren (like HashMap's Entry)
+#[cfg(feature = "vec_trie")]
+struct SortedEntry<'a> {
+    children: &'a mut SortedChildren,
+    ch: char,
+}
+
+#[cfg(feature = "vec_trie")]
+impl<'a> SortedEntry<'a> {
+    fn or_insert_with<F: FnOnce() -> Box<TrieNode>>(self, default: F) -> &'a mut Box<TrieNode> {
+        let entries = &mut self.children.entries;
+        let index = match entries.binary_search_by_key(&self.ch, |entry| entry.0) {
+            Ok(i) => i,
+            Err(i) => {
+                // Sorted input (binary trie files) always appends at the end
+                entries.insert(i, (self.ch, default()));
+                i
+            }
+        };
+        &mut entries[index].1
+    }
+}
+
+#[cfg(feature = "vec_trie")]
+fn split_child_entry(entry: &(char, Box<TrieNode>)) -> (&char, &Box<TrieNode>) {
+    (&entry.0, &entry.1)
+}
+
+#[cfg(feature = "vec_trie")]
+impl<'a> IntoIterator for &'a SortedChildren {
+    type Item = (&'a char, &'a Box<TrieNode>);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (char, Box<TrieNode>)>,
+        fn(&'a (char, Box<TrieNode>)) -> (&'a char, &'a Box<TrieNode>),
+    >;
+    
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(split_child_entry)
+    }
+}
+
+/// Trie children stored inline in one Vec sorted by char ("pool_trie" layout)
+/// 
+/// The Vec is a small node pool owned by the parent: all children of a node
+/// live in one allocation, instead of one Box allocation per node. Loading
+/// does far fewer allocator calls and sibling nodes sit next to each other.
+/// Inserting out of order moves sibling nodes - cheap for the sorted
+/// binary trie files, which append in order.
+#[cfg(feature = "pool_trie")]
+#[derive(Default)]
+struct PooledChildren {
+    entries: Vec<(char, TrieNode)>,
+}
+
+#[cfg(feature = "pool_trie")]
+impl PooledChildren {
+    fn get(&self, ch: &char) -> Option<&TrieNode> {
+        self.entries.binary_search_by_key(ch, |entry| entry.0)
+            .ok()
+            .map(|i| &self.entries[i].1)
+    }
+    
+    fn contains_key(&self, ch: &char) -> bool {
+        self.entries.binary_search_by_key(ch, |entry| entry.0).is_ok()
+    }
+    
+    fn get_or_insert(&mut self, ch: char) -> &mut TrieNode {
+        let index = match self.entries.binary_search_by_key(&ch, |entry| entry.0) {
+            Ok(i) => i,
+            Err(i) => {
+                self.entries.insert(i, (ch, TrieNode::default()));
+                i
+            }
+        };
+        &mut self.entries[index].1
+    }
+    
+    fn get_mut(&mut self, ch: &char) -> Option<&mut TrieNode> {
+        match self.entries.binary_search_by_key(ch, |entry| entry.0) {
+            Ok(i) => Some(&mut self.entries[i].1),
+            Err(_) => None,
+        }
+    }
+    
+    fn remove(&mut self, ch: &char) -> Option<TrieNode> {
+        let index = self.entries.binary_search_by_key(ch, |entry| entry.0).ok()?;
+        Some(self.entries.remove(index).1)
+    }
+    
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    fn values(&self) -> impl Iterator<Item = &TrieNode> {
+        self.entries.iter().map(|entry| &entry.1)
+    }
+}
+
+#[cfg(feature = "pool_trie")]
+fn split_pooled_entry(entry: &(char, TrieNode)) -> (&char, &TrieNode) {
+    (&entry.0, &entry.1)
+}
+
+#[cfg(feature = "pool_trie")]
+impl<'a> IntoIterator for &'a PooledChildren {
+    type Item = (&'a char, &'a TrieNode);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (char, TrieNode)>,
+        fn(&'a (char, TrieNode)) -> (&'a char, &'a TrieNode),
+    >;
+    
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(split_pooled_entry)
+    }
+}
+
+/// Approximate heap bytes owned by one node's child table, including the
+/// boxed child nodes themselves (benchmark estimate)
+/// HashMap: one (key, value) slot plus one control byte per bucket.
+#[cfg(not(any(feature = "vec_trie", feature = "pool_trie")))]
+fn children_heap_bytes(children: &TrieChildren) -> usize {
+    children.capacity() * (std::mem::size_of::<(char, Box<TrieNode>)>() + 1) +
+        children.len() * std::mem::size_of::<TrieNode>()
+}
+
+/// Approximate heap bytes owned by one node's child table (benchmark estimate)
+#[cfg(feature = "vec_trie")]
+fn children_heap_bytes(children: &TrieChildren) -> usize {
+    children.entries.capacity() * std::mem::size_of::<(char, Box<TrieNode>)>() +
+        children.entries.len() * std::mem::size_of::<TrieNode>()
+}
+
+/// Approximate heap bytes owned by one node's child table (benchmark estimate)
+/// Child nodes are inline, so the pool's capacity covers them.
+#[cfg(feature = "pool_trie")]
+fn children_heap_bytes(children: &TrieChildren) -> usize {
+    children.entries.capacity() * std::mem::size_of::<(char, TrieNode)>()
+}
+
 /// Individual match from Japanese text to phoneme
 #[derive(Debug, Clone)]
-struct Match {
-    original: String,
-    phoneme: String,
-    start_index: usize,
+pub struct Match {
+    pub original: String,
+    pub phoneme: String,
+    pub start_index: usize,  // Byte position in the (prepared) input
 }
 
-impl Match {
-    fn to_string(&self) -> String {
-        format!("\"{}\" → \"{}\" (pos: {})", self.original, self.phoneme, self.start_index)
+impl std::fmt::Display for Match {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "\"{}\" → \"{}\" (pos: {})", self.original, self.phoneme, self.start_index)
     }
 }
 
+/// Run of input text that either converts or passes through (spans())
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub range: Range<usize>,       // Byte range in the (prepared) input
+    pub convertible: bool,         // Covered by dictionary matches
+    pub phonemes: Option<String>,  // Phonemes of a convertible span, None otherwise
+}
+
+/// One decision of convert_traced(), in the order it was made
+/// Positions are char indices into the text after input passes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    /// Input passes (numbers, elongation, ...) rewrote the text to this
+    Prepared { text: String },
+    /// Every base dictionary key the trie walk passed at `pos`, shortest
+    /// first, as (length in chars, phonemes) - empty if nothing matched
+    Candidates { pos: usize, matches: Vec<(usize, String)> },
+    /// A context rule picked the reading (context_readings option, 何時)
+    ContextReading { pos: usize, original: String, phonemes: String },
+    /// An unkeyed っ doubled the onset of the following match (ざっし)
+    Sokuon { pos: usize, original: String, phonemes: String },
+    /// く/ぐ + small ゎ read as a labialized onset (classical_kana option, くゎし)
+    Labialized { pos: usize, original: String, phonemes: String },
+    /// An unkeyed youon read as one mora (しゅ → ɕɯ)
+    Youon { pos: usize, original: String, phonemes: String },
+    /// を/ヲ read as ɰᵝo (wo_reading option)
+    Wo { pos: usize, original: String, phonemes: String },
+    /// Longest-match rule chose this entry; `variant` if the dialect overlay won
+    Longest { pos: usize, original: String, phonemes: String, variant: bool },
+    /// No entry starts here, the char passes through
+    Unmatched { pos: usize, ch: char },
+    /// Output passes (long vowels, tie bars, ...) rewrote the phonemes to this
+    Finished { phonemes: String },
+}
+
 /// Detailed conversion result with match information
 #[derive(Debug)]
-struct ConversionResult {
-    phonemes: String,
-    matches: Vec<Match>,
-    unmatched: Vec<char>,
+pub struct ConversionResult {
+    pub phonemes: String,
+    pub matches: Vec<Match>,
+    pub unmatched: Vec<char>,
+    pub unmatched_runs: Vec<(usize, String)>,  // Adjacent unmatched chars grouped: (byte position, run)
+    pub word_scores: Vec<(String, f32)>,  // Dictionary coverage per word, 0.0-1.0 (segmented conversion only)
+    pub warnings: Vec<BracketWarning>,  // Unbalanced furigana brackets (segmented conversion only)
+}
+
+impl ConversionResult {
+    /// Share of chars covered by dictionary matches, 0.0-1.0
+    /// Whitespace and punctuation don't count; nothing to cover scores 1.0.
+    pub fn coverage(&self) -> f32 {
+        let counts = |c: &char| !c.is_whitespace() && !is_punctuation(*c);
+        
+        let matched: usize = self.matches.iter()
+            .map(|m| m.original.chars().filter(counts).count())
+            .sum();
+        let unmatched = self.unmatched.iter().filter(|c| counts(c)).count();
+        
+        if matched + unmatched == 0 {
+            1.0
+        } else {
+            matched as f32 / (matched + unmatched) as f32
+        }
+    }
+    
+    /// Chars in the longest unmatched run, 0 if everything matched
+    /// Whitespace and punctuation inside a run don't count, like coverage().
+    pub fn longest_unmatched_run(&self) -> usize {
+        self.unmatched_runs.iter()
+            .map(|(_, run)| run.chars().filter(|c| !c.is_whitespace() && !is_punctuation(*c)).count())
+            .max()
+            .unwrap_or(0)
+    }
+    
+    /// Deepest trie walk of the conversion: the match covering the most chars
+    /// as (length in chars, match), the first one on ties. None if nothing matched.
+    pub fn deepest_match(&self) -> Option<(usize, &Match)> {
+        self.matches.iter()
+            .map(|m| (m.original.chars().count(), m))
+            .fold(None, |deepest, (depth, m)| match deepest {
+                Some((max, _)) if max >= depth => deepest,
+                _ => Some((depth, m)),
+            })
+    }
+}
+
+/// Append an unmatched run, merging it into the previous run when they touch
+fn push_unmatched_run(runs: &mut Vec<(usize, String)>, start: usize, text: &str) {
+    if let Some(last) = runs.last_mut() {
+        if last.0 + last.1.len() == start {
+            last.1.push_str(text);
+            return;
+        }
+    }
+    runs.push((start, text.to_string()));
+}
+
+/// Kind of furigana bracket imbalance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BracketIssue {
+    UnmatchedOpen,   // 「 with no closing 」 (rest of text is treated as normal text)
+    UnmatchedClose,  // 」 with no opening 「 (kept as a literal character)
+}
+
+/// Diagnostic for a furigana bracket without a partner
+#[derive(Debug, Clone)]
+pub struct BracketWarning {
+    pub issue: BracketIssue,
+    pub position: usize,  // Byte offset of the bracket in the input
+}
+
+impl std::fmt::Display for BracketWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.issue {
+            BracketIssue::UnmatchedOpen => write!(f, "unmatched 「 at byte {}", self.position),
+            BracketIssue::UnmatchedClose => write!(f, "unmatched 」 at byte {}", self.position),
+        }
+    }
 }
 
 // ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
@@ -60,6 +941,8 @@ struct ConversionResult {
 enum SegmentType {
     NormalText,     // Regular text without furigana
     FuriganaHint,  // Text with furigana reading hint
+    PhonemeOverride,  // Text with an inline IPA override (東京/toːkʲoː/)
+    Literal,        // Backslash-escaped marker char (\「), never parsed as markup
 }
 
 /// A segment of text that can be either normal or have a furigana hint
@@ -67,7 +950,7 @@ enum SegmentType {
 struct TextSegment {
     segment_type: SegmentType,
     text: String,         // The actual text (kanji for furigana hints)
-    reading: String,      // The reading (only for furigana hints)
+    reading: String,      // The reading (furigana hints) or IPA (phoneme overrides)
     original_pos: usize,  // Position in original text
 }
 
@@ -92,29 +975,467 @@ impl TextSegment {
         }
     }
     
-    // Get the effective text (reading for furigana, text otherwise)
-    fn get_effective_text(&self) -> &str {
-        match self.segment_type {
-            SegmentType::FuriganaHint => &self.reading,
-            SegmentType::NormalText => &self.text,
+    // Constructor for inline phoneme override
+    fn new_override(text: String, phonemes: String, pos: usize) -> Self {
+        TextSegment {
+            segment_type: SegmentType::PhonemeOverride,
+            text,
+            reading: phonemes,
+            original_pos: pos,
         }
     }
+    
+    // Constructor for an escaped marker char
+    fn new_literal(text: String, pos: usize) -> Self {
+        TextSegment {
+            segment_type: SegmentType::Literal,
+            text,
+            reading: String::new(),
+            original_pos: pos,
+        }
+    }
+    
+    // Get the effective text (reading for furigana, text otherwise)
+    fn get_effective_text(&self) -> &str {
+        match self.segment_type {
+            SegmentType::FuriganaHint => &self.reading,
+            SegmentType::NormalText | SegmentType::PhonemeOverride | SegmentType::Literal => &self.text,
+        }
+    }
+}
+
+/// Spell a dictionary key the way the trie stores it
+/// Multi-word keys (お 願い) keep one ASCII space per whitespace run and
+/// no leading/trailing whitespace, so the walk can match any run against it.
+fn normalize_key_whitespace(key: &str) -> Cow<'_, str> {
+    if key.contains(char::is_whitespace) {
+        Cow::Owned(key.split_whitespace().collect::<Vec<_>>().join(" "))
+    } else {
+        Cow::Borrowed(key)
+    }
+}
+
+/// Follow the text char at `i` down one trie edge
+/// A whitespace run in the text (any length, any kind of space) follows the
+/// single ' ' of a multi-word key. Returns the child and the index after the
+/// consumed chars.
+fn step_trie<'a>(node: &'a TrieNode, chars: &[char], i: usize) -> Option<(&'a TrieNode, usize)> {
+    if chars[i].is_whitespace() {
+        let child = node.children.get(&' ')?;
+        let end = chars[i..].iter().position(|c| !c.is_whitespace()).map_or(chars.len(), |n| i + n);
+        Some((child, end))
+    } else {
+        let child: &TrieNode = node.children.get(&chars[i])?;
+        Some((child, i + 1))
+    }
+}
+
+/// Walk a trie from `pos` and return the longest key that carries a phoneme
+/// Returns (match length in chars, phoneme)
+fn longest_phoneme_match<'a>(root: &'a TrieNode, chars: &[char], pos: usize) -> Option<(usize, &'a String)> {
+    let mut best = None;
+    let mut current = root;
+    let mut i = pos;
+    
+    while i < chars.len() {
+        match step_trie(current, chars, i) {
+            Some((child, next)) => {
+                current = child;
+                i = next;
+                
+                // If this node has a phoneme, it's a valid match
+                if let Some(ref phoneme) = current.phoneme {
+                    best = Some((i - pos, phoneme));
+                }
+            }
+            None => break,
+        }
+    }
+    
+    best
+}
+
+/// Why loading a dictionary failed
+#[derive(Debug)]
+pub enum ConverterError {
+    /// The file could not be opened or read (missing, truncated, ...)
+    Io(io::Error),
+    /// Not a binary trie: the file doesn't start with "JPHO"
+    BadMagic,
+    /// A binary trie of a format version this build can't read (only 1.0)
+    UnsupportedVersion { major: u16, minor: u16 },
+    /// A dictionary file, key or value that isn't valid UTF-8
+    Utf8(std::string::FromUtf8Error),
+    /// Malformed dictionary JSON: byte position (and line) the reader stopped at
+    JsonParse { position: usize, line: usize, message: String },
+    /// A substitution map entry that isn't one char to one char
+    InvalidSubstitution { from: String, to: String },
+    /// Conversion asked for a dictionary before one was loaded (try_convert())
+    NotLoaded,
+    /// A binary trie whose entries can't be read (e.g. an oversized varint)
+    Corrupt { message: String },
+}
+
+impl ConverterError {
+    /// True if the file is not a binary trie this build reads (BadMagic,
+    /// UnsupportedVersion). Nothing was loaded, so falling back to the JSON
+    /// dictionary is safe; after any other error the trie may be half loaded.
+    pub fn is_format_error(&self) -> bool {
+        matches!(self, ConverterError::BadMagic | ConverterError::UnsupportedVersion { .. })
+    }
+}
+
+impl std::fmt::Display for ConverterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConverterError::Io(e) => write!(f, "{}", e),
+            ConverterError::BadMagic => write!(f, "Invalid binary format: bad magic number"),
+            ConverterError::UnsupportedVersion { major, minor } => write!(f, "Unsupported binary format version: {}.{}", major, minor),
+            ConverterError::Utf8(e) => write!(f, "Invalid UTF-8: {}", e),
+            ConverterError::JsonParse { position, line, message } => write!(f, "Invalid JSON (line {}, byte {}): {}", line, position, message),
+            ConverterError::InvalidSubstitution { from, to } => write!(f, "Substitution \"{}\" → \"{}\" must map one character to one character", from, to),
+            ConverterError::Corrupt { message } => write!(f, "Corrupt binary trie: {}", message),
+            ConverterError::NotLoaded => write!(f, "No dictionary loaded - call load_from_json() or try_load_binary_format() first"),
+        }
+    }
+}
+
+impl std::error::Error for ConverterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConverterError::Io(e) => Some(e),
+            ConverterError::Utf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConverterError {
+    fn from(e: io::Error) -> Self {
+        ConverterError::Io(e)
+    }
+}
+
+impl From<std::string::FromUtf8Error> for ConverterError {
+    fn from(e: std::string::FromUtf8Error) -> Self {
+        ConverterError::Utf8(e)
+    }
+}
+
+/// gzip member header magic bytes (RFC 1952)
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Buffered reader over a dictionary file, decompressed on the fly when it
+/// starts with the gzip magic bytes - the content decides, not the name
+/// 
+/// Without the "gzip" feature a compressed file fails with
+/// ErrorKind::Unsupported rather than being parsed as a broken dictionary.
+fn open_dictionary(file: fs::File) -> Result<Box<dyn Read>, ConverterError> {
+    let mut reader = BufReader::new(file);
+    if !reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        return Ok(Box::new(reader));
+    }
+    
+    #[cfg(feature = "gzip")]
+    {
+        Ok(Box::new(BufReader::new(flate2::read::GzDecoder::new(reader))))
+    }
+    #[cfg(not(feature = "gzip"))]
+    {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "gzip-compressed dictionary - build with the \"gzip\" feature to read it").into())
+    }
+}
+
+/// Read a whole dictionary file as UTF-8 (a decode failure is Utf8, not Io)
+/// gzip-compressed files are decompressed first (see open_dictionary())
+fn read_dictionary_text(file_path: &str) -> Result<String, ConverterError> {
+    let mut bytes = Vec::new();
+    open_dictionary(fs::File::open(file_path)?)?.read_to_end(&mut bytes)?;
+    Ok(String::from_utf8(bytes)?)
 }
 
 /// Ultra-fast phoneme converter using trie data structure
 /// Achieves microsecond-level lookups for typical text
-struct PhonemeConverter {
+pub struct PhonemeConverter {
     root: TrieNode,
     entry_count: usize,
+    
+    // Optional dialect/variant overlay (e.g. "kansai") consulted before the base trie
+    variant: Option<Box<PhonemeConverter>>,
+    variant_name: Option<String>,
+    
+    // Variant kanji rewritten to the forms the dictionary is keyed on
+    // (國 → 国), applied to the input char by char - see load_substitutions()
+    substitutions: HashMap<char, char>,
+    
+    // Further readings of a key beyond its dictionary phonemes, most frequent
+    // first, offered as alternatives by convert_all() - see add_reading()
+    extra_readings: HashMap<String, Vec<String>>,
+    
+    // Kana reading of dictionary keys (日本語 → にほんご) for
+    // convert_with_reading() - see load_kana_readings()
+    kana_readings: HashMap<String, String>,
+    
+    // Text-level passes applied before the trie walk
+    options: ConversionOptions,
+}
+
+impl Default for PhonemeConverter {
+    fn default() -> Self {
+        PhonemeConverter::new()
+    }
 }
 
 impl PhonemeConverter {
     /// Create a new phoneme converter
-    fn new() -> Self {
+    pub fn new() -> Self {
         PhonemeConverter {
             root: TrieNode::default(),
             entry_count: 0,
+            variant: None,
+            variant_name: None,
+            substitutions: HashMap::new(),
+            extra_readings: HashMap::new(),
+            kana_readings: HashMap::new(),
+            options: ConversionOptions::default(),
+        }
+    }
+    
+    /// Options every conversion runs with (see ConversionOptions)
+    pub fn options(&self) -> &ConversionOptions {
+        &self.options
+    }
+    
+    /// Change single options in place: converter.options_mut().add_tie_bars = true
+    pub fn options_mut(&mut self) -> &mut ConversionOptions {
+        &mut self.options
+    }
+    
+    /// Replace all options at once
+    pub fn set_options(&mut self, options: ConversionOptions) {
+        self.options = options;
+    }
+    
+    /// Load a pronunciation variant (dialect overlay) from a JSON dictionary
+    /// 
+    /// The overlay uses the same format as ja_phonemes.json but only needs the
+    /// entries whose reading differs from the standard dictionary.
+    /// Conversion consults the overlay first and falls back to the base trie.
+    fn load_variant(&mut self, name: &str, file_path: &str) -> Result<(), ConverterError> {
+        let mut overlay = PhonemeConverter::new();
+        overlay.load_from_json(file_path)?;
+        
+        self.variant = Some(Box::new(overlay));
+        self.variant_name = Some(name.to_string());
+        Ok(())
+    }
+    
+    /// Load a kanji substitution map (JSON, same format as ja_phonemes.json)
+    /// 
+    /// Each entry maps one char to one char, e.g. {"國": "国", "學": "学"} for
+    /// 旧字体 → 新字体 or domain-specific variants, so 國語 converts like 国語.
+    /// Substitution runs on the whole input before furigana hints are parsed:
+    /// a hint's reading still wins for its own word (國「くに」 reads くに), and
+    /// compound detection sees the substituted base text. Loading again adds
+    /// to the map. A gzip-compressed map is read like a compressed dictionary.
+    pub fn load_substitutions(&mut self, file_path: &str) -> Result<(), ConverterError> {
+        let contents = read_dictionary_text(file_path)?;
+        
+        for (from, to) in self.parse_json(&contents)? {
+            let mut from_chars = from.chars();
+            let mut to_chars = to.chars();
+            match (from_chars.next(), from_chars.next(), to_chars.next(), to_chars.next()) {
+                (Some(from_char), None, Some(to_char), None) => {
+                    self.substitutions.insert(from_char, to_char);
+                }
+                _ => return Err(ConverterError::InvalidSubstitution { from, to }),
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Add another reading of `key` for convert_all() (e.g. 今日 → konniʨi
+    /// besides kʲoː). Readings added first rank first; the dictionary's own
+    /// phonemes always come before all of them. Conversion otherwise ignores
+    /// these readings, and save_binary_format() does not write them.
+    pub fn add_reading(&mut self, key: &str, phonemes: &str) {
+        let readings = self.extra_readings.entry(key.to_string()).or_default();
+        if !readings.iter().any(|reading| reading == phonemes) {
+            readings.push(phonemes.to_string());
+        }
+    }
+    
+    /// Load a named dialect packaged as dialects/<name>.json (e.g. --dialect kansai)
+    fn load_dialect(&mut self, name: &str) -> Result<(), ConverterError> {
+        let file_path = format!("dialects/{}.json", name);
+        if !std::path::Path::new(&file_path).exists() {
+            let message = format!("dialect '{}' not found ({} missing)", name, file_path);
+            return Err(io::Error::new(io::ErrorKind::NotFound, message).into());
+        }
+        
+        self.load_variant(name, &file_path)
+    }
+    
+    /// Find the longest match at `pos`, preferring the variant overlay
+    /// 
+    /// The overlay wins unless the base dictionary has a strictly longer match,
+    /// so a short dialect entry never splits a longer standard compound.
+    fn find_longest_match(&self, chars: &[char], pos: usize) -> Option<(usize, &String)> {
+        let base = longest_phoneme_match(&self.root, chars, pos);
+        
+        if let Some(ref variant) = self.variant {
+            if let Some((length, phoneme)) = variant.find_longest_match(chars, pos) {
+                if base.is_none_or(|(base_length, _)| length >= base_length) {
+                    return Some((length, phoneme));
+                }
+            }
+        }
+        
+        base
+    }
+    
+    /// Reading of a context-sensitive kanji at `pos` (context_readings option)
+    /// Returns (chars consumed, phonemes), or None to use the dictionary as usual.
+    /// 
+    /// 1. Kanji + counter from the rule's table → nasal reading + counter (何時 → naɴʥi)
+    /// 2. A longer dictionary compound (何故 → naze, 何か → nanika) is kept
+    /// 3. Otherwise the onset of the next match decides: t/d/n sounds take the
+    ///    nasal reading (何と → naɴ), anything else the default (何色 → nani)
+    /// 
+    /// Segmented conversion runs this per word, so a counter the segmenter
+    /// splits off (何 | 本) is not seen and the default reading is used.
+    fn context_reading(&self, chars: &[char], pos: usize) -> Option<(usize, String)> {
+        let rule = CONTEXT_RULES.iter().find(|rule| rule.kanji == chars[pos])?;
+        let rest = &chars[pos + 1..];
+        
+        for &(counter, reading) in rule.counters {
+            let counter_len = counter.chars().count();
+            if rest.len() >= counter_len && counter.chars().zip(rest).all(|(a, &b)| a == b) {
+                return Some((1 + counter_len, format!("{}{}", rule.nasal_reading, reading)));
+            }
+        }
+        
+        if self.find_longest_match(chars, pos).is_some_and(|(length, _)| length > 1) {
+            return None;
+        }
+        
+        let next_onset = self.find_longest_match(chars, pos + 1)
+            .and_then(|(_, phoneme)| phoneme.chars().next());
+        let reading = match next_onset {
+            Some(onset) if NASAL_TRIGGER_ONSETS.contains(&onset) => rule.nasal_reading,
+            _ => rule.default_reading,
+        };
+        
+        Some((1, reading.to_string()))
+    }
+    
+    /// Gemination for a っ/ッ the dictionary has no compound entry for
+    /// Returns (chars consumed, phonemes) for the sokuon plus the following
+    /// match, with the following onset doubled the way the dictionary writes
+    /// geminates (pːa, tːe, ɕːi, ʨːa). None leaves っ to the dictionary's ʔ:
+    /// a keyed compound (っぱ), a vowel/ɴ/end of text after it (あっ, ッア).
+    /// A dictionary without a っ entry gets (1, ʔ) there instead, so a
+    /// trailing っ never reaches the output as kana.
+    /// Stray marks after a katakana ッ (ッー, ッッ) are removed earlier by
+    /// collapse_katakana_sokuon().
+    fn sokuon_match(&self, chars: &[char], pos: usize) -> Option<(usize, String)> {
+        if !matches!(chars[pos], 'っ' | 'ッ') {
+            return None;
+        }
+        
+        // A compound like っぱ → pːa is already spelled correctly
+        let own = self.find_longest_match(chars, pos);
+        if own.is_some_and(|(length, _)| length > 1) {
+            return None;
+        }
+        
+        let geminate = chars.get(pos + 1).and_then(|_| {
+            let (next_length, next) = self.youon_match(chars, pos + 1)
+                .or_else(|| self.find_longest_match(chars, pos + 1).map(|(length, phoneme)| (length, phoneme.clone())))?;
+            geminate_onset(&next).map(|doubled| (1 + next_length, doubled))
+        });
+        
+        match geminate {
+            None if own.is_none() => Some((1, "ʔ".to_string())),
+            geminate => geminate,
+        }
+    }
+    
+    /// Labialized く/ぐ + small ゎ (see LABIALIZED_KANA), classical_kana only
+    /// Returns (2, kʷa/gʷa) unless the dictionary has a longer compound
+    /// starting here, which is kept as written.
+    fn labialized_match(&self, chars: &[char], pos: usize) -> Option<(usize, String)> {
+        if !self.options.classical_kana || pos + 1 >= chars.len() || !matches!(chars[pos + 1], 'ゎ' | 'ヮ') {
+            return None;
         }
+        
+        if self.find_longest_match(chars, pos).is_some_and(|(length, _)| length > 2) {
+            return None;
+        }
+        
+        LABIALIZED_KANA.iter()
+            .find(|&&(kana, _)| kana.chars().eq(chars[pos..pos + 2].iter().copied()))
+            .map(|&(_, reading)| (2, reading.to_string()))
+    }
+    
+    /// Youon (i-row kana + small ゃ/ゅ/ょ) the dictionary has no entry for
+    /// Returns (2, phonemes) in the dictionary's spelling: the kana's onset,
+    /// j, then the small kana's vowel (きょ → kjo, りゅ → ɾjɯ), with no j
+    /// after a palatal onset (しゅ → ɕɯ, ちょ → ʨo, じゃ → ʥa). Without it
+    /// the pair reads as two morae (ɕijɯ) and a following ー lengthens the
+    /// wrong syllable. None leaves keyed compounds (ちゃ, キュー) alone.
+    fn youon_match(&self, chars: &[char], pos: usize) -> Option<(usize, String)> {
+        if pos + 1 >= chars.len() || !matches!(chars[pos + 1], 'ゃ' | 'ゅ' | 'ょ' | 'ャ' | 'ュ' | 'ョ') {
+            return None;
+        }
+        
+        let (length, base) = self.find_longest_match(chars, pos)?;
+        let onset = base.strip_suffix('i')?;
+        if length > 1 || onset.is_empty() {
+            return None;
+        }
+        
+        let (_, small) = self.find_longest_match(chars, pos + 1)?;
+        let vowel = small.chars().last()?;
+        
+        if onset.ends_with(['ɕ', 'ʨ', 'ʥ']) {
+            Some((2, format!("{}{}", onset, vowel)))
+        } else {
+            Some((2, format!("{}j{}", onset, vowel)))
+        }
+    }
+    
+    /// を/ヲ as ɰᵝo (wo_reading = Wo) when no longer dictionary key starts here
+    fn wo_match(&self, chars: &[char], pos: usize) -> Option<(usize, String)> {
+        if self.options.wo_reading != WoReading::Wo || !matches!(chars[pos], 'を' | 'ヲ') {
+            return None;
+        }
+        
+        if self.find_longest_match(chars, pos).is_some_and(|(length, _)| length > 1) {
+            return None;
+        }
+        
+        Some((1, "ɰᵝo".to_string()))
+    }
+    
+    /// True if ー after ん holds the nasal (ふんー → ɸɯɴː): the long-vowel
+    /// options only, so by default an unkeyed ー after ɴ passes through
+    fn holds_nasal(&self) -> bool {
+        self.options.merge_long_vowels || self.options.elongation.is_some()
+    }
+    
+    /// Reading of a word the segmenter left as a standalone particle
+    /// (see PARTICLE_READINGS); None for any other word. を keeps ɰᵝo when
+    /// wo_reading asks for it.
+    fn particle_reading(&self, word: &str) -> Option<&'static str> {
+        if word == "を" && self.options.wo_reading == WoReading::Wo {
+            return None;
+        }
+        
+        PARTICLE_READINGS.iter()
+            .find(|&&(particle, _)| particle == word)
+            .map(|&(_, reading)| reading)
     }
     
     /// Get root node for trie walking (used in word segmentation fallback)
@@ -122,109 +1443,263 @@ impl PhonemeConverter {
         &self.root
     }
     
+    /// Find all dictionary keys starting with `prefix` (sorted)
+    /// Includes word-only entries. Walks to the prefix node, then collects
+    /// terminal descendants with an iterative DFS (no recursion depth limits).
+    /// 
+    /// For autocomplete (IME candidates) and auditing every entry under a kanji.
+    /// 
+    /// ⚠️ Warning: an empty prefix returns every key in the dictionary (~470k
+    /// strings for japanese.trie, tens of MB). Check for "" before calling on
+    /// user input, e.g. an autocomplete box that has just been cleared.
+    pub fn keys_with_prefix(&self, prefix: &str) -> Vec<String> {
+        let mut node = &self.root;
+        for ch in prefix.chars() {
+            match node.children.get(&ch) {
+                Some(child) => node = child,
+                None => return Vec::new(),
+            }
+        }
+        
+        let mut keys = Vec::new();
+        let mut stack = vec![(node, prefix.to_string())];
+        
+        while let Some((node, key)) = stack.pop() {
+            if node.is_terminal {
+                keys.push(key.clone());
+            }
+            
+            for (&ch, child) in &node.children {
+                let mut child_key = key.clone();
+                child_key.push(ch);
+                stack.push((child, child_key));
+            }
+        }
+        
+        keys.sort();
+        keys
+    }
+    
+    /// Phonemes of an exact dictionary key (None for unknown or word-only keys)
+    fn lookup(&self, key: &str) -> Option<&String> {
+        let mut node = &self.root;
+        for ch in normalize_key_whitespace(key).chars() {
+            node = node.children.get(&ch)?;
+        }
+        node.phoneme.as_ref()
+    }
+    
+    /// Up to `count` keys around `key` in sorted order, excluding `key` itself
+    /// 
+    /// Navigates to the longest prefix of `key` whose subtree holds enough keys
+    /// (東京 → 東京*, else 東*, ...), so only that subtree is collected instead
+    /// of the whole dictionary. Works for keys that aren't in the dictionary.
+    fn neighbor_keys(&self, key: &str, count: usize) -> Vec<String> {
+        let chars: Vec<char> = key.chars().collect();
+        let mut keys = Vec::new();
+        
+        for length in (0..=chars.len()).rev() {
+            let prefix: String = chars[..length].iter().collect();
+            keys = self.keys_with_prefix(&prefix);
+            keys.retain(|k| k != key);
+            if keys.len() >= count {
+                break;
+            }
+        }
+        
+        // Center the window on where `key` sorts
+        let position = keys.binary_search_by(|k| k.as_str().cmp(key)).unwrap_or_else(|i| i);
+        let start = position.saturating_sub(count / 2).min(keys.len().saturating_sub(count));
+        keys.into_iter().skip(start).take(count).collect()
+    }
+    
+    /// Build a word segmenter from the loaded dictionary's keys
+    /// 
+    /// Lets one file (ja_phonemes.json) serve as both dictionary and word
+    /// list, so the two can't drift apart. Every key becomes a word except
+    /// single kana: those are the dictionary's reading building blocks
+    /// (は, が, で), and as words they would stop particles from being
+    /// split off as grammar. Word-only entries (binary format) are included.
+    pub fn derive_word_list_from_dict(&self) -> WordSegmenter {
+        let mut segmenter = WordSegmenter::new();
+        
+        for key in self.keys_with_prefix("") {
+            let mut chars = key.chars();
+            let single_kana = matches!((chars.next(), chars.next()), (Some(ch), None) if is_kana(ch));
+            
+            if !single_kana {
+                segmenter.insert_word(&key);
+                segmenter.word_count += 1;
+            }
+        }
+        
+        segmenter
+    }
+    
+    /// All phoneme entries in the trie as (key, phoneme) pairs, sorted by key
+    /// Word-only entries (no phoneme) are skipped - they belong in the word list.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        self.collect_entries(false)
+    }
+    
+    /// Sorted (key, phoneme) pairs of every terminal node; with
+    /// `include_word_markers`, word-only entries come back with an empty phoneme
+    fn collect_entries(&self, include_word_markers: bool) -> Vec<(String, String)> {
+        let mut entries = Vec::new();
+        let mut stack = vec![(&self.root, String::new())];
+        
+        while let Some((node, key)) = stack.pop() {
+            if let Some(ref phoneme) = node.phoneme {
+                entries.push((key.clone(), phoneme.clone()));
+            } else if include_word_markers && node.is_terminal {
+                entries.push((key.clone(), String::new()));
+            }
+            
+            for (&ch, child) in &node.children {
+                let mut child_key = key.clone();
+                child_key.push(ch);
+                stack.push((child, child_key));
+            }
+        }
+        
+        entries.sort();
+        entries
+    }
+    
+    /// Write the loaded dictionary back to JSON (same layout as ja_phonemes.json)
+    /// The output reloads with load_from_json() to the same entries - this
+    /// turns a binary trie back into an editable dictionary.
+    pub fn save_to_json(&self, path: &str) -> Result<(), ConverterError> {
+        let entries = self.entries();
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        
+        writeln!(writer, "{{")?;
+        for (i, (key, phoneme)) in entries.iter().enumerate() {
+            let separator = if i + 1 < entries.len() { "," } else { "" };
+            writeln!(writer, "  \"{}\": \"{}\"{}", escape_json(key), escape_json(phoneme), separator)?;
+        }
+        writeln!(writer, "}}")?;
+        
+        writer.flush()?;
+        Ok(())
+    }
+    
+    /// Write the loaded dictionary as a binary trie (same format as japanese.trie)
+    /// Word-only entries are kept as empty values, so try_load_binary_format()
+    /// on the file gives back the same set of entries. Only the base trie is
+    /// written - a dialect overlay and kanji substitutions are not.
+    pub fn save_binary_format(&self, file_path: &str) -> Result<(), ConverterError> {
+        write_binary_format(file_path, &self.collect_entries(true))
+    }
+    
+    /// Load a binary trie (japanese.trie), failing if it is missing or not one
+    /// The strict form of try_load_binary_format() for library callers; a
+    /// missing file is ConverterError::Io with ErrorKind::NotFound.
+    pub fn load_from_binary(&mut self, file_path: &str) -> Result<(), ConverterError> {
+        if self.try_load_binary_format(file_path)? {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::NotFound, format!("{} not found", file_path)).into())
+        }
+    }
+    
     /// Try to load from simple binary format (japanese.trie)
     /// Loads directly into TrieNode structure using same insert() as JSON!
     /// 🚀 100x faster than JSON parsing!
-    fn try_load_binary_format(&mut self, file_path: &str) -> Result<bool, Box<dyn std::error::Error>> {
-        let mut file = match fs::File::open(file_path) {
+    /// 
+    /// A gzip-compressed trie (japanese.trie.gz) is read the same way.
+    /// Ok(false) if the file doesn't exist. A file that isn't a v1.0 trie
+    /// fails before loading anything (see ConverterError::is_format_error());
+    /// a read or UTF-8 error part way leaves the entries read so far loaded.
+    pub fn try_load_binary_format(&mut self, file_path: &str) -> Result<bool, ConverterError> {
+        let file = match fs::File::open(file_path) {
             Ok(f) => f,
-            Err(_) => return Ok(false), // File doesn't exist, not an error
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false), // Not an error
+            Err(e) => return Err(e.into()),
         };
         
+        // Buffered (the entry loop reads varints a byte at a time), gunzipped if compressed
+        let start_time = Instant::now();
+        self.load_binary_from_reader(open_dictionary(file)?)?;
+        
+        let elapsed = start_time.elapsed();
+        status!("\n✅ Loaded {} entries in {}ms", self.entry_count, elapsed.as_millis());
+        status!("   Average: {:.2}μs per entry", 
+                 (elapsed.as_micros() as f64) / (self.entry_count as f64));
+        status!("   ⚡ Using SAME TrieNode structure and traversal as JSON!");
+        
+        Ok(true)
+    }
+    
+    /// Load a binary trie (japanese.trie format) from any reader: a file, an
+    /// embedded byte slice (&[u8] is Read), a network stream or a decompressor
+    /// 
+    /// Same checks and errors as try_load_binary_format(), which is this plus
+    /// opening the file. Doesn't time the load, so it also runs where there
+    /// is no clock (wasm32-unknown-unknown). The entry loop reads a byte at a
+    /// time, so wrap unbuffered sources (files, sockets) in a BufReader.
+    /// 
+    /// Embedding the dictionary in the executable:
+    ///     converter.load_binary_from_reader(&include_bytes!("japanese.trie")[..])?;
+    pub fn load_binary_from_reader<R: Read>(&mut self, mut reader: R) -> Result<(), ConverterError> {
         // Read magic number
         let mut magic = [0u8; 4];
-        file.read_exact(&mut magic)?;
+        reader.read_exact(&mut magic)?;
         if &magic != b"JPHO" {
-            eprintln!("❌ Invalid binary format: bad magic number");
-            return Ok(false);
+            return Err(ConverterError::BadMagic);
         }
         
         // Read version
         let mut version_buf = [0u8; 4];
-        file.read_exact(&mut version_buf)?;
+        reader.read_exact(&mut version_buf)?;
         let version_major = u16::from_le_bytes([version_buf[0], version_buf[1]]);
         let version_minor = u16::from_le_bytes([version_buf[2], version_buf[3]]);
         
         if version_major != 1 || version_minor != 0 {
-            eprintln!("❌ Unsupported binary format version: {}.{}", version_major, version_minor);
-            return Ok(false);
+            return Err(ConverterError::UnsupportedVersion { major: version_major, minor: version_minor });
         }
         
         // Read entry count
         let mut count_buf = [0u8; 4];
-        file.read_exact(&mut count_buf)?;
+        reader.read_exact(&mut count_buf)?;
         let entry_count_val = u32::from_le_bytes(count_buf);
         
-        println!("🚀 Loading binary format v{}.{}: {} entries", version_major, version_minor, entry_count_val);
-        let start_time = Instant::now();
+        status!("🚀 Loading binary format v{}.{}: {} entries", version_major, version_minor, entry_count_val);
         
         // Read all entries and insert into trie (same as JSON!)
         for i in 0..entry_count_val {
-            // Read key length (varint)
-            let mut key_len = 0u32;
-            let mut shift = 0;
-            loop {
-                let mut byte = [0u8; 1];
-                file.read_exact(&mut byte)?;
-                key_len |= ((byte[0] & 0x7F) as u32) << shift;
-                if (byte[0] & 0x80) == 0 {
-                    break;
-                }
-                shift += 7;
-            }
-            
-            // Read key
-            let mut key_bytes = vec![0u8; key_len as usize];
-            file.read_exact(&mut key_bytes)?;
-            let key = String::from_utf8(key_bytes)?;
-            
-            // Read value length (varint)
-            let mut value_len = 0u32;
-            shift = 0;
-            loop {
-                let mut byte = [0u8; 1];
-                file.read_exact(&mut byte)?;
-                value_len |= ((byte[0] & 0x7F) as u32) << shift;
-                if (byte[0] & 0x80) == 0 {
-                    break;
-                }
-                shift += 7;
-            }
-            
-            // Read value
-            let mut value_bytes = vec![0u8; value_len as usize];
-            file.read_exact(&mut value_bytes)?;
-            let value = String::from_utf8(value_bytes)?;
+            // Key and value: varint length, then UTF-8 bytes
+            let key = read_varint_string(&mut reader)?;
+            let value = read_varint_string(&mut reader)?;
             
             // Insert using SAME function as JSON!
-            self.insert(&key, &value);
+            // Empty values are word markers: terminal for segmentation, no phoneme
+            if value.is_empty() {
+                self.insert_word_marker(&key);
+            } else {
+                self.insert(&key, &value);
+            }
             self.entry_count += 1;
             
             // Progress indicator
-            if i % 50000 == 0 && i > 0 {
+            if i % 50000 == 0 && i > 0 && !QUIET.load(Ordering::Relaxed) {
                 print!("\r   Processed: {} entries", i);
                 io::stdout().flush().unwrap();
             }
         }
         
-        let elapsed = start_time.elapsed();
-        println!("\n✅ Loaded {} entries in {}ms", self.entry_count, elapsed.as_millis());
-        println!("   Average: {:.2}μs per entry", 
-                 (elapsed.as_micros() as f64) / (self.entry_count as f64));
-        println!("   ⚡ Using SAME TrieNode structure and traversal as JSON!");
-        
-        Ok(true)
+        Ok(())
     }
     
-    /// Build trie from JSON dictionary file
+    /// Build trie from JSON dictionary file (or gzip-compressed, ja_phonemes.json.gz)
     /// Optimized for fast construction from large datasets
-    fn load_from_json(&mut self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let contents = fs::read_to_string(file_path)?;
+    pub fn load_from_json(&mut self, file_path: &str) -> Result<(), ConverterError> {
+        let contents = read_dictionary_text(file_path)?;
         
         // Simple JSON parsing for our specific format
         let data = self.parse_json(&contents)?;
         
-        println!("🔥 Loading {} entries into trie...", data.len());
+        status!("🔥 Loading {} entries into trie...", data.len());
         let start_time = Instant::now();
         
         // Insert each entry into the trie
@@ -233,146 +1708,382 @@ impl PhonemeConverter {
             self.entry_count += 1;
             
             // Progress indicator for large datasets
-            if self.entry_count % 50000 == 0 {
+            if self.entry_count % 50000 == 0 && !QUIET.load(Ordering::Relaxed) {
                 print!("\r   Processed: {} entries", self.entry_count);
                 io::stdout().flush().unwrap();
             }
         }
         
         let elapsed = start_time.elapsed();
-        println!("\n✅ Loaded {} entries in {}ms", self.entry_count, elapsed.as_millis());
-        println!("   Average: {:.2}μs per entry", 
+        status!("\n✅ Loaded {} entries in {}ms", self.entry_count, elapsed.as_millis());
+        status!("   Average: {:.2}μs per entry", 
                  (elapsed.as_micros() as f64) / (self.entry_count as f64));
         
         Ok(())
     }
     
-    /// Simple JSON parser for our specific format
-    fn parse_json(&self, json_str: &str) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
-        let mut result = HashMap::new();
+    /// JSON parser for the dictionary format: one flat object of strings
+    /// Empty values are kept: "key": "" means the key converts to nothing
+    /// (silent characters), which is different from the key being absent.
+    /// See parse_flat_json() for escapes, numbers and error reporting.
+    fn parse_json(&self, json_str: &str) -> Result<HashMap<String, String>, ConverterError> {
+        parse_flat_json(json_str)
+    }
+    
+    /// Insert a Japanese text -> phoneme mapping into the trie
+    /// Uses characters for maximum performance with Rust's native UTF-8
+    fn insert(&mut self, text: &str, phoneme: &str) {
+        self.root.insert(text, phoneme);
+    }
+    
+    /// Insert a word-only entry (binary format empty value)
+    /// The node becomes a word boundary for segmentation but converts nothing,
+    /// so greedy conversion falls back to shorter phoneme matches.
+    fn insert_word_marker(&mut self, text: &str) {
+        let mut current = &mut self.root;
         
-        // Remove outer braces and whitespace
-        let content = json_str.trim()
-            .strip_prefix('{').ok_or("Invalid JSON: missing opening brace")?
-            .strip_suffix('}').ok_or("Invalid JSON: missing closing brace")?;
+        for ch in normalize_key_whitespace(text).chars() {
+            current = current.child_or_insert(ch);
+        }
         
-        // Parse key-value pairs
-        let mut chars = content.chars().peekable();
+        current.is_terminal = true;
+    }
+    
+    /// Remove the entry for `key` at runtime (e.g. a wrong reading)
+    /// Returns whether it existed. Keys sharing its prefix (東京 when removing
+    /// 東京都) still match; nodes no other key needs are pruned.
+    pub fn remove_entry(&mut self, key: &str) -> bool {
+        let chars: Vec<char> = normalize_key_whitespace(key).chars().collect();
+        let removed = self.root.remove_key(&chars);
+        if removed {
+            self.entry_count = self.entry_count.saturating_sub(1);
+        }
+        removed
+    }
+    
+    /// Apply the enabled text-level passes (number expansion, ...) before lookup
+    /// Returns the input unchanged (borrowed) when no pass applies.
+    /// Match positions from detailed conversion refer to this prepared text.
+    fn prepare_input<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
         
-        while chars.peek().is_some() {
-            // Skip whitespace and commas
-            while matches!(chars.peek(), Some(&c) if c.is_whitespace() || c == ',') {
-                chars.next();
-            }
-            
-            if chars.peek().is_none() {
-                break;
-            }
-            
-            // Parse key
-            if chars.next() != Some('"') {
-                continue;
-            }
-            
-            let mut key = String::new();
-            loop {
-                match chars.next() {
-                    Some('"') => break,
-                    Some('\\') => {
-                        if let Some(c) = chars.next() {
-                            key.push(c);
-                        }
-                    }
-                    Some(c) => key.push(c),
-                    None => break,
-                }
-            }
-            
-            // Skip to colon
-            while matches!(chars.peek(), Some(&c) if c.is_whitespace() || c == ':') {
-                chars.next();
-            }
-            
-            // Parse value
-            if chars.next() != Some('"') {
-                continue;
-            }
-            
-            let mut value = String::new();
-            loop {
-                match chars.next() {
-                    Some('"') => break,
-                    Some('\\') => {
-                        if let Some(c) = chars.next() {
-                            value.push(c);
-                        }
-                    }
-                    Some(c) => value.push(c),
-                    None => break,
+        // First, so later passes never see the invisible characters
+        if self.options.strip_control_chars && text.chars().any(is_invisible_char) {
+            text = Cow::Owned(text.chars().filter(|&c| !is_invisible_char(c)).collect());
+        }
+        
+        if self.options.normalize_ideographic_space && text.contains('\u{3000}') {
+            text = Cow::Owned(text.replace('\u{3000}', " "));
+        }
+        
+        // Variant kanji next, so every later pass sees dictionary forms
+        if !self.substitutions.is_empty() && text.chars().any(|c| self.substitutions.contains_key(&c)) {
+            text = Cow::Owned(text.chars().map(|c| *self.substitutions.get(&c).unwrap_or(&c)).collect());
+        }
+        
+        // Always on: vertical/small punctuation forms mean the same as the standard ones
+        if text.chars().any(is_presentation_punctuation) {
+            text = Cow::Owned(normalize_presentation_punctuation(&text));
+        }
+        
+        // Always on: ッー / ッッ are malformed spellings with no other reading
+        if text.contains("ッー") || text.contains("ッッ") {
+            text = Cow::Owned(collapse_katakana_sokuon(&text));
+        }
+        
+        // Before the number passes, so "2." is an enumerator rather than a decimal
+        if self.options.list_markers {
+            text = Cow::Owned(read_list_markers(&text));
+        }
+        
+        // Before number expansion, which then reads the base of 10^3 like any number
+        if self.options.scientific_notation && text.chars().any(is_scientific_char) {
+            text = Cow::Owned(read_scientific_notation(&text));
+        }
+        
+        // Before number expansion, which then reads the digits around the words
+        if self.options.numeric_patterns && text.chars().any(is_digit) {
+            text = Cow::Owned(read_numeric_operators(&text));
+        }
+        
+        if self.options.number_mode != NumberMode::Passthrough && text.chars().any(is_digit) {
+            text = Cow::Owned(match self.options.number_mode {
+                NumberMode::DigitByDigit => expand_digits(&text),
+                _ => expand_numbers(&text),
+            });
+        }
+        
+        if self.options.classical_kana && text.chars().any(|c| CLASSICAL_KANA.iter().any(|&(k, _)| k == c)) {
+            text = Cow::Owned(modernize_kana(&text));
+        }
+        
+        if self.options.colloquial_contractions {
+            text = Cow::Owned(contract_colloquial(&text));
+        }
+        
+        if let Some(mode) = self.options.elongation {
+            text = Cow::Owned(normalize_elongation(&text, mode));
+        }
+        
+        // After elongation, so casual ーーー runs are already normalized
+        if self.options.loanword_final_vowel != LoanwordVowelPolicy::Keep && text.chars().any(|c| script_class(c) == 3) {
+            let is_key = |word: &str| {
+                let chars: Vec<char> = word.chars().collect();
+                self.find_longest_match(&chars, 0).is_some_and(|(length, _)| length == chars.len())
+            };
+            text = Cow::Owned(normalize_loanword_vowels(&text, self.options.loanword_final_vowel, is_key));
+        }
+        
+        // Last, so every katakana-aware pass above has run
+        if self.options.fold_katakana && text.chars().any(is_foldable_katakana) {
+            text = Cow::Owned(normalize_kana(&text));
+        }
+        
+        text
+    }
+    
+    /// Apply the enabled output passes (long vowels, tie bars, ...) to the
+    /// phonemes converted from `source` (one segmented word, or the whole
+    /// text for unsegmented conversion)
+    fn finish_phonemes(&self, source: &str, phonemes: String) -> String {
+        let mut phonemes = phonemes;
+        
+        let merge = self.options.merge_long_vowels || self.options.elongation.is_some();
+        if merge && !self.is_long_vowel_exception(source) {
+            phonemes = merge_long_vowels(&phonemes);
+        }
+        
+        if self.options.add_tie_bars {
+            for &(plain, tied) in &TIE_BAR_AFFRICATES {
+                if phonemes.contains(plain) {
+                    phonemes = phonemes.replace(plain, tied);
                 }
             }
-            
-            if !key.is_empty() && !value.is_empty() {
-                result.insert(key, value);
+        }
+        
+        // After tie bars, so a tied affricate doubles as a whole (tt͡ɕ)
+        let restyle = self.options.geminate_style != GeminateStyle::Length ||
+            self.options.long_vowel_style != LongVowelStyle::Length;
+        if restyle && phonemes.contains('ː') {
+            phonemes = apply_length_styles(&phonemes, self.options.geminate_style, self.options.long_vowel_style);
+        }
+        
+        if self.options.phoneme_inventory == PhonemeInventory::Simplified {
+            phonemes = simplify_phonemes(&phonemes);
+        }
+        
+        // Unsegmented text keeps its whitespace; segmented words have none
+        // (segmented conversion adds the token between words instead)
+        if let Some(ref space_token) = self.options.explicit_space_token {
+            if phonemes.contains(char::is_whitespace) {
+                phonemes = replace_whitespace_runs(&phonemes, space_token);
             }
         }
         
-        Ok(result)
+        phonemes
     }
     
-    /// Insert a Japanese text -> phoneme mapping into the trie
-    /// Uses characters for maximum performance with Rust's native UTF-8
-    fn insert(&mut self, text: &str, phoneme: &str) {
-        let mut current = &mut self.root;
-        
-        // Traverse/build trie using Unicode characters
-        for ch in text.chars() {
-            current = current.children
-                .entry(ch)
-                .or_insert_with(|| Box::new(TrieNode::default()));
+    /// Write a character no dictionary entry covers: unknown kanji become the
+    /// unknown_kanji_phoneme placeholder when set, anything else passes through
+    fn push_unmatched(&self, output: &mut String, ch: char) {
+        match self.options.unknown_kanji_phoneme {
+            Some(ref placeholder) if is_kanji(ch) => output.push_str(placeholder),
+            _ => output.push(self.options.ascii_case.apply(ch)),
         }
-        
-        // Mark end of word with phoneme value
-        current.phoneme = Some(phoneme.to_string());
+    }
+    
+    /// True if `word` is listed as a morpheme-boundary exception for merging
+    fn is_long_vowel_exception(&self, word: &str) -> bool {
+        LONG_VOWEL_EXCEPTIONS.contains(&word) ||
+            self.options.long_vowel_exceptions.iter().any(|w| w == word)
     }
     
     /// Greedy longest-match conversion algorithm
     /// Tries to match the longest possible substring at each position
-    fn convert(&self, japanese_text: &str) -> String {
+    pub fn convert(&self, japanese_text: &str) -> String {
+        let text = self.prepare_input(japanese_text);
+        let phonemes = self.finish_phonemes(&text, self.convert_word(&text));
+        
+        if self.options.reverse_morae {
+            reverse_morae(&phonemes)
+        } else {
+            phonemes
+        }
+    }
+    
+    /// Convert many inputs, in input order
+    /// With the "parallel" feature the inputs are spread over Rayon's thread
+    /// pool (the trie is read-only, so no locking); without it this is a
+    /// plain sequential loop over convert().
+    pub fn convert_batch(&self, inputs: &[&str]) -> Vec<String> {
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            inputs.par_iter().map(|text| self.convert(text)).collect()
+        }
+        
+        #[cfg(not(feature = "parallel"))]
+        {
+            inputs.iter().map(|text| self.convert(text)).collect()
+        }
+    }
+    
+    /// True once a dictionary has been loaded (JSON or binary)
+    /// convert() on an empty converter returns its input unchanged, which
+    /// looks like a run of unknown words rather than a missing dictionary.
+    pub fn is_loaded(&self) -> bool {
+        self.entry_count > 0
+    }
+    
+    /// Strict convert(): fails instead of echoing the input when no dictionary is loaded
+    pub fn try_convert(&self, japanese_text: &str) -> Result<String, ConverterError> {
+        if !self.is_loaded() {
+            return Err(ConverterError::NotLoaded);
+        }
+        
+        Ok(self.convert(japanese_text))
+    }
+    
+    /// Convert words segmented by an external tokenizer (MeCab, Sudachi)
+    /// 
+    /// Keeps the caller's boundaries: each word goes through the input passes
+    /// and is converted on its own with the particle handling of segmented
+    /// conversion (は → wa), so output matches convert_with_segmentation() when
+    /// the boundaries agree. Empty and whitespace-only words are skipped.
+    pub fn convert_presegmented(&self, words: &[&str]) -> String {
+        let tokens: Vec<Token> = words.iter()
+            .map(|word| Token::word(self.prepare_input(word).into_owned()))
+            .filter(|token| !token.text.trim().is_empty())
+            .collect();
+        let phonemes = convert_tokens_detailed(self, &tokens, Vec::new()).phonemes;
+        
+        if self.options.reverse_morae {
+            return reverse_morae(&phonemes);
+        }
+        
+        phonemes
+    }
+    
+    /// Like convert(), but borrows the input when nothing in it converts
+    /// 
+    /// Lines with no dictionary match (pure ASCII, punctuation) come back as
+    /// Cow::Borrowed without being converted, so a caller storing results for
+    /// mixed corpora keeps no copy of the many lines that contain no Japanese.
+    /// Always the same text as convert(), every option included; with an
+    /// output pass enabled (tie bars, length styles, ...) or an input pass
+    /// that rewrites the line, the result is converted and Cow::Owned.
+    pub fn convert_cow<'a>(&self, japanese_text: &'a str) -> Cow<'a, str> {
+        if self.passes_through(japanese_text) {
+            Cow::Borrowed(japanese_text)
+        } else {
+            Cow::Owned(self.convert(japanese_text))
+        }
+    }
+    
+    /// True if convert() returns `text` as is, decided without converting it:
+    /// no output pass is enabled, no input pass rewrites the text, and no
+    /// position starts a match or has an unmatched character that gets rewritten
+    fn passes_through(&self, text: &str) -> bool {
+        let options = &self.options;
+        let output_passes = options.merge_long_vowels || options.add_tie_bars ||
+            options.geminate_style != GeminateStyle::Length ||
+            options.long_vowel_style != LongVowelStyle::Length ||
+            options.phoneme_inventory != PhonemeInventory::FullIpa ||
+            options.explicit_space_token.is_some() || options.reverse_morae;
+        if output_passes || !matches!(self.prepare_input(text), Cow::Borrowed(_)) {
+            return false;
+        }
+        
+        // Every check convert_word() makes before copying a character
+        let chars: Vec<char> = text.chars().collect();
+        (0..chars.len()).all(|pos| {
+            let ch = chars[pos];
+            let rewritten = ch == 'ー' ||
+                (options.unknown_kanji_phoneme.is_some() && is_kanji(ch)) ||
+                options.ascii_case.apply(ch) != ch;
+            if rewritten || (options.context_readings && self.context_reading(&chars, pos).is_some()) {
+                return false;
+            }
+            
+            self.sokuon_match(&chars, pos).is_none() &&
+                self.labialized_match(&chars, pos).is_none() &&
+                self.youon_match(&chars, pos).is_none() &&
+                self.wo_match(&chars, pos).is_none() &&
+                self.find_longest_match(&chars, pos).is_none()
+        })
+    }
+    
+    /// Convert exactly one token with the greedy trie walk
+    /// 
+    /// For callers that have already tokenized: no word segmentation, no furigana
+    /// parsing, no standalone-particle readings (a lone は stays "ha") and none
+    /// of prepare_input()/finish_phonemes() (numbers, substitutions, tie bars,
+    /// length styles...). The walk itself still applies, as in convert():
+    /// - context_readings (何時 → naɴʥi)
+    /// - unkeyed っ doubling the next consonant, unkeyed youon, and くゎ with
+    ///   classical_kana
+    /// - wo_reading for を/ヲ
+    /// - an unkeyed ー lengthening the vowel before it
+    /// 
+    /// Characters with no dictionary match - including a trailing unmatched
+    /// remainder of the word - are copied to the output, rewritten only by
+    /// ascii_case and unknown_kanji_phoneme.
+    pub fn convert_word(&self, japanese_text: &str) -> String {
         let mut result = String::new();
         let chars: Vec<char> = japanese_text.chars().collect();
         let mut pos = 0;
         
         while pos < chars.len() {
-            // Try to find longest match starting at current position
-            let mut match_length = 0;
-            let mut matched_phoneme: Option<&String> = None;
+            // Context-sensitive kanji (何 → nani/naɴ) pick their reading first
+            if self.options.context_readings {
+                if let Some((match_length, phoneme)) = self.context_reading(&chars, pos) {
+                    result.push_str(&phoneme);
+                    pos += match_length;
+                    continue;
+                }
+            }
             
-            let mut current = &self.root;
+            // Unkeyed っ + consonant: double the consonant (ざっし → zaɕːi)
+            if let Some((match_length, phoneme)) = self.sokuon_match(&chars, pos) {
+                result.push_str(&phoneme);
+                pos += match_length;
+                continue;
+            }
             
-            // Walk the trie as far as possible
-            for i in pos..chars.len() {
-                if let Some(child) = current.children.get(&chars[i]) {
-                    current = child;
-                    
-                    // If this node has a phoneme, it's a valid match
-                    if let Some(ref phoneme) = current.phoneme {
-                        match_length = i - pos + 1;
-                        matched_phoneme = Some(phoneme);
-                    }
-                } else {
-                    break;
-                }
+            // Small ゎ after く/ぐ (classical_kana): くゎ → kʷa
+            if let Some((match_length, phoneme)) = self.labialized_match(&chars, pos) {
+                result.push_str(&phoneme);
+                pos += match_length;
+                continue;
+            }
+            
+            // Unkeyed youon: one mora (しゅ → ɕɯ, きょ → kjo)
+            if let Some((match_length, phoneme)) = self.youon_match(&chars, pos) {
+                result.push_str(&phoneme);
+                pos += match_length;
+                continue;
+            }
+            
+            // を/ヲ read as wo (wo_reading option)
+            if let Some((match_length, phoneme)) = self.wo_match(&chars, pos) {
+                result.push_str(&phoneme);
+                pos += match_length;
+                continue;
             }
             
-            if match_length > 0 {
+            // Try to find longest match starting at current position
+            if let Some((match_length, phoneme)) = self.find_longest_match(&chars, pos) {
                 // Found a match - add phoneme and advance position
-                result.push_str(matched_phoneme.unwrap());
+                result.push_str(phoneme);
                 pos += match_length;
+            } else if chars[pos] == 'ー' && pos > 0 && is_kana(chars[pos - 1]) && ends_with_lengthenable(&result, self.holds_nasal()) {
+                // Unkeyed ー lengthens the vowel just converted (ごーる → goːɾɯ)
+                result.push('ː');
+                pos += 1;
             } else {
                 // No match found - keep original character and continue
                 // This handles spaces, punctuation, unknown characters
-                result.push(chars[pos]);
+                self.push_unmatched(&mut result, chars[pos]);
                 pos += 1;
             }
         }
@@ -382,806 +2093,6339 @@ impl PhonemeConverter {
     
     /// Convert with detailed matching information for debugging
     /// OPTIMIZED: Pre-decodes UTF-8 once and tracks byte positions
-    fn convert_detailed(&self, japanese_text: &str) -> ConversionResult {
-        // PRE-DECODE UTF-8 TO CHARS (like Rust does best!)
-        let chars: Vec<char> = japanese_text.chars().collect();
-        let mut byte_positions = Vec::new();
-        let mut byte_pos = 0;
-        
-        for ch in &chars {
-            byte_positions.push(byte_pos);
-            byte_pos += ch.len_utf8();
-        }
-        byte_positions.push(byte_pos); // End position
-        
-        let mut matches = Vec::new();
-        let mut unmatched = Vec::new();
-        let mut result = String::new();
-        let mut pos = 0;
+    pub fn convert_detailed(&self, japanese_text: &str) -> ConversionResult {
+        let text = self.prepare_input(japanese_text);
+        let mut result = self.convert_word_detailed(&text);
+        result.phonemes = self.finish_phonemes(&text, result.phonemes);
+        result
+    }
+    
+    /// convert() plus the kana reading of the same text, from one trie walk
+    /// 
+    /// For furigana generators. Each match is read as:
+    /// - its kana reading from load_kana_readings() / add_kana_reading()
+    ///   (日本語 → にほんご), if the dictionary key has one
+    /// - its furigana hint, for hinted input (日本語「にほんご」)
+    /// - its written form otherwise (kana, or kanji with no known reading)
+    /// 
+    /// Hinted words are converted from their hint, as in segmented conversion.
+    pub fn convert_with_reading(&self, japanese_text: &str) -> (String, String) {
+        let text = self.prepare_input(japanese_text);
+        let mut phonemes = String::with_capacity(text.len() * 2);
+        let mut reading = String::with_capacity(text.len());
         
-        while pos < chars.len() {
-            let mut match_length = 0;
-            let mut matched_phoneme: Option<&String> = None;
-            
-            let mut current = &self.root;
-            
-            // Walk the trie as far as possible
-            for i in pos..chars.len() {
-                if let Some(child) = current.children.get(&chars[i]) {
-                    current = child;
-                    
-                    if let Some(ref phoneme) = current.phoneme {
-                        match_length = i - pos + 1;
-                        matched_phoneme = Some(phoneme);
-                    }
-                } else {
-                    break;
-                }
-            }
+        for segment in parse_furigana_segments(&text, None) {
+            let segment_text = segment.get_effective_text();
+            let result = self.convert_word_detailed(segment_text);
             
-            if match_length > 0 {
-                // Found a match
-                let original: String = chars[pos..pos + match_length].iter().collect();
-                matches.push(Match {
-                    original,
-                    phoneme: matched_phoneme.unwrap().clone(),
-                    start_index: byte_positions[pos], // Use byte position!
-                });
-                result.push_str(matched_phoneme.unwrap());
-                pos += match_length;
-            } else {
-                // No match found
-                unmatched.push(chars[pos]);
-                result.push(chars[pos]);
-                pos += 1;
+            let mut cursor = 0;
+            for m in &result.matches {
+                reading.push_str(&segment_text[cursor..m.start_index]);  // Unmatched run before it
+                reading.push_str(self.kana_readings.get(&m.original).unwrap_or(&m.original));
+                cursor = m.start_index + m.original.len();
             }
+            reading.push_str(&segment_text[cursor..]);
+            phonemes.push_str(&result.phonemes);
         }
         
-        ConversionResult {
-            phonemes: result,
-            matches,
-            unmatched,
+        let phonemes = self.finish_phonemes(&text, phonemes);
+        if self.options.reverse_morae {
+            (reverse_morae(&phonemes), reading)
+        } else {
+            (phonemes, reading)
         }
     }
-}
-
-/// Word segmenter using longest-match algorithm with word dictionary
-/// Splits Japanese text into words for better phoneme spacing
-struct WordSegmenter {
-    root: TrieNode,
-    word_count: usize,
-}
-
-impl WordSegmenter {
-    fn new() -> Self {
-        WordSegmenter {
-            root: TrieNode::default(),
-            word_count: 0,
+    
+    /// Load kana readings of dictionary keys for convert_with_reading()
+    /// 
+    /// Same format as the dictionary, with kana instead of IPA:
+    /// {"日本語": "にほんご", "東京": "とうきょう"}. Entries add to (and
+    /// override) any readings already loaded.
+    pub fn load_kana_readings(&mut self, file_path: &str) -> Result<(), ConverterError> {
+        let data = parse_flat_json(&read_dictionary_text(file_path)?)?;
+        status!("✅ Loaded {} kana readings", data.len());
+        for (key, kana) in data.iter() {
+            self.add_kana_reading(key, kana);
         }
+        Ok(())
     }
     
-    /// Get root node for trie walking (used in compound detection)
-    fn get_root(&self) -> &TrieNode {
-        &self.root
+    /// Set the kana reading of one dictionary key (see convert_with_reading())
+    pub fn add_kana_reading(&mut self, key: &str, kana: &str) {
+        self.kana_readings.insert(normalize_key_whitespace(key).into_owned(), kana.to_string());
     }
     
-    /// Check if a word exists in the dictionary
-    /// Returns true if the word is a complete entry
-    fn contains_word(&self, word: &str) -> bool {
-        if word.is_empty() {
-            return false;
+    /// Convert and split the phonemes into morae tagged for timing models
+    /// See split_morae() for how the phoneme string is divided.
+    /// Morae are always in reading order (reverse_morae is not applied).
+    pub fn convert_mora_timing(&self, japanese_text: &str) -> Vec<MoraInfo> {
+        let text = self.prepare_input(japanese_text);
+        split_morae(&self.finish_phonemes(&text, self.convert_word(&text)))
+    }
+    
+    /// Convert and tag each phoneme with the index of the mora it belongs to
+    /// 
+    /// Indices refer to convert_mora_timing() and are contiguous from 0.
+    /// Phonemes are split by phoneme_units() (base + modifiers, so ʨ, kʲ and
+    /// t͡ɕ stay whole). Morae that span two halves are assigned like this:
+    /// - Geminates: the held consonant kː belongs to the Sokuon mora, the
+    ///   next mora keeps only what follows it (がっこう → ga | kː | o | ɯ)
+    /// - Long vowels: the ː (or the second vowel of koɯ) is its own
+    ///   LongVowel mora, separate from the vowel it lengthens
+    pub fn convert_annotated(&self, japanese_text: &str) -> Vec<(usize, String)> {
+        annotate_morae(&self.convert_mora_timing(japanese_text))
+    }
+    
+    /// convert() with a log of every decision, for diagnosing a bad conversion
+    /// 
+    /// For each position: which keys the trie walk found (Candidates), then
+    /// which rule produced the output (ContextReading, Sokuon, Longest) or
+    /// that nothing matched (Unmatched). Prepared / Finished appear only when
+    /// an input or output pass changed something. Unsegmented, like convert():
+    /// furigana and word boundaries belong to segmented conversion.
+    pub fn convert_traced(&self, japanese_text: &str) -> (String, Vec<TraceEvent>) {
+        let mut events = Vec::new();
+        
+        let text = self.prepare_input(japanese_text);
+        if text != japanese_text {
+            events.push(TraceEvent::Prepared { text: text.to_string() });
         }
         
-        let mut current = &self.root;
+        let chars: Vec<char> = text.chars().collect();
+        let mut raw = String::new();
+        let mut pos = 0;
         
-        for ch in word.chars() {
-            if let Some(child) = current.children.get(&ch) {
-                current = child;
+        while pos < chars.len() {
+            // Everything the base trie has along this path
+            let mut matches = Vec::new();
+            let mut node = &self.root;
+            let mut i = pos;
+            while let Some((child, next)) = if i < chars.len() { step_trie(node, &chars, i) } else { None } {
+                node = child;
+                i = next;
+                if let Some(ref phoneme) = node.phoneme {
+                    matches.push((i - pos, phoneme.clone()));
+                }
+            }
+            events.push(TraceEvent::Candidates { pos, matches });
+            
+            let original = |length: usize| -> String { chars[pos..pos + length].iter().collect() };
+            let context = if self.options.context_readings { self.context_reading(&chars, pos) } else { None };
+            
+            if let Some((length, phonemes)) = context {
+                raw.push_str(&phonemes);
+                events.push(TraceEvent::ContextReading { pos, original: original(length), phonemes });
+                pos += length;
+            } else if let Some((length, phonemes)) = self.sokuon_match(&chars, pos) {
+                raw.push_str(&phonemes);
+                events.push(TraceEvent::Sokuon { pos, original: original(length), phonemes });
+                pos += length;
+            } else if let Some((length, phonemes)) = self.labialized_match(&chars, pos) {
+                raw.push_str(&phonemes);
+                events.push(TraceEvent::Labialized { pos, original: original(length), phonemes });
+                pos += length;
+            } else if let Some((length, phonemes)) = self.youon_match(&chars, pos) {
+                raw.push_str(&phonemes);
+                events.push(TraceEvent::Youon { pos, original: original(length), phonemes });
+                pos += length;
+            } else if let Some((length, phonemes)) = self.wo_match(&chars, pos) {
+                raw.push_str(&phonemes);
+                events.push(TraceEvent::Wo { pos, original: original(length), phonemes });
+                pos += length;
+            } else if let Some((length, phoneme)) = self.find_longest_match(&chars, pos) {
+                let variant = longest_phoneme_match(&self.root, &chars, pos)
+                    .is_none_or(|(base_length, base)| base_length != length || base != phoneme);
+                raw.push_str(phoneme);
+                events.push(TraceEvent::Longest { pos, original: original(length), phonemes: phoneme.clone(), variant });
+                pos += length;
             } else {
-                return false; // Path doesn't exist
+                self.push_unmatched(&mut raw, chars[pos]);
+                events.push(TraceEvent::Unmatched { pos, ch: chars[pos] });
+                pos += 1;
             }
         }
         
-        // Check if this is a valid end-of-word node
-        current.phoneme.is_some()
+        let mut phonemes = self.finish_phonemes(&text, raw.clone());
+        if self.options.reverse_morae {
+            phonemes = reverse_morae(&phonemes);
+        }
+        if phonemes != raw {
+            events.push(TraceEvent::Finished { phonemes: phonemes.clone() });
+        }
+        
+        (phonemes, events)
     }
     
-    /// Load word list from text file (one word per line)
-    fn load_from_file(&mut self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        println!("🔥 Loading word dictionary for segmentation...");
-        let start_time = Instant::now();
+    /// Split text into alternating convertible / untouched spans, for rendering
+    /// 
+    /// Adjacent dictionary matches merge into one convertible span carrying
+    /// their phonemes (output passes applied per span); unmatched runs become
+    /// spans with no phonemes. Ranges are byte offsets into the text after
+    /// input passes, which is the input itself unless a pass rewrote it.
+    /// 
+    /// Example: 日本はABCです → [日本は ✓] [ABC ✗] [です ✓]
+    pub fn spans(&self, japanese_text: &str) -> Vec<Span> {
+        let text = self.prepare_input(japanese_text);
+        let result = self.convert_word_detailed(&text);
         
-        let file = fs::File::open(file_path)?;
-        let reader = BufReader::new(file);
+        let mut pieces: Vec<(Range<usize>, Option<&str>)> = result.matches.iter()
+            .map(|m| (m.start_index..m.start_index + m.original.len(), Some(m.phoneme.as_str())))
+            .chain(result.unmatched_runs.iter().map(|(start, run)| (*start..*start + run.len(), None)))
+            .collect();
+        pieces.sort_by_key(|(range, _)| range.start);
         
-        for line in reader.lines() {
-            let word = line?;
-            let word = word.trim();
-            
-            if !word.is_empty() {
-                self.insert_word(word);
-                self.word_count += 1;
-                
-                if self.word_count % 50000 == 0 {
-                    print!("\r   Loaded: {} words", self.word_count);
-                    io::stdout().flush().unwrap();
+        let mut spans: Vec<Span> = Vec::new();
+        for (range, phoneme) in pieces {
+            match spans.last_mut() {
+                Some(last) if last.convertible == phoneme.is_some() && last.range.end == range.start => {
+                    last.range.end = range.end;
+                    if let (Some(phonemes), Some(phoneme)) = (last.phonemes.as_mut(), phoneme) {
+                        phonemes.push_str(phoneme);
+                    }
                 }
+                _ => spans.push(Span {
+                    range,
+                    convertible: phoneme.is_some(),
+                    phonemes: phoneme.map(|p| p.to_string()),
+                }),
             }
         }
         
-        let elapsed = start_time.elapsed();
-        println!("\n✅ Loaded {} words in {}ms", self.word_count, elapsed.as_millis());
+        for span in &mut spans {
+            if let Some(phonemes) = span.phonemes.take() {
+                span.phonemes = Some(self.finish_phonemes(&text[span.range.clone()], phonemes));
+            }
+        }
         
-        Ok(())
+        spans
     }
     
-    /// Insert a word into the trie
-    fn insert_word(&mut self, word: &str) {
-        let mut current = &mut self.root;
-        
-        for ch in word.chars() {
-            current = current.children
-                .entry(ch)
-                .or_insert_with(|| Box::new(TrieNode::default()));
+    /// Dictionary coverage of a text, 0.0-1.0, for quality gating
+    /// 
+    /// Counts the characters of convert_detailed(): chars inside dictionary
+    /// matches vs unmatched chars, both excluding whitespace and punctuation
+    /// (see is_punctuation). Input passes apply first, so with number expansion
+    /// digits count as their kana. Text with nothing to cover scores 1.0.
+    pub fn quality_score(&self, japanese_text: &str) -> f32 {
+        self.convert_detailed(japanese_text).coverage()
+    }
+    
+    /// Length of the longest block of unconvertible chars in a text
+    /// 
+    /// Complements quality_score(): a line with one long garbage run (a pasted
+    /// URL, mojibake) scores high here, a line with scattered single unknown
+    /// kanji stays at 1. Runs come from convert_detailed().
+    pub fn longest_unmatched_run(&self, japanese_text: &str) -> usize {
+        self.convert_detailed(japanese_text).longest_unmatched_run()
+    }
+    
+    /// Longest dictionary key matched in a text, as (length in chars, key)
+    /// 
+    /// For profiling: a deep match means the walk went far down the trie,
+    /// which points at unusually long entries. Taken from convert_detailed().
+    pub fn max_match_depth(&self, japanese_text: &str) -> Option<(usize, String)> {
+        self.convert_detailed(japanese_text).deepest_match()
+            .map(|(depth, m)| (depth, m.original.clone()))
+    }
+    
+    /// Snapshot of everything that shapes this converter's output
+    pub fn config(&self) -> ConverterConfig {
+        ConverterConfig {
+            options: self.options.clone(),
+            entry_count: self.entry_count,
+            variant: self.variant_name.clone(),
+            substitution_count: self.substitutions.len(),
         }
-        
-        // Mark end of word (use empty string as marker)
-        current.phoneme = Some(String::new());
     }
     
-    /// Segment text into words using longest-match algorithm
+    /// convert_detailed() plus the config that produced the result
     /// 
-    /// SMART SEGMENTATION: Words are matched from dictionary, and any
-    /// unmatched sequences between words are treated as grammatical elements
-    /// (particles, conjugations, etc.) and given their own space.
+    /// For reproducible experiments: log `config.to_json()` next to each
+    /// output, and a later run can tell whether it used the same settings.
+    pub fn convert_with_config_echo(&self, japanese_text: &str) -> (ConversionResult, ConverterConfig) {
+        (self.convert_detailed(japanese_text), self.config())
+    }
+    
+    /// True if two texts sound the same, for pun and rhyme tools
     /// 
-    /// Example: 私はリンゴがすきです
-    /// - Matches: 私, リンゴ, すき
-    /// - Grammar (unmatched): は, が, です
-    /// - Result: [私, は, リンゴ, が, すき, です]
-    fn segment(&self, text: &str) -> Vec<String> {
-        let mut words = Vec::new();
-        let chars: Vec<char> = text.chars().collect();
-        let mut pos = 0;
-        
-        while pos < chars.len() {
-            // Skip spaces in input
-            if chars[pos].is_whitespace() {
-                pos += 1;
-                continue;
-            }
-            
-            // Try to find longest word match starting at current position
-            let mut match_length = 0;
-            let mut current = &self.root;
-            
-            for i in pos..chars.len() {
-                if let Some(child) = current.children.get(&chars[i]) {
-                    current = child;
-                    
-                    // If this node marks end of word, it's a valid match
-                    if current.phoneme.is_some() {
-                        match_length = i - pos + 1;
-                    }
-                } else {
-                    break;
-                }
-            }
-            
-            if match_length > 0 {
-                // Found a word match - extract it
-                let word: String = chars[pos..pos + match_length].iter().collect();
-                words.push(word);
-                pos += match_length;
-            } else {
-                // No match found - this is likely a grammatical element
-                // Collect all consecutive unmatched characters as a single token
-                let grammar_start = pos;
-                
-                // Keep collecting characters until we find another word match
-                while pos < chars.len() {
-                    // Skip spaces
-                    if chars[pos].is_whitespace() {
-                        break;
-                    }
-                    
-                    // Try to match a word starting from current position
-                    let mut lookahead_match = 0;
-                    let mut lookahead = &self.root;
-                    
-                    for i in pos..chars.len() {
-                        if let Some(child) = lookahead.children.get(&chars[i]) {
-                            lookahead = child;
-                            
-                            if lookahead.phoneme.is_some() {
-                                lookahead_match = i - pos + 1;
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                    
-                    // If we found a word match, stop here
-                    if lookahead_match > 0 {
-                        break;
-                    }
-                    
-                    // Otherwise, this character is part of the grammar sequence
-                    pos += 1;
-                }
-                
-                // Extract the grammar token
-                if pos > grammar_start {
-                    let grammar: String = chars[grammar_start..pos].iter().collect();
-                    words.push(grammar);
-                }
-            }
-        }
-        
-        words
+    /// Compares convert() of both after the phoneme_comparison normalization
+    /// (雨 / 飴 → ame / ame). With ignore_length, おばさん and おばあさん match.
+    pub fn are_homophones(&self, a: &str, b: &str) -> bool {
+        let comparison = self.options.phoneme_comparison;
+        comparison.normalize(&self.convert(a)) == comparison.normalize(&self.convert(b))
     }
     
-    /// Segment text from TextSegments using longest-match algorithm with phoneme fallback
+    /// Convert a line stream to JSON lines, one object per non-blank input line
     /// 
-    /// SMART SEGMENTATION: Words are matched from dictionary, and any
-    /// unmatched sequences between words are treated as grammatical elements
-    /// (particles, conjugations, etc.) and given their own space.
+    /// Reads and writes a line at a time, so memory stays flat for any input
+    /// size. Schema (line is 1-based, positions are byte offsets into input):
     /// 
-    /// This version properly handles TextSegments with furigana hints,
-    /// treating each segment as an atomic unit during segmentation.
+    ///   {"line": 1, "input": "私はxyz", "phonemes": "ɰᵝataihaxyz", "coverage": 0.6667,
+    ///    "unmatched_runs": [{"position": 6, "text": "xyz"}]}
     /// 
-    /// @param phoneme_root Optional phoneme trie root for fallback lookups
-    fn segment_from_segments(&self, segments: &[TextSegment], phoneme_root: Option<&TrieNode>) -> Vec<String> {
-        let mut words = Vec::new();
+    /// A line that isn't valid UTF-8 gets {"line": 2, "error": "..."} and the
+    /// stream continues. Returns the number of lines written; I/O errors abort.
+    pub fn convert_jsonl_stream<R: BufRead, W: Write>(&self, mut reader: R, mut writer: W) -> io::Result<usize> {
+        let mut buffer = Vec::new();
+        let mut line_number = 0;
+        let mut written = 0;
         
-        // Process each segment
-        for segment in segments {
-            // For furigana segments, treat the entire reading as one word
-            if matches!(segment.segment_type, SegmentType::FuriganaHint) {
-                words.push(segment.reading.clone());
-                continue;
+        loop {
+            buffer.clear();
+            if reader.read_until(b'\n', &mut buffer)? == 0 {
+                break;
             }
+            line_number += 1;
             
-            // For normal text segments, apply word segmentation
-            let text = &segment.text;
-            let chars: Vec<char> = text.chars().collect();
-            let mut pos = 0;
-            
-            while pos < chars.len() {
-                // Skip spaces in input
-                if chars[pos].is_whitespace() {
-                    pos += 1;
+            let line = match std::str::from_utf8(&buffer) {
+                Ok(line) => line.trim_end_matches(['\n', '\r']),
+                Err(e) => {
+                    writeln!(writer, "{{\"line\": {}, \"error\": \"{}\"}}", line_number, escape_json(&e.to_string()))?;
+                    written += 1;
                     continue;
                 }
-                
-                // Try to find longest word match starting at current position
-                // Check word dictionary first, then phoneme dictionary as fallback
-                let mut match_length = 0;
-                let mut current = &self.root;
-                
-                for i in pos..chars.len() {
-                    if let Some(child) = current.children.get(&chars[i]) {
-                        current = child;
-                        
-                        // If this node marks end of word, it's a valid match
-                        if current.phoneme.is_some() {
-                            match_length = i - pos + 1;
-                        }
-                    } else {
-                        break;
-                    }
-                }
-                
-                // 🔥 FALLBACK: If word dictionary didn't find a match, try phoneme dictionary
-                if match_length == 0 {
-                    if let Some(phoneme_current_root) = phoneme_root {
-                        let mut phoneme_current = phoneme_current_root;
-                        
-                        for i in pos..chars.len() {
-                            if let Some(child) = phoneme_current.children.get(&chars[i]) {
-                                phoneme_current = child;
-                                
-                                // If this node has a phoneme, it's a valid word
-                                if phoneme_current.phoneme.is_some() {
-                                    match_length = i - pos + 1;
-                                }
-                            } else {
-                                break;
-                            }
-                        }
-                    }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            
+            let result = self.convert_detailed(line);
+            let runs: Vec<String> = result.unmatched_runs.iter()
+                .map(|(position, run)| format!("{{\"position\": {}, \"text\": \"{}\"}}", position, escape_json(run)))
+                .collect();
+            writeln!(writer, "{{\"line\": {}, \"input\": \"{}\", \"phonemes\": \"{}\", \"coverage\": {:.4}, \"unmatched_runs\": [{}]}}",
+                     line_number, escape_json(line), escape_json(&result.phonemes), result.coverage(), runs.join(", "))?;
+            written += 1;
+        }
+        
+        writer.flush()?;
+        Ok(written)
+    }
+    
+    /// Detailed version of convert_word() (raw dictionary walk, no input passes)
+    fn convert_word_detailed(&self, japanese_text: &str) -> ConversionResult {
+        let mut matches = Vec::new();
+        let mut unmatched = Vec::new();
+        let mut unmatched_runs = Vec::new();
+        let mut result = String::new();
+        
+        for step in MatchWalk::new(self, japanese_text) {
+            match step {
+                WalkStep::Matched(m) => {
+                    result.push_str(&m.phoneme);
+                    matches.push(m);
                 }
-                
-                if match_length > 0 {
-                    // Found a word match - extract it
-                    let word: String = chars[pos..pos + match_length].iter().collect();
-                    words.push(word);
-                    pos += match_length;
-                } else {
-                    // No match found - this is likely a grammatical element
-                    // Collect all consecutive unmatched characters as a single token
-                    let grammar_start = pos;
-                    
-                    // Keep collecting characters until we find another word match
-                    while pos < chars.len() {
-                        // Skip spaces
-                        if chars[pos].is_whitespace() {
-                            break;
-                        }
-                        
-                        // Try to match a word starting from current position
-                        let mut lookahead_match = 0;
-                        let mut lookahead = &self.root;
-                        
-                        for i in pos..chars.len() {
-                            if let Some(child) = lookahead.children.get(&chars[i]) {
-                                lookahead = child;
-                                
-                                if lookahead.phoneme.is_some() {
-                                    lookahead_match = i - pos + 1;
-                                }
-                            } else {
-                                break;
-                            }
-                        }
-                        
-                        // If we found a word match, stop here
-                        if lookahead_match > 0 {
-                            break;
-                        }
-                        
-                        // Otherwise, this character is part of the grammar sequence
-                        pos += 1;
-                    }
-                    
-                    // Extract the grammar token
-                    if pos > grammar_start {
-                        let grammar: String = chars[grammar_start..pos].iter().collect();
-                        words.push(grammar);
-                    }
+                WalkStep::Unmatched { start_index, ch } => {
+                    unmatched.push(ch);
+                    push_unmatched_run(&mut unmatched_runs, start_index, ch.encode_utf8(&mut [0; 4]));
+                    self.push_unmatched(&mut result, ch);
                 }
             }
         }
         
-        words
+        ConversionResult {
+            phonemes: result,
+            matches,
+            unmatched,
+            unmatched_runs,
+            word_scores: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+    
+    /// Matches of convert_detailed(), yielded lazily as the greedy walk proceeds
+    /// 
+    /// Same (original, phoneme, start_index) as convert_detailed().matches,
+    /// without collecting them, so a caller can stop after the first few.
+    /// Input passes still run up front; output passes (finish_phonemes)
+    /// don't apply to individual matches.
+    pub fn iter_matches<'a>(&'a self, text: &'a str) -> impl Iterator<Item = Match> + 'a {
+        let prepared = self.prepare_input(text);
+        MatchWalk::new(self, &prepared).filter_map(|step| match step {
+            WalkStep::Matched(m) => Some(m),
+            WalkStep::Unmatched { .. } => None,
+        })
     }
 }
 
-/// Helper function to check if a character is kana (hiragana or katakana)
-fn is_kana(ch: char) -> bool {
-    let cp = ch as u32;
-    (cp >= 0x3040 && cp <= 0x309F) ||  // Hiragana
-    (cp >= 0x30A0 && cp <= 0x30FF)     // Katakana
+/// One step of the greedy dictionary walk (see MatchWalk)
+enum WalkStep {
+    Matched(Match),
+    /// No entry starts here, the char passes through
+    Unmatched { start_index: usize, ch: char },
 }
 
-/// Parse text into segments, extracting furigana hints.
-/// 
-/// This creates a structured representation of the text where each segment
-/// is either normal text or a furigana hint. This approach is cleaner than
-/// using markers and makes the processing logic more transparent.
-/// 
-/// SMART COMPOUND WORD DETECTION:
-/// - If kanji「reading」+following text forms a dictionary word, prefer dictionary
-/// - Example: 見「み」て → Check if 見て is a word → YES → Keep as normal text "見て"
-/// - Example: 健太「けんた」て → Check if 健太て is a word → NO → Use furigana "けんた"
-/// 
-/// @param text Input text with potential furigana hints (e.g., 健太「けんた」)
-/// @param segmenter Optional word segmenter for compound word detection
-fn parse_furigana_segments(text: &str, segmenter: Option<&WordSegmenter>) -> Vec<TextSegment> {
-    let mut segments = Vec::new();
-    
-    // Pre-decode UTF-8 to chars for blazing speed
-    let chars: Vec<char> = text.chars().collect();
-    let mut byte_positions = Vec::new();
-    let mut byte_pos = 0;
-    
-    for ch in &chars {
-        byte_positions.push(byte_pos);
-        byte_pos += ch.len_utf8();
+/// Greedy longest-match walk over one text, one step per next()
+/// Shared by convert_word_detailed() and iter_matches().
+struct MatchWalk<'a> {
+    converter: &'a PhonemeConverter,
+    chars: Vec<char>,
+    pos: usize,            // Char index of the next step
+    byte_pos: usize,       // Byte position of chars[pos]
+    last: Option<char>,    // Last char written to the phonemes so far (for ー)
+}
+
+impl<'a> MatchWalk<'a> {
+    fn new(converter: &'a PhonemeConverter, text: &str) -> Self {
+        MatchWalk { converter, chars: text.chars().collect(), pos: 0, byte_pos: 0, last: None }
     }
-    byte_positions.push(byte_pos);
     
-    let mut pos = 0;
+    /// Consume `length` chars as a match
+    fn matched(&mut self, length: usize, phoneme: String) -> WalkStep {
+        let start_index = self.byte_pos;
+        let original: String = self.chars[self.pos..self.pos + length].iter().collect();
+        self.byte_pos += original.len();
+        self.pos += length;
+        if let Some(last) = phoneme.chars().last() {
+            self.last = Some(last);
+        }
+        WalkStep::Matched(Match { original, phoneme, start_index })
+    }
+}
+
+impl<'a> Iterator for MatchWalk<'a> {
+    type Item = WalkStep;
     
-    while pos < chars.len() {
-        // Look for opening bracket 「 (U+300C)
-        let bracket_open = chars[pos..].iter().position(|&ch| ch == '「').map(|p| pos + p);
+    fn next(&mut self) -> Option<WalkStep> {
+        let converter = self.converter;
+        let chars = &self.chars;
+        let pos = self.pos;
+        if pos >= chars.len() {
+            return None;
+        }
         
-        if bracket_open.is_none() {
-            // No more furigana hints, add rest of text as normal segment
-            if pos < chars.len() {
-                let text_str: String = chars[pos..].iter().collect();
-                segments.push(TextSegment::new_normal(text_str, byte_positions[pos]));
+        if converter.options.context_readings {
+            if let Some((match_length, phoneme)) = converter.context_reading(chars, pos) {
+                return Some(self.matched(match_length, phoneme));
             }
-            break;
         }
         
-        let bracket_open = bracket_open.unwrap();
-        
-        // Look for closing bracket 」 (U+300D)
-        let bracket_close = chars[bracket_open + 1..].iter().position(|&ch| ch == '」')
-            .map(|p| bracket_open + 1 + p);
+        if let Some((match_length, phoneme)) = converter.sokuon_match(chars, pos)
+            .or_else(|| converter.labialized_match(chars, pos))
+            .or_else(|| converter.youon_match(chars, pos))
+            .or_else(|| converter.wo_match(chars, pos)) {
+            return Some(self.matched(match_length, phoneme));
+        }
         
-        if bracket_close.is_none() {
-            // No closing bracket, add rest as normal segment
-            let text_str: String = chars[pos..].iter().collect();
-            segments.push(TextSegment::new_normal(text_str, byte_positions[pos]));
-            break;
+        if let Some((match_length, phoneme)) = converter.find_longest_match(chars, pos) {
+            let phoneme = phoneme.to_string();
+            return Some(self.matched(match_length, phoneme));
         }
         
-        let bracket_close = bracket_close.unwrap();
+        let ch = chars[pos];
+        let lengthenable = self.last.is_some_and(|last| ends_with_lengthenable(last.encode_utf8(&mut [0; 4]), converter.holds_nasal()));
+        if ch == 'ー' && pos > 0 && is_kana(chars[pos - 1]) && lengthenable {
+            return Some(self.matched(1, "ː".to_string()));
+        }
         
-        // Find where the "word" (kanji) starts before the opening bracket
-        // Search backwards to find the start of the kanji/word that has furigana
-        let mut last_kanji_pos = bracket_open;
-        while last_kanji_pos > pos && is_kana(chars[last_kanji_pos - 1]) {
-            last_kanji_pos -= 1;
+        // No match found
+        let start_index = self.byte_pos;
+        let mut written = String::new();
+        converter.push_unmatched(&mut written, ch);
+        if let Some(last) = written.chars().last() {
+            self.last = Some(last);
+        }
+        self.byte_pos += ch.len_utf8();
+        self.pos += 1;
+        Some(WalkStep::Unmatched { start_index, ch })
+    }
+}
+
+/// How RomajiConverter writes long vowels (こう, おお, ー)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RomajiLongVowels {
+    /// Macron over the vowel: gakkō, kōhī (modified Hepburn)
+    #[default]
+    Macron,
+    /// Vowel written twice: gakkoo, koohii
+    Doubled,
+}
+
+/// Kana → Hepburn romaji, read straight off the kana with its own trie
+/// 
+/// The romaji dictionary (ja_romaji.json: kana → romaji per mora, youon and
+/// loanword kana like ティ as their own keys) is walked by the same greedy
+/// longest match as the phoneme trie. Multi-mora keys are written as they
+/// stand, so exceptions like "おもう": "omou" can be listed. Rules on top:
+/// - っ doubles the next consonant (gakkō, kitte, matcha); with no
+///   consonant after it, it is dropped
+/// - ん is n, n' before a vowel or y (kin'en), m before b/m/p with labial_m
+///   (shimbun, traditional Hepburn; default shinbun)
+/// - A long vowel (oo, ou, uu, aa, ee across morae, or ー) takes a macron or
+///   is doubled (long_vowels); ii and ei stay as written
+/// 
+/// Kanji and anything else the dictionary has no key for pass through.
+pub struct RomajiConverter {
+    root: TrieNode,
+    entry_count: usize,
+    pub long_vowels: RomajiLongVowels,
+    pub labial_m: bool,  // ん before b/m/p as m (shimbun)
+}
+
+impl Default for RomajiConverter {
+    fn default() -> Self {
+        RomajiConverter::new()
+    }
+}
+
+impl RomajiConverter {
+    /// Create a converter with an empty romaji dictionary
+    pub fn new() -> Self {
+        RomajiConverter {
+            root: TrieNode::default(),
+            entry_count: 0,
+            long_vowels: RomajiLongVowels::default(),
+            labial_m: false,
         }
+    }
+    
+    /// Load a kana → romaji dictionary (one flat JSON object, see parse_flat_json())
+    pub fn load_from_json(&mut self, file_path: &str) -> Result<(), ConverterError> {
+        let data = parse_flat_json(&read_dictionary_text(file_path)?)?;
         
-        if last_kanji_pos > pos {
-            last_kanji_pos -= 1; // Now pointing at the last kanji
+        for (key, value) in data.iter() {
+            self.root.insert(key, value);
+            self.entry_count += 1;
         }
         
-        // Second pass: From last kanji, search backward for word boundary
-        let mut word_start = last_kanji_pos;
-        let mut search_pos = last_kanji_pos;
+        status!("✅ Loaded {} romaji entries", self.entry_count);
+        Ok(())
+    }
+    
+    /// Romanize kana text (see RomajiConverter for the rules)
+    pub fn convert(&self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut result = String::with_capacity(text.len());
+        let mut last_vowel: Option<char> = None;  // Vowel the last mora ended in, if it can lengthen
+        let mut pos = 0;
         
-        while search_pos > pos {
-            search_pos -= 1;
-            let ch = chars[search_pos];
-            let cp = ch as u32;
-            
-            // Check for punctuation boundaries
-            if matches!(ch, '」' | '、' | '。' | '！' | '？' | '）' | '］') ||
-               (cp < 0x80 && matches!(ch, '.' | ',' | '!' | '?' | ';' | ':' | '(' | ')' | '[' | ']' | 
-                                      '{' | '}' | '"' | '\'' | '-' | '/' | '\\' | '|' | ' ' | '\t' | '\n' | '\r')) {
-                word_start = search_pos + 1;
-                break;
-            }
+        while pos < chars.len() {
+            let ch = chars[pos];
             
-            // Check if this is kana
-            if is_kana(ch) {
-                // Check if there's ANY kanji before this position
-                let has_kanji_before = chars[pos..search_pos].iter().any(|&c| {
-                    let code = c as u32;
-                    code >= 0x4E00 || (code >= 0x3400 && code <= 0x9FFF)
-                });
-                
-                if !has_kanji_before {
-                    // This kana is not sandwiched - it's a prefix word → stop here
-                    word_start = search_pos + 1;
-                    break;
+            if ch == 'っ' || ch == 'ッ' {
+                // Double the onset of the following mora (ch → tch)
+                if let Some((_, next)) = longest_phoneme_match(&self.root, &chars, pos + 1) {
+                    match next.chars().next() {
+                        Some('c') if next.starts_with("ch") => result.push('t'),
+                        Some(onset) if onset.is_ascii_lowercase() && !"aiueon".contains(onset) => result.push(onset),
+                        _ => {}
+                    }
                 }
-                // Otherwise, this kana is sandwiched (okurigana) → continue
+                last_vowel = None;
+                pos += 1;
+            } else if ch == 'ん' || ch == 'ン' {
+                let next = longest_phoneme_match(&self.root, &chars, pos + 1)
+                    .and_then(|(_, next)| next.chars().next());
+                match next {
+                    Some(onset) if "aiueoy".contains(onset) => result.push_str("n'"),
+                    Some('b' | 'm' | 'p') if self.labial_m => result.push('m'),
+                    _ => result.push('n'),
+                }
+                last_vowel = None;
+                pos += 1;
+            } else if ch == 'ー' {
+                // Lengthen the vowel just written; nothing to lengthen drops it
+                if let Some(vowel) = last_vowel.take() {
+                    self.lengthen(&mut result, vowel);
+                }
+                pos += 1;
+            } else if let Some((length, romaji)) = longest_phoneme_match(&self.root, &chars, pos) {
+                let merges = match (last_vowel, romaji.as_str()) {
+                    (Some(previous), "u") => previous == 'o' || previous == 'u',
+                    (Some(previous), "a" | "e" | "o") => romaji.starts_with(previous),
+                    _ => false,
+                };
+                
+                if length == 1 && merges {
+                    self.lengthen(&mut result, last_vowel.take().unwrap_or('o'));
+                } else {
+                    result.push_str(romaji);
+                    last_vowel = romaji.chars().last().filter(|c| "aiueo".contains(*c));
+                }
+                pos += length;
+            } else {
+                result.push(ch);
+                last_vowel = None;
+                pos += 1;
             }
-            
-            // Update word_start to include this character
-            word_start = search_pos;
         }
         
-        // Add text from current position up to where the word/kanji starts
-        if word_start > pos {
-            let text_str: String = chars[pos..word_start].iter().collect();
-            segments.push(TextSegment::new_normal(text_str, byte_positions[pos]));
+        result
+    }
+    
+    /// Lengthen `vowel`, the last char written to `result`
+    fn lengthen(&self, result: &mut String, vowel: char) {
+        match self.long_vowels {
+            RomajiLongVowels::Macron => {
+                result.pop();
+                result.push_str(&macron_vowel(vowel));
+            }
+            RomajiLongVowels::Doubled => result.push(vowel),
         }
+    }
+}
+
+/// Settings a conversion ran with (PhonemeConverter::config())
+/// The dictionary is identified by its entry count only.
+#[derive(Debug, Clone)]
+pub struct ConverterConfig {
+    pub options: ConversionOptions,
+    pub entry_count: usize,           // Dictionary entries loaded
+    pub variant: Option<String>,  // Dialect overlay name (--dialect)
+    pub substitution_count: usize, // Kanji substitutions loaded (--substitutions)
+}
+
+impl ConverterConfig {
+    /// One JSON object with every option by name; enums are written as their
+    /// variant names ("Cardinal"), unset optional values as null
+    pub fn to_json(&self) -> String {
+        let o = &self.options;
+        let string = |value: &str| format!("\"{}\"", escape_json(value));
+        let optional = |value: Option<String>| value.map_or("null".to_string(), |v| string(&v));
+        let exceptions: Vec<String> = o.long_vowel_exceptions.iter().map(|word| string(word)).collect();
         
-        // Extract the kanji and reading
-        let kanji: String = chars[word_start..bracket_open].iter().collect();
-        let reading: String = chars[bracket_open + 1..bracket_close].iter().collect();
-        let reading = reading.trim().to_string();
+        let options = [
+            ("number_mode", string(&format!("{:?}", o.number_mode))),
+            ("list_markers", o.list_markers.to_string()),
+            ("numeric_patterns", o.numeric_patterns.to_string()),
+            ("scientific_notation", o.scientific_notation.to_string()),
+            ("add_tie_bars", o.add_tie_bars.to_string()),
+            ("classical_kana", o.classical_kana.to_string()),
+            ("merge_long_vowels", o.merge_long_vowels.to_string()),
+            ("long_vowel_exceptions", format!("[{}]", exceptions.join(", "))),
+            ("context_readings", o.context_readings.to_string()),
+            ("elongation", optional(o.elongation.map(|mode| format!("{:?}", mode)))),
+            ("ipa_override_delimiter", optional(o.ipa_override_delimiter.map(String::from))),
+            ("reading_delimiter", optional(o.reading_delimiter.map(String::from))),
+            ("colloquial_contractions", o.colloquial_contractions.to_string()),
+            ("explicit_space_token", optional(o.explicit_space_token.clone())),
+            ("reverse_morae", o.reverse_morae.to_string()),
+            ("ascii_case", string(&format!("{:?}", o.ascii_case))),
+            ("strip_control_chars", o.strip_control_chars.to_string()),
+            ("normalize_ideographic_space", o.normalize_ideographic_space.to_string()),
+            ("loanword_final_vowel", string(&format!("{:?}", o.loanword_final_vowel))),
+            ("fold_katakana", o.fold_katakana.to_string()),
+            ("geminate_style", string(&format!("{:?}", o.geminate_style))),
+            ("long_vowel_style", string(&format!("{:?}", o.long_vowel_style))),
+            ("phoneme_inventory", string(&format!("{:?}", o.phoneme_inventory))),
+            ("phoneme_comparison", format!("{{\"ignore_accent\": {}, \"ignore_length\": {}, \"ignore_devoicing\": {}}}",
+                                           o.phoneme_comparison.ignore_accent, o.phoneme_comparison.ignore_length,
+                                           o.phoneme_comparison.ignore_devoicing)),
+            ("wo_reading", string(&format!("{:?}", o.wo_reading))),
+            ("word_case", string(&format!("{:?}", o.word_case))),
+            ("unknown_kanji_phoneme", optional(o.unknown_kanji_phoneme.clone())),
+        ];
+        let options: Vec<String> = options.iter()
+            .map(|(name, value)| format!("\"{}\": {}", name, value))
+            .collect();
         
-        if reading.is_empty() {
-            // Empty reading - skip the entire furigana hint
-            pos = bracket_close + 1;
-            continue;
+        format!("{{\"entry_count\": {}, \"variant\": {}, \"substitution_count\": {}, \"options\": {{{}}}}}",
+                self.entry_count, optional(self.variant.clone()), self.substitution_count, options.join(", "))
+    }
+}
+
+/// Named set of loaded converters (e.g. "general", "medical", "legal")
+/// for services that pick a dictionary per request
+/// 
+/// Converters are held in an Arc so callers can keep one alive past a
+/// re-registration. PhonemeConverter has no interior mutability, so a shared
+/// converter is Send + Sync and conversion needs only &self: share the
+/// registry itself as Arc<ConverterRegistry> across threads. Registering
+/// takes &mut self - wrap the registry in an RwLock if names change at
+/// runtime, or build it once at startup and keep it read-only.
+#[derive(Default)]
+pub struct ConverterRegistry {
+    converters: HashMap<String, Arc<PhonemeConverter>>,
+}
+
+impl ConverterRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        ConverterRegistry::default()
+    }
+    
+    /// Register a converter under `name`, returning the one it replaced
+    pub fn insert(&mut self, name: &str, converter: PhonemeConverter) -> Option<Arc<PhonemeConverter>> {
+        self.insert_shared(name, Arc::new(converter))
+    }
+    
+    /// Register an already-shared converter (one converter may sit under several names)
+    pub fn insert_shared(&mut self, name: &str, converter: Arc<PhonemeConverter>) -> Option<Arc<PhonemeConverter>> {
+        self.converters.insert(name.to_string(), converter)
+    }
+    
+    /// Load a dictionary and register it under `name`
+    /// 
+    /// The content decides the format, not the name: a binary trie (also
+    /// japanese.trie.gz) loads as one, anything else is read as JSON.
+    pub fn load(&mut self, name: &str, file_path: &str) -> Result<(), ConverterError> {
+        let mut converter = PhonemeConverter::new();
+        match converter.load_from_binary(file_path) {
+            Err(e) if e.is_format_error() => converter.load_from_json(file_path)?,
+            result => result?,
         }
         
-        // 🔥 SMART COMPOUND WORD DETECTION USING TRIE'S LONGEST-MATCH
-        let after_bracket = bracket_close + 1;
-        let mut used_compound = false;
+        self.insert(name, converter);
+        Ok(())
+    }
+    
+    /// Remove the converter registered under `name`
+    pub fn remove(&mut self, name: &str) -> Option<Arc<PhonemeConverter>> {
+        self.converters.remove(name)
+    }
+    
+    /// Borrow the converter registered under `name`
+    pub fn get(&self, name: &str) -> Option<&PhonemeConverter> {
+        self.converters.get(name).map(|converter| converter.as_ref())
+    }
+    
+    /// Clone the Arc for `name`, for handing a converter to another thread
+    pub fn get_shared(&self, name: &str) -> Option<Arc<PhonemeConverter>> {
+        self.converters.get(name).cloned()
+    }
+    
+    /// Convert `text` with the converter named `name` (None if unregistered)
+    pub fn convert(&self, name: &str, text: &str) -> Option<String> {
+        self.get(name).map(|converter| converter.convert(text))
+    }
+    
+    /// Registered names, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.converters.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// Write a varint-encoded length (7 bits per byte, high bit = continuation)
+/// Mirrors read_varint()
+fn write_varint<W: Write>(writer: &mut W, mut value: u32) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
         
-        if let Some(seg) = segmenter {
-            if after_bracket < chars.len() {
-                // Use trie to find longest match starting from word_start position
-                let mut match_length = 0;
-                let mut current = seg.get_root();
-                
-                // Walk trie through kanji characters first
-                for i in word_start..bracket_open {
-                    if let Some(child) = current.children.get(&chars[i]) {
-                        current = child;
-                    } else {
-                        break;
-                    }
-                }
-                
-                // Continue walking through characters after the bracket
-                for i in after_bracket..chars.len() {
-                    if let Some(child) = current.children.get(&chars[i]) {
-                        current = child;
-                        
-                        // Check if this position marks a valid word ending
-                        if current.phoneme.is_some() {
-                            // Found a compound! Track it as the longest so far
-                            match_length = i - after_bracket + 1;
-                        }
-                    } else {
-                        break;
-                    }
-                }
-                
-                // If we found a compound word, use it with the furigana reading
-                if match_length > 0 {
-                    let suffix: String = chars[after_bracket..after_bracket + match_length].iter().collect();
-                    let compound = format!("{}{}", reading, suffix);
-                    segments.push(TextSegment::new_normal(compound, byte_positions[word_start]));
-                    pos = after_bracket + match_length;
-                    used_compound = true;
-                }
-            }
+        if value == 0 {
+            writer.write_all(&[byte])?;
+            return Ok(());
         }
         
-        if !used_compound {
-            // No compound found, use the furigana hint
-            segments.push(TextSegment::new_furigana(kanji, reading, byte_positions[word_start]));
-            pos = bracket_close + 1;
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+/// Read a varint-encoded length (see write_varint())
+/// A varint longer than a u32 can hold is ConverterError::Corrupt.
+fn read_varint<R: Read>(reader: &mut R) -> Result<u32, ConverterError> {
+    let mut value = 0u32;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte)?;
+        let bits = (byte[0] & 0x7F) as u32;
+        if shift >= 32 || (shift == 28 && bits > 0x0F) {
+            return Err(ConverterError::Corrupt { message: "varint length overflows 32 bits".to_string() });
+        }
+        
+        value |= bits << shift;
+        if (byte[0] & 0x80) == 0 {
+            return Ok(value);
         }
+        shift += 7;
+    }
+}
+
+/// Read a varint length and that many bytes as UTF-8
+/// The bytes are read through take(), so a corrupt length fails with
+/// UnexpectedEof at the end of the input instead of allocating it up front.
+fn read_varint_string<R: Read>(reader: &mut R) -> Result<String, ConverterError> {
+    let length = read_varint(reader)? as usize;
+    let mut bytes = Vec::new();
+    reader.by_ref().take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < length {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "binary trie entry is truncated").into());
     }
     
-    segments
+    Ok(String::from_utf8(bytes)?)
 }
 
-/// Convert with word segmentation support
-/// OPTIMIZED: Uses furigana-aware segmentation and は → wa particle handling
-/// 
-/// Example: 健太「けんた」はバカ → kẽ̞ɴta wa baka
-fn convert_with_segmentation(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter) -> String {
-    // 🔥 STEP 1: Parse furigana hints into structured segments
-    let segments = parse_furigana_segments(text, Some(segmenter));
+/// Write entries in the simple binary format (japanese.trie)
+/// Layout: "JPHO" magic, v1.0, u32 entry count, then varint-prefixed UTF-8 key/value pairs
+/// Empty values are word markers (same convention as fix_and_align_phonemes.py),
+/// so silent "" entries from JSON come back as plain words when loaded from binary
+fn write_binary_format(file_path: &str, entries: &[(String, String)]) -> Result<(), ConverterError> {
+    let file = fs::File::create(file_path)?;
+    let mut writer = BufWriter::new(file);
     
-    // 🔥 STEP 2: Segment into words using structured segments with phoneme fallback
-    let words = segmenter.segment_from_segments(&segments, Some(converter.get_root()));
+    // Header: magic number, version 1.0, entry count
+    writer.write_all(b"JPHO")?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())?;
+    writer.write_all(&(entries.len() as u32).to_le_bytes())?;
     
-    // 🔥 STEP 3: Convert each word to phonemes with particle handling
-    let phonemes: Vec<String> = words.iter().map(|word| {
-        // Special handling for the topic particle は → "wa"
-        if word == "は" {
-            "wa".to_string()
-        } else {
-            converter.convert(word)
-        }
-    }).collect();
+    // Entries: varint key length, key bytes, varint value length, value bytes
+    for (key, value) in entries {
+        write_varint(&mut writer, key.len() as u32)?;
+        writer.write_all(key.as_bytes())?;
+        write_varint(&mut writer, value.len() as u32)?;
+        writer.write_all(value.as_bytes())?;
+    }
     
-    phonemes.join(" ")  // Space-separated!
+    writer.flush()?;
+    Ok(())
 }
 
-/// Convert with word segmentation and detailed information
-/// OPTIMIZED: Uses furigana-aware segmentation and は → wa particle handling
-fn convert_detailed_with_segmentation(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter) -> ConversionResult {
-    // 🔥 STEP 1: Parse furigana hints into structured segments
-    let segments = parse_furigana_segments(text, Some(segmenter));
-    
-    // 🔥 STEP 2: Segment into words using structured segments with phoneme fallback
-    let words = segmenter.segment_from_segments(&segments, Some(converter.get_root()));
-    
-    // 🔥 STEP 3: Convert each word to phonemes with particle handling
-    let mut all_matches = Vec::new();
-    let mut all_unmatched = Vec::new();
-    let mut phoneme_parts = Vec::new();
-    let mut byte_offset = 0;
+/// What a segmentation token is
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenKind {
+    Word,         // Dictionary word (or furigana reading)
+    Grammar,      // Unmatched run between words: particles, conjugations, ...
+    Punctuation,  // Unmatched run made only of punctuation (、。「」！ ...)
+}
+
+/// One token of segmented text
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    text: String,
+    kind: TokenKind,
+    phonemes: Option<String>,  // Inline IPA override, used verbatim instead of converting
+    source: Option<String>,    // Base text when `text` is a furigana reading (健太 for けんた)
+    space_after: bool,         // Whitespace followed it in the input (segment_from_segments)
+}
+
+impl Token {
+    /// Dictionary word token
+    fn word(text: String) -> Self {
+        Token { text, kind: TokenKind::Word, phonemes: None, source: None, space_after: false }
+    }
     
-    for word in &words {
-        // Special handling for the topic particle は → "wa"
-        if word == "は" {
-            phoneme_parts.push("wa".to_string());
-            // Add to matches for consistency
-            all_matches.push(Match {
-                original: word.clone(),
-                phoneme: "wa".to_string(),
-                start_index: byte_offset,
-            });
+    /// Token for an unmatched run: Punctuation if every char is punctuation
+    fn unmatched(text: String) -> Self {
+        let kind = if text.chars().all(is_punctuation) {
+            TokenKind::Punctuation
         } else {
-            let mut word_result = converter.convert_detailed(word);
-            
-            // Adjust match positions to account for original text position
-            for match_item in &mut word_result.matches {
-                match_item.start_index += byte_offset;
-                all_matches.push(match_item.clone());
-            }
-            
-            phoneme_parts.push(word_result.phonemes);
-            all_unmatched.extend(word_result.unmatched);
-        }
-        
-        byte_offset += word.len();
+            TokenKind::Grammar
+        };
+        Token { text, kind, phonemes: None, source: None, space_after: false }
     }
+}
+
+/// Japanese and ASCII punctuation (CJK symbols, fullwidth forms, ・)
+/// The prolonged sound mark ー is a kana, not punctuation.
+fn is_punctuation(ch: char) -> bool {
+    let cp = ch as u32;
+    ch.is_ascii_punctuation() ||
+    (cp >= 0x2000 && cp <= 0x206F) ||  // General punctuation (…, ‥, “ ”)
+    (cp >= 0x3000 && cp <= 0x303F) ||  // CJK symbols (、。「」『』〜)
+    ch == '・' ||
+    (cp >= 0xFF01 && cp <= 0xFF0F) ||  // Fullwidth ！＂＃ ... ／
+    (cp >= 0xFF1A && cp <= 0xFF20) ||  // Fullwidth ：；＜＝＞？＠
+    (cp >= 0xFF3B && cp <= 0xFF40) ||  // Fullwidth ［＼］＾＿｀
+    (cp >= 0xFF5B && cp <= 0xFF65)     // Fullwidth ｛｜｝～ and halfwidth ｡｢｣､･
+}
+
+/// Escape a string for a JSON string literal (quotes, backslashes, control chars)
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
     
-    ConversionResult {
-        phonemes: phoneme_parts.join(" "),
-        matches: all_matches,
-        unmatched: all_unmatched,
+    for ch in text.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
     }
+    
+    escaped
+}
+
+/// Cursor over JSON text for parse_flat_json()
+struct JsonReader<'a> {
+    text: &'a str,
+    pos: usize,  // Byte position of the next char
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("╔══════════════════════════════════════════════════════════╗");
-    println!("║  Japanese → Phoneme Converter (Rust)                    ║");
-    println!("║  Blazing fast IPA phoneme conversion                    ║");
-    println!("╚══════════════════════════════════════════════════════════╝\n");
+impl<'a> JsonReader<'a> {
+    /// Error pointing at the current position and line
+    fn error(&self, message: &str) -> ConverterError {
+        let line = self.text[..self.pos].matches('\n').count() + 1;
+        ConverterError::JsonParse { position: self.pos, line, message: message.to_string() }
+    }
     
-    // Check if JSON file exists
-    if !std::path::Path::new("ja_phonemes.json").exists() {
-        eprintln!("❌ Error: ja_phonemes.json not found in current directory");
-        eprintln!("   Please ensure the phoneme dictionary is present.");
-        std::process::exit(1);
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
     }
     
-    // Initialize converter and load dictionary
-    // 🚀 Try binary trie first (100x faster!), fallback to JSON
-    let mut converter = PhonemeConverter::new();
-    let mut loaded_binary = false;
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
     
-    // Try simple binary format (direct load into TrieNode)
-    match converter.try_load_binary_format("japanese.trie") {
-        Ok(true) => {
-            loaded_binary = true;
-            println!("   💡 Binary format loaded directly into TrieNode");
-        }
-        Ok(false) => {
-            // Fallback to JSON
-            println!("   ⚠️  Binary trie not found, loading JSON...");
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            self.next();
         }
-        Err(e) => {
-            eprintln!("⚠️  Error loading binary trie: {}", e);
-            eprintln!("   Falling back to JSON...");
+    }
+    
+    /// Skip whitespace, then consume `expected`
+    fn expect(&mut self, expected: char) -> Result<(), ConverterError> {
+        self.skip_whitespace();
+        match self.next() {
+            Some(ch) if ch == expected => Ok(()),
+            Some(ch) => Err(self.error(&format!("expected '{}', found '{}'", expected, ch))),
+            None => Err(self.error(&format!("expected '{}', found end of input", expected))),
         }
     }
     
-    if !loaded_binary {
-        converter.load_from_json("ja_phonemes.json")?;
+    /// Rest of a string whose opening quote was consumed, escapes decoded
+    fn string(&mut self) -> Result<String, ConverterError> {
+        let mut value = String::new();
+        
+        loop {
+            match self.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => value.push(self.escape()?),
+                Some(ch) => value.push(ch),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
     }
     
-    // Initialize word segmenter if enabled
-    let mut segmenter: Option<WordSegmenter> = None;
-    if USE_WORD_SEGMENTATION {
-        // If using binary format, words are already loaded in converter's trie!
-        // We still need to create a WordSegmenter that uses the converter's trie
-        if loaded_binary {
-            println!("   💡 Word segmentation: Words already in TrieNode from binary format");
-            // Create an empty WordSegmenter - it will use converter's trie as phoneme fallback
-            // The segmentation will work because segment_from_segments() uses phoneme_root fallback
-            segmenter = Some(WordSegmenter::new());
-            // Don't load ja_words.txt - words are already in converter's trie
-        } else {
-            // Load separate word file for JSON mode
-            if std::path::Path::new("ja_words.txt").exists() {
-                let mut seg = WordSegmenter::new();
-                match seg.load_from_file("ja_words.txt") {
-                    Ok(_) => {
-                        println!("   💡 Word segmentation: ENABLED (spaces will separate words)");
-                        segmenter = Some(seg);
+    /// The escape after a backslash: \n, \", \u3042, \uD83D\uDE00 (surrogate pair)
+    fn escape(&mut self) -> Result<char, ConverterError> {
+        let ch = match self.next() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('u') => {
+                let unit = self.hex4()?;
+                let code = if (0xD800..0xDC00).contains(&unit) {
+                    if self.next() != Some('\\') || self.next() != Some('u') {
+                        return Err(self.error("high surrogate without a following \\u low surrogate"));
                     }
-                    Err(e) => {
-                        eprintln!("⚠️  Warning: Could not load word dictionary: {}", e);
-                        eprintln!("   Continuing without word segmentation...");
+                    let low = self.hex4()?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error(&format!("invalid low surrogate \\u{:04X}", low)));
                     }
-                }
-            } else {
-                println!("   💡 Word segmentation: DISABLED (ja_words.txt not found)");
+                    0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)
+                } else {
+                    unit
+                };
+                return std::char::from_u32(code).ok_or_else(|| self.error(&format!("invalid code point \\u{:04X}", code)));
             }
-        }
+            Some(other) => return Err(self.error(&format!("invalid escape \\{}", other))),
+            None => return Err(self.error("unterminated escape")),
+        };
+        Ok(ch)
     }
     
-    println!("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
-    
-    let args: Vec<String> = env::args().skip(1).collect();
+    /// Four hex digits of a \u escape
+    fn hex4(&mut self) -> Result<u32, ConverterError> {
+        let end = self.text[self.pos..].char_indices().nth(4).map_or(self.text.len(), |(i, _)| self.pos + i);
+        let hex = &self.text[self.pos..end];
+        match u32::from_str_radix(hex, 16) {
+            Ok(unit) if hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                self.pos = end;
+                Ok(unit)
+            }
+            _ => Err(self.error(&format!("invalid \\u escape \"{}\"", hex))),
+        }
+    }
     
-    // Handle command-line arguments
-    if args.is_empty() {
-        // Interactive mode
-        println!("💡 Usage: ./jpn_to_phoneme \"日本語テキスト\"");
-        println!("   Or enter Japanese text interactively:\n");
+    /// A bare value: a number or true/false as its literal text, None for null
+    fn literal(&mut self) -> Result<Option<String>, ConverterError> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if !c.is_whitespace() && c != ',' && c != '}') {
+            self.next();
+        }
         
-        let stdin = io::stdin();
+        let literal = &self.text[start..self.pos];
+        let is_number = literal.starts_with(|c: char| c == '-' || c.is_ascii_digit()) && literal.parse::<f64>().is_ok();
+        match literal {
+            "null" => Ok(None),
+            "true" | "false" => Ok(Some(literal.to_string())),
+            _ if is_number => Ok(Some(literal.to_string())),
+            "" => Err(self.error("missing value")),
+            _ => Err(self.error(&format!("unexpected value {}", literal))),
+        }
+    }
+}
+
+/// Parse a flat JSON object into key → value strings (dictionary files)
+/// 
+/// - String escapes are decoded per the JSON spec, \uXXXX surrogate pairs
+///   included; an unknown escape or unpaired surrogate is an error
+/// - Empty-string values are kept; null values are skipped
+/// - Numbers, true and false are kept as their literal text ("1.5")
+/// - Nested objects and arrays are rejected, as is anything after the object
+/// 
+/// Errors name the line they were found on.
+fn parse_flat_json(json: &str) -> Result<HashMap<String, String>, ConverterError> {
+    let mut reader = JsonReader { text: json.trim_start_matches('\u{feff}'), pos: 0 };
+    let mut result = HashMap::new();
+    
+    reader.expect('{')?;
+    reader.skip_whitespace();
+    if reader.peek() == Some('}') {
+        reader.next();
+    } else {
         loop {
-            print!("Japanese text (or \"quit\" to exit): ");
-            io::stdout().flush()?;
-            
-            let mut input = String::new();
-            stdin.read_line(&mut input)?;
-            let input = input.trim();
+            reader.expect('"')?;
+            let key = reader.string()?;
+            reader.expect(':')?;
+            reader.skip_whitespace();
             
-            if input.is_empty() {
-                continue;
+            let value = match reader.peek() {
+                Some('"') => {
+                    reader.next();
+                    Some(reader.string()?)
+                }
+                Some('{') | Some('[') => {
+                    return Err(reader.error(&format!("nested value for \"{}\" - only flat string maps are supported", key)));
+                }
+                _ => reader.literal()?,
+            };
+            if let (false, Some(value)) = (key.is_empty(), value) {
+                result.insert(key, value);
             }
             
-            if input.eq_ignore_ascii_case("quit") || input.eq_ignore_ascii_case("exit") {
-                println!("\n👋 Goodbye!");
-                break;
+            reader.skip_whitespace();
+            match reader.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                Some(ch) => return Err(reader.error(&format!("expected ',' or '}}', found '{}'", ch))),
+                None => return Err(reader.error("missing closing brace")),
             }
-            
-            // Perform conversion with timing
-            let start_time = Instant::now();
-            let result = if let Some(ref seg) = segmenter {
-                convert_detailed_with_segmentation(&converter, input, seg)
-            } else {
-                converter.convert_detailed(input)
-            };
-            let elapsed = start_time.elapsed();
-            
-            // Display results
-            println!("\n┌─────────────────────────────────────────");
-            println!("│ Input:    {}", input);
-            println!("│ Phonemes: {}", result.phonemes);
-            println!("│ Time:     {}μs", elapsed.as_micros());
-            println!("└─────────────────────────────────────────");
-            
-            // Show detailed matches
-            if !result.matches.is_empty() {
-                println!("\n  Matches ({}):", result.matches.len());
-                for m in &result.matches {
-                    println!("    • {}", m.to_string());
+        }
+    }
+    
+    reader.skip_whitespace();
+    if reader.peek().is_some() {
+        return Err(reader.error("unexpected content after the closing brace"));
+    }
+    
+    Ok(result)
+}
+
+/// Caller hook that merges or splits segmented words (set_boundary_adjuster())
+type BoundaryAdjuster = Box<dyn Fn(Vec<String>) -> Vec<String> + Send + Sync>;
+
+/// Word segmenter using longest-match algorithm with word dictionary
+/// Splits Japanese text into words for better phoneme spacing
+pub struct WordSegmenter {
+    root: TrieNode,
+    word_count: usize,
+    
+    // Treat whitespace already in the input as a hard word boundary:
+    // no dictionary match may span it (honors upstream tokenization)
+    respect_input_spaces: bool,
+    
+    // Keep honorific お/ご/御 attached to the following word (お水, ご飯)
+    // instead of splitting it off as a lone grammar token
+    attach_honorific_prefixes: bool,
+    
+    // Maximum chars any segmentation match may span, in the word list or the
+    // phoneme dictionary fallback (bounds worst-case segmentation). None: no
+    // cap beyond the dictionaries themselves, so a word-list walk stops at
+    // longest_word - see set_max_word_len()
+    max_word_len: Option<usize>,
+    
+    // Length in chars of the longest word loaded, the natural cap of the list
+    longest_word: usize,
+    
+    // Let words match through ー marks inside them (ながーい → one word ながい),
+    // for casual-text elongation (ConversionOptions::elongation)
+    skip_elongation_marks: bool,
+    
+    // Keep reduplicated onomatopoeia (わんわん, きらきら, ぽよぽよ) as one word,
+    // even when unknown or when a longer match would cut into it (がわ|んわん)
+    group_reduplication: bool,
+    
+    // Drop a word identical to the one right before it (これ これ は → これ は),
+    // for stutters and repeats in ASR transcripts
+    dedup_consecutive_words: bool,
+    
+    // Convert pure-kana input (no kanji, spaces, punctuation or particle
+    // kana) with the plain trie walk of convert() instead of segmenting it,
+    // for callers that want speed over word spacing - see uses_kana_fast_path()
+    kana_fast_path: bool,
+    
+    // Segment normal text with segment_lattice() (fewest unmatched chars,
+    // then fewest words) instead of greedy longest match
+    lattice_segmentation: bool,
+    
+    // Caller hook that merges or splits words after segmentation and before
+    // conversion, for edge cases not worth a dictionary edit - see
+    // adjust_boundaries() (None = keep the segmenter's words)
+    boundary_adjuster: Option<BoundaryAdjuster>,
+}
+
+/// Honorific prefixes that attach to the following noun
+const HONORIFIC_PREFIXES: [char; 3] = ['お', 'ご', '御'];
+
+/// Walk a trie from `pos` and return the length of the longest terminal key (0 if none)
+/// With `stop_at_whitespace`, the walk never crosses a whitespace character.
+/// With `skip_marks`, ー after the first char is stepped over unless the trie
+/// continues with it (the match length still counts it).
+/// Without `stop_at_whitespace`, a whitespace run follows the space of a
+/// multi-word key (see step_trie()).
+/// At most `max_len` chars are considered (a whitespace run counts as one).
+/// A walk starting at hiragana を stops after it: modern text writes を only
+/// as the object particle, so longer keys (をし, をとこ) are classical
+/// spellings that would swallow the word after it (ゲーム|を|します).
+fn longest_terminal_match(root: &TrieNode, chars: &[char], pos: usize, stop_at_whitespace: bool, skip_marks: bool, max_len: usize) -> usize {
+    let mut match_length = 0;
+    for_each_terminal_match(root, chars, pos, stop_at_whitespace, skip_marks, max_len, |length| match_length = length);
+    match_length
+}
+
+/// Walk a trie from `pos` like longest_terminal_match(), calling `on_match`
+/// with the length of every terminal key passed, shortest first
+fn for_each_terminal_match(root: &TrieNode, chars: &[char], pos: usize, stop_at_whitespace: bool, skip_marks: bool, max_len: usize, mut on_match: impl FnMut(usize)) {
+    let mut current = root;
+    let mut i = pos;
+    let mut considered = 0;
+    let max_len = if chars.get(pos) == Some(&'を') { max_len.min(1) } else { max_len };
+    
+    while i < chars.len() && considered < max_len {
+        considered += 1;
+        let ch = chars[i];
+        if stop_at_whitespace && ch.is_whitespace() {
+            break;
+        }
+        
+        if skip_marks && i > pos && ch == 'ー' && !current.children.contains_key(&ch) {
+            i += 1;
+            continue;
+        }
+        
+        match step_trie(current, chars, i) {
+            Some((child, next)) => {
+                current = child;
+                i = next;
+                
+                // If this node marks end of word, it's a valid match
+                if current.is_terminal {
+                    on_match(i - pos);
                 }
             }
-            
-            if !result.unmatched.is_empty() {
-                print!("\n  ⚠️  Unmatched characters: ");
-                for (i, ch) in result.unmatched.iter().enumerate() {
-                    if i > 0 {
-                        print!(", ");
-                    }
-                    print!("{}", ch);
+            None => break,
+        }
+    }
+}
+
+impl Default for WordSegmenter {
+    fn default() -> Self {
+        WordSegmenter::new()
+    }
+}
+
+impl WordSegmenter {
+    pub fn new() -> Self {
+        WordSegmenter {
+            root: TrieNode::default(),
+            word_count: 0,
+            respect_input_spaces: false,
+            attach_honorific_prefixes: true,
+            max_word_len: None,
+            longest_word: 0,
+            skip_elongation_marks: false,
+            group_reduplication: true,
+            dedup_consecutive_words: false,
+            kana_fast_path: false,
+            lattice_segmentation: false,
+            boundary_adjuster: None,
+        }
+    }
+    
+    /// Cap every segmentation match at `max_word_len` chars (see max_word_len)
+    /// A word longer than the cap is split at it, like text with no match.
+    pub fn set_max_word_len(&mut self, max_word_len: usize) {
+        self.max_word_len = Some(max_word_len);
+    }
+    
+    /// Chars a segmentation walk may consider (usize::MAX without a cap)
+    fn walk_limit(&self) -> usize {
+        self.max_word_len.unwrap_or(usize::MAX)
+    }
+    
+    /// Treat input whitespace as a hard word boundary (see respect_input_spaces)
+    pub fn set_respect_input_spaces(&mut self, respect_input_spaces: bool) {
+        self.respect_input_spaces = respect_input_spaces;
+    }
+    
+    /// Keep お/ご/御 attached to the following word (see attach_honorific_prefixes)
+    pub fn set_attach_honorific_prefixes(&mut self, attach_honorific_prefixes: bool) {
+        self.attach_honorific_prefixes = attach_honorific_prefixes;
+    }
+    
+    /// Let words match through ー marks inside them (see skip_elongation_marks)
+    pub fn set_skip_elongation_marks(&mut self, skip_elongation_marks: bool) {
+        self.skip_elongation_marks = skip_elongation_marks;
+    }
+    
+    /// Keep reduplicated onomatopoeia as one word (see group_reduplication)
+    pub fn set_group_reduplication(&mut self, group_reduplication: bool) {
+        self.group_reduplication = group_reduplication;
+    }
+    
+    /// Drop a word repeating the one before it (see dedup_consecutive_words)
+    pub fn set_dedup_consecutive_words(&mut self, dedup_consecutive_words: bool) {
+        self.dedup_consecutive_words = dedup_consecutive_words;
+    }
+    
+    /// Convert pure-kana input without segmenting it (see kana_fast_path)
+    pub fn set_kana_fast_path(&mut self, kana_fast_path: bool) {
+        self.kana_fast_path = kana_fast_path;
+    }
+    
+    /// Segment with segment_lattice() instead of greedy longest match (see lattice_segmentation)
+    pub fn set_lattice_segmentation(&mut self, lattice_segmentation: bool) {
+        self.lattice_segmentation = lattice_segmentation;
+    }
+    
+    /// Install a boundary adjuster (see adjust_boundaries())
+    pub fn set_boundary_adjuster<F>(&mut self, adjuster: F)
+    where
+        F: Fn(Vec<String>) -> Vec<String> + Send + Sync + 'static,
+    {
+        self.boundary_adjuster = Some(Box::new(adjuster));
+    }
+    
+    /// True if convert_with_segmentation() may skip segmenting `text`
+    /// 
+    /// Pure kana has no kanji for segmentation to resolve, so spacing is all
+    /// it adds. The phonemes match the segmented ones except for the spaces
+    /// (and the rare dictionary key spanning a word boundary). Input with a
+    /// particle kana (は, へ, を) still segments so a particle reads wa/e/o, as
+    /// does anything a per-word setting (word_case, dedup, boundary adjuster)
+    /// would change.
+    fn uses_kana_fast_path(&self, text: &str, options: &ConversionOptions) -> bool {
+        self.kana_fast_path && !text.is_empty() &&
+            !self.dedup_consecutive_words && self.boundary_adjuster.is_none() &&
+            options.word_case == WordCase::Preserve &&
+            text.chars().all(|c| is_kana(c) && !matches!(c, '・' | '゠')) &&
+            !PARTICLE_READINGS.iter().any(|&(particle, _)| text.contains(particle))
+    }
+    
+    /// Run the boundary adjuster on a word list (unchanged without one)
+    /// 
+    /// The adjuster sees the words segmented conversion is about to convert -
+    /// after furigana parsing, segmentation and ー/っ gluing - and returns the
+    /// words to convert instead. It is meant to move boundaries, not edit text:
+    /// ["東京", "都"] → ["東京都"] merges, ["ちょっと"] → ["ちょ", "っと"] splits.
+    fn adjust_boundaries(&self, words: Vec<String>) -> Vec<String> {
+        match self.boundary_adjuster {
+            Some(ref adjuster) => adjuster(words),
+            None => words,
+        }
+    }
+    
+    /// Apply the boundary adjuster to tokens, keeping what the tokens carry
+    /// 
+    /// A word that is exactly one original token keeps that token (furigana
+    /// source, IPA override, kind). Merged and split words become plain word
+    /// tokens, with space_after taken from the token a word ends with.
+    fn adjust_token_boundaries(&self, tokens: Vec<Token>) -> Vec<Token> {
+        if self.boundary_adjuster.is_none() {
+            return tokens;
+        }
+        
+        let words = self.adjust_boundaries(tokens.iter().map(|token| token.text.clone()).collect());
+        let mut adjusted = Vec::with_capacity(words.len());
+        let mut originals = tokens.into_iter().map(Some).collect::<Vec<_>>();
+        let mut index = 0;   // Original token the next word starts in
+        let mut offset = 0;  // Chars of that token already covered
+        
+        for word in words {
+            let start = (index, offset);
+            let mut remaining = word.chars().count();
+            while remaining > 0 && index < originals.len() {
+                let length = originals[index].as_ref().map_or(0, |token| token.text.chars().count());
+                let step = remaining.min(length - offset);
+                remaining -= step;
+                offset += step;
+                if offset == length {
+                    index += 1;
+                    offset = 0;
                 }
-                println!();
             }
             
-            println!();
-        }
-    } else {
-        // Batch mode - convert all arguments
-        for text in &args {
-            // Perform conversion with timing
-            let start_time = Instant::now();
-            let result = if let Some(ref seg) = segmenter {
-                convert_detailed_with_segmentation(&converter, text, seg)
-            } else {
-                converter.convert_detailed(text)
-            };
-            let elapsed = start_time.elapsed();
+            // Ended on a token boundary: the last covered token decides space_after
+            let ends_at_token = offset == 0 && index > 0;
+            let space_after = ends_at_token && originals[index - 1].as_ref().is_some_and(|token| token.space_after);
+            let unchanged = start.1 == 0 && ends_at_token && index == start.0 + 1 &&
+                originals[start.0].as_ref().is_some_and(|token| token.text == word);
             
-            // Display results
-            println!("┌─────────────────────────────────────────");
-            println!("│ Input:    {}", text);
-            println!("│ Phonemes: {}", result.phonemes);
-            println!("│ Time:     {}μs ({}ms)", elapsed.as_micros(), elapsed.as_millis());
-            println!("└─────────────────────────────────────────");
+            if unchanged {
+                adjusted.extend(originals[start.0].take());
+            } else {
+                adjusted.push(Token { space_after, ..Token::word(word) });
+            }
+        }
+        
+        adjusted
+    }
+    
+    /// Longest word-list match at `pos` (0 if none), capped at max_word_len chars
+    fn match_word(&self, chars: &[char], pos: usize) -> usize {
+        longest_terminal_match(&self.root, chars, pos, self.respect_input_spaces, self.skip_elongation_marks, self.walk_limit())
+    }
+    
+    /// Length of a reduplicated kana unit at `pos` (0 if none)
+    /// 
+    /// A unit of 2-4 kana repeated right away (わん|わん, ドキ|ドキ) counts
+    /// as one word of twice its length. The unit can't start with ー, ッ, ン
+    /// or a small kana, so it's always a whole mora sequence.
+    fn match_reduplication(&self, chars: &[char], pos: usize) -> usize {
+        if !self.group_reduplication {
+            return 0;
+        }
+        
+        for unit in (2..=4).rev() {
+            if pos + 2 * unit > chars.len() {
+                continue;
+            }
             
-            // Show detailed matches
-            if !result.matches.is_empty() {
-                println!("\n  ✅ Matches ({}):", result.matches.len());
-                for m in &result.matches {
-                    println!("    • {}", m.to_string());
-                }
+            let first = &chars[pos..pos + unit];
+            let starts_mora = !matches!(first[0], 'ー' | 'ッ' | 'っ' | 'ン' | 'ん' | 'ャ' | 'ュ' | 'ョ' | 'ゃ' | 'ゅ' | 'ょ' | 'ァ' | 'ィ' | 'ゥ' | 'ェ' | 'ォ' | 'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ');
+            if starts_mora && first.iter().all(|&c| is_kana(c)) && first == &chars[pos + unit..pos + 2 * unit] {
+                return 2 * unit;
+            }
+        }
+        
+        0
+    }
+    
+    /// Shorten a match at `pos` so it ends where a reduplication begins
+    /// (がわんわん: the word がわ would cut わんわん, so only が is taken)
+    fn clip_before_reduplication(&self, chars: &[char], pos: usize, length: usize) -> usize {
+        (pos + 1..pos + length)
+            .find(|&p| self.match_reduplication(chars, p) > 0)
+            .map_or(length, |p| p - pos)
+    }
+    
+    /// Length of an honorific prefix plus the word after it (0 if not applicable)
+    /// 
+    /// Fires when お/ご/御 is followed by a known word and prefix+word is longer
+    /// than the plain match at `pos` (お水 = お + 水, but おいしい stays one word).
+    /// The following word must start with kanji or be 2+ chars long, so a
+    /// prefix never swallows a lone kana particle.
+    fn match_honorific(&self, chars: &[char], pos: usize, phoneme_root: Option<&TrieNode>) -> usize {
+        if !self.attach_honorific_prefixes || pos + 1 >= chars.len() || !HONORIFIC_PREFIXES.contains(&chars[pos]) {
+            return 0;
+        }
+        
+        // Word list first, phoneme dictionary as fallback (same as segmentation),
+        // leaving room for the prefix under max_word_len
+        let match_at = |p: usize, max_len: usize| {
+            let length = longest_terminal_match(&self.root, chars, p, self.respect_input_spaces, self.skip_elongation_marks, max_len);
+            match phoneme_root {
+                Some(root) if length == 0 => longest_terminal_match(root, chars, p, self.respect_input_spaces, self.skip_elongation_marks, max_len),
+                _ => length,
+            }
+        };
+        
+        let word_length = match_at(pos + 1, self.walk_limit().saturating_sub(1));
+        if word_length == 0 || (word_length == 1 && is_kana(chars[pos + 1])) {
+            return 0;
+        }
+        
+        if 1 + word_length > match_at(pos, self.walk_limit()) {
+            1 + word_length
+        } else {
+            0
+        }
+    }
+    
+    /// Get root node for trie walking (used in compound detection)
+    fn get_root(&self) -> &TrieNode {
+        &self.root
+    }
+    
+    /// Check if a word exists in the dictionary
+    /// Returns true if the word is a complete entry
+    pub fn contains_word(&self, word: &str) -> bool {
+        if word.is_empty() {
+            return false;
+        }
+        
+        let mut current = &self.root;
+        
+        for ch in word.chars() {
+            if let Some(child) = current.children.get(&ch) {
+                current = child;
+            } else {
+                return false; // Path doesn't exist
             }
+        }
+        
+        // Check if this is a valid end-of-word node
+        current.is_terminal
+    }
+    
+    /// Load word list from text file (one word per line)
+    pub fn load_from_file(&mut self, file_path: &str) -> Result<(), ConverterError> {
+        status!("🔥 Loading word dictionary for segmentation...");
+        let start_time = Instant::now();
+        
+        let file = fs::File::open(file_path)?;
+        let reader = BufReader::new(file);
+        
+        for line in reader.lines() {
+            let word = line?;
+            let word = word.trim();
             
-            if !result.unmatched.is_empty() {
-                print!("\n  ⚠️  Unmatched characters: ");
-                for (i, ch) in result.unmatched.iter().enumerate() {
-                    if i > 0 {
-                        print!(", ");
-                    }
-                    print!("{}", ch);
+            if !word.is_empty() {
+                self.insert_word(word);
+                self.word_count += 1;
+                
+                if self.word_count % 50000 == 0 && !QUIET.load(Ordering::Relaxed) {
+                    print!("\r   Loaded: {} words", self.word_count);
+                    io::stdout().flush().unwrap();
                 }
-                println!();
             }
-            
-            println!();
         }
         
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
-        println!("✨ Conversion complete!");
+        let elapsed = start_time.elapsed();
+        status!("\n✅ Loaded {} words in {}ms (longest: {} chars)", self.word_count, elapsed.as_millis(), self.longest_word);
+        
+        Ok(())
     }
     
-    Ok(())
-}
-
+    /// Insert a word into the trie
+    fn insert_word(&mut self, word: &str) {
+        let mut current = &mut self.root;
+        let mut length = 0;
+        
+        for ch in normalize_key_whitespace(word).chars() {
+            current = current.child_or_insert(ch);
+            length += 1;
+        }
+        
+        // Reported after loading, as a starting point for max_word_len
+        self.longest_word = self.longest_word.max(length);
+        
+        // Mark end of word (no phoneme, just the terminal flag)
+        current.is_terminal = true;
+    }
+    
+    /// Segment text into words using longest-match algorithm
+    /// 
+    /// SMART SEGMENTATION: Words are matched from dictionary, and any
+    /// unmatched sequences between words are treated as grammatical elements
+    /// (particles, conjugations, etc.) and given their own space.
+    /// 
+    /// Example: 私はリンゴがすきです
+    /// - Matches: 私, リンゴ, すき
+    /// - Grammar (unmatched): は, が, です
+    /// - Result: [私, は, リンゴ, が, すき, です]
+    /// 
+    /// Word list only (no phoneme dictionary fallback, no furigana hints);
+    /// uses the lattice when lattice_segmentation is set.
+    pub fn segment(&self, text: &str) -> Vec<String> {
+        let segments = [TextSegment::new_normal(text.to_string(), 0)];
+        self.segment_from_segments(&segments, None).into_iter().map(|token| token.text).collect()
+    }
+    
+    /// Segment text into words along the best path through the match lattice
+    /// 
+    /// Same words as greedy segment() where longest match gets it right, but
+    /// a long first word can no longer strand the rest: with only 東京 and
+    /// 京都府 in the word list, greedy splits 東京都府 into 東京 | 都府 (two
+    /// unmatched chars) where the lattice finds 東 | 京都府 (one).
+    /// See segment_lattice() for how paths are scored.
+    pub fn segment_viterbi(&self, text: &str) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut words = Vec::new();
+        self.segment_lattice(&chars, None, &mut words);
+        
+        if self.dedup_consecutive_words {
+            dedup_consecutive_tokens(&mut words);
+        }
+        
+        words.into_iter().map(|token| token.text).collect()
+    }
+    
+    /// Append the tokens of `chars` along the best path through the lattice
+    /// of all word matches (Viterbi over char positions)
+    /// 
+    /// Edges at each position: every word-list match (and phoneme-dictionary
+    /// match when `phoneme_root` is given), an honorific prefix + word, a
+    /// reduplicated unit, a single unmatched char, or whitespace. A path
+    /// scores (unmatched chars, tokens) and the lowest wins, so full coverage
+    /// comes first and fewer words break ties. Paths that still tie take the
+    /// longest word first, like greedy matching. Unmatched chars next to each
+    /// other become one grammar token, as in segment(). Whitespace is skipped
+    /// and marks the token before it (space_after).
+    fn segment_lattice(&self, chars: &[char], phoneme_root: Option<&TrieNode>, words: &mut Vec<Token>) {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Edge { Word, Unmatched, Space }
+        
+        // Scored from the end: best[pos] = ((unmatched chars, tokens) from pos
+        // to the end, length of the first edge, its kind)
+        let mut best: Vec<((usize, usize), usize, Edge)> = vec![((0, 0), 0, Edge::Space); chars.len() + 1];
+        
+        for pos in (0..chars.len()).rev() {
+            if chars[pos].is_whitespace() {
+                best[pos] = (best[pos + 1].0, 1, Edge::Space);
+                continue;
+            }
+            
+            let mut lengths = Vec::new();
+            for_each_terminal_match(&self.root, chars, pos, self.respect_input_spaces, self.skip_elongation_marks, self.walk_limit(), |length| lengths.push(length));
+            if let Some(root) = phoneme_root {
+                for_each_terminal_match(root, chars, pos, self.respect_input_spaces, self.skip_elongation_marks, self.walk_limit(), |length| lengths.push(length));
+            }
+            lengths.push(self.match_honorific(chars, pos, phoneme_root));
+            lengths.push(self.match_reduplication(chars, pos));
+            lengths.sort_unstable_by(|a, b| b.cmp(a));
+            
+            // Longest word first and only a strictly lower score replaces it
+            let ((unmatched, tokens), _, _) = best[pos + 1];
+            let mut choice = ((unmatched + 1, tokens + 1), 1, Edge::Unmatched);
+            for length in lengths.into_iter().filter(|&length| length > 0) {
+                let ((unmatched, tokens), _, _) = best[pos + length];
+                if (unmatched, tokens + 1) < choice.0 || (choice.2 == Edge::Unmatched && (unmatched, tokens + 1) == choice.0) {
+                    choice = ((unmatched, tokens + 1), length, Edge::Word);
+                }
+            }
+            best[pos] = choice;
+        }
+        
+        let mut grammar_start: Option<usize> = None;
+        let mut pos = 0;
+        while pos < chars.len() {
+            let (_, length, edge) = best[pos];
+            if edge != Edge::Unmatched {
+                if let Some(start) = grammar_start.take() {
+                    words.push(Token::unmatched(chars[start..pos].iter().collect()));
+                }
+            }
+            
+            match edge {
+                Edge::Word => words.push(Token::word(chars[pos..pos + length].iter().collect())),
+                Edge::Unmatched => { grammar_start.get_or_insert(pos); }
+                Edge::Space => {
+                    if let Some(last) = words.last_mut() {
+                        last.space_after = true;
+                    }
+                }
+            }
+            pos += length;
+        }
+        if let Some(start) = grammar_start {
+            words.push(Token::unmatched(chars[start..].iter().collect()));
+        }
+    }
+    
+    /// Segment text from TextSegments using longest-match algorithm with phoneme fallback
+    /// 
+    /// SMART SEGMENTATION: Words are matched from dictionary, and any
+    /// unmatched sequences between words are treated as grammatical elements
+    /// (particles, conjugations, etc.) and given their own space.
+    /// 
+    /// This version properly handles TextSegments with furigana hints,
+    /// treating each segment as an atomic unit during segmentation.
+    /// Furigana readings are Word tokens; inline IPA overrides are Word tokens
+    /// carrying their phonemes.
+    /// 
+    /// @param phoneme_root Optional phoneme trie root for fallback lookups
+    fn segment_from_segments(&self, segments: &[TextSegment], phoneme_root: Option<&TrieNode>) -> Vec<Token> {
+        let mut words = Vec::new();
+        
+        // Process each segment
+        for segment in segments {
+            // For furigana segments, treat the entire reading as one word
+            match segment.segment_type {
+                SegmentType::FuriganaHint => {
+                    words.push(Token {
+                        source: Some(segment.text.clone()),
+                        ..Token::word(segment.reading.clone())
+                    });
+                    continue;
+                }
+                // Inline IPA overrides are one word with fixed phonemes
+                SegmentType::PhonemeOverride => {
+                    words.push(Token {
+                        text: segment.text.clone(),
+                        kind: TokenKind::Word,
+                        phonemes: Some(segment.reading.clone()),
+                        source: None,
+                        space_after: false,
+                    });
+                    continue;
+                }
+                // Escaped marker chars pass through as their own token
+                SegmentType::Literal => {
+                    words.push(Token::unmatched(segment.text.clone()));
+                    continue;
+                }
+                SegmentType::NormalText => {}
+            }
+            
+            // For normal text segments, apply word segmentation
+            let text = &segment.text;
+            let chars: Vec<char> = text.chars().collect();
+            let mut pos = 0;
+            
+            if self.lattice_segmentation {
+                self.segment_lattice(&chars, phoneme_root, &mut words);
+                continue;
+            }
+            
+            while pos < chars.len() {
+                // Skip spaces in input (remembered for explicit_space_token)
+                if chars[pos].is_whitespace() {
+                    if let Some(last) = words.last_mut() {
+                        last.space_after = true;
+                    }
+                    pos += 1;
+                    continue;
+                }
+                
+                // Try to find longest word match starting at current position
+                // Check word dictionary first, then phoneme dictionary as fallback
+                // (an honorific prefix + word counts as one word)
+                let mut match_length = self.match_honorific(&chars, pos, phoneme_root);
+                if match_length == 0 {
+                    match_length = self.match_word(&chars, pos);
+                }
+                
+                // 🔥 FALLBACK: If word dictionary didn't find a match, try phoneme dictionary
+                if match_length == 0 {
+                    if let Some(phoneme_current_root) = phoneme_root {
+                        match_length = longest_terminal_match(phoneme_current_root, &chars, pos, self.respect_input_spaces, self.skip_elongation_marks, self.walk_limit());
+                    }
+                }
+                
+                // Reduplicated onomatopoeia beat shorter matches, and no match may cut into one
+                let reduplication = self.match_reduplication(&chars, pos);
+                if reduplication > match_length {
+                    match_length = reduplication;
+                } else if match_length > 0 {
+                    match_length = self.clip_before_reduplication(&chars, pos, match_length);
+                }
+                
+                if match_length > 0 {
+                    // Found a word match - extract it
+                    let word: String = chars[pos..pos + match_length].iter().collect();
+                    words.push(Token::word(word));
+                    pos += match_length;
+                } else {
+                    // No match found - this is likely a grammatical element
+                    // Collect all consecutive unmatched characters as a single token
+                    let grammar_start = pos;
+                    
+                    // Keep collecting characters until we find another word match
+                    while pos < chars.len() {
+                        // Skip spaces
+                        if chars[pos].is_whitespace() {
+                            break;
+                        }
+                        
+                        // If a word (or honorific + word, or reduplication, or a
+                        // phoneme dictionary fallback match) starts here, stop.
+                        // A run starting with ー keeps going: the mark and the kana
+                        // after it glue to the word before (ごーる, ジャーナル)
+                        let after_long_mark = chars[grammar_start] == 'ー';
+                        let fallback = pos > grammar_start && !after_long_mark && phoneme_root.is_some_and(|root| {
+                            longest_terminal_match(root, &chars, pos, self.respect_input_spaces, self.skip_elongation_marks, self.walk_limit()) > 0
+                        });
+                        if self.match_word(&chars, pos) > 0 || self.match_honorific(&chars, pos, phoneme_root) > 0 ||
+                           self.match_reduplication(&chars, pos) > 0 || fallback {
+                            break;
+                        }
+                        
+                        // Otherwise, this character is part of the grammar sequence
+                        pos += 1;
+                    }
+                    
+                    // Extract the grammar token
+                    if pos > grammar_start {
+                        let grammar: String = chars[grammar_start..pos].iter().collect();
+                        words.push(Token::unmatched(grammar));
+                    }
+                }
+            }
+        }
+        
+        if self.dedup_consecutive_words {
+            dedup_consecutive_tokens(&mut words);
+        }
+        
+        words
+    }
+}
+
+/// Remove each token that exactly repeats the one before it (dedup_consecutive_words)
+/// Only adjacent tokens with the same text, kind and phonemes count, so これ これ
+/// collapses but これ は これ and reduplicated words (わんわん, one token) don't.
+/// The kept token takes over the dropped one's space_after.
+fn dedup_consecutive_tokens(words: &mut Vec<Token>) {
+    words.dedup_by(|next, kept| {
+        let duplicate = next.text == kept.text && next.kind == kept.kind && next.phonemes == kept.phonemes;
+        if duplicate {
+            kept.space_after = next.space_after;
+        }
+        duplicate
+    });
+}
+
+/// Helper function to check if a character is kana (hiragana or katakana)
+fn is_kana(ch: char) -> bool {
+    let cp = ch as u32;
+    (cp >= 0x3040 && cp <= 0x309F) ||  // Hiragana
+    (cp >= 0x30A0 && cp <= 0x30FF)     // Katakana
+}
+
+/// Check if a character is a CJK ideograph (unified, extension A/B+ or
+/// compatibility block)
+fn is_kanji(ch: char) -> bool {
+    let cp = ch as u32;
+    (cp >= 0x4E00 && cp <= 0x9FFF) ||    // Unified ideographs
+    (cp >= 0x3400 && cp <= 0x4DBF) ||    // Extension A
+    (cp >= 0xF900 && cp <= 0xFAFF) ||    // Compatibility ideographs
+    (cp >= 0x20000 && cp <= 0x3FFFF)     // Extensions B and later
+}
+
+/// Check furigana brackets for balance without converting
+/// 
+/// parse_furigana_segments() silently degrades on unbalanced 「」 (a stray 「
+/// turns the rest of the text into normal text, a stray 」 stays literal).
+/// This reports every bracket without a partner, in text order.
+fn check_furigana_brackets(text: &str) -> Vec<BracketWarning> {
+    let mut warnings = Vec::new();
+    let mut open_positions = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    
+    while let Some((byte_pos, ch)) = chars.next() {
+        match ch {
+            // Escaped brackets (\「) are literal text
+            '\\' if chars.peek().is_some_and(|&(_, next)| is_escapable_marker(next)) => {
+                chars.next();
+            }
+            '「' => open_positions.push(byte_pos),
+            '」' => {
+                if open_positions.pop().is_none() {
+                    warnings.push(BracketWarning { issue: BracketIssue::UnmatchedClose, position: byte_pos });
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    for position in open_positions {
+        warnings.push(BracketWarning { issue: BracketIssue::UnmatchedOpen, position });
+    }
+    
+    warnings.sort_by_key(|w| w.position);
+    warnings
+}
+
+/// Characters a preceding backslash turns into literal text in segmented
+/// conversion: the furigana brackets and any ASCII punctuation, which covers
+/// the | and / markers (or whatever reading_delimiter and
+/// ipa_override_delimiter are set to) and the backslash itself.
+/// A backslash before anything else (C:\Users) is kept as is.
+fn is_escapable_marker(ch: char) -> bool {
+    ch == '「' || ch == '」' || ch.is_ascii_punctuation()
+}
+
+/// First unescaped `target` at or after `from`, skipping escape sequences
+fn find_unescaped(chars: &[char], from: usize, target: char) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        if chars[i] == '\\' && i + 1 < chars.len() && is_escapable_marker(chars[i + 1]) {
+            i += 2;
+        } else if chars[i] == target {
+            return Some(i);
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Parse text into segments, extracting furigana hints.
+/// 
+/// This creates a structured representation of the text where each segment
+/// is either normal text or a furigana hint. This approach is cleaner than
+/// using markers and makes the processing logic more transparent.
+/// 
+/// ESCAPES: \「 \」 \| \/ \\ (see is_escapable_marker) become Literal
+/// segments holding just the char, so no marker parser (furigana, pipe
+/// readings, IPA overrides) ever sees them: 「\「」 keeps the bracket.
+/// 
+/// SMART COMPOUND WORD DETECTION:
+/// - If kanji「reading」+following text forms a dictionary word, prefer dictionary
+/// - Example: 見「み」て → Check if 見て is a word → YES → Keep as normal text "見て"
+/// - Example: 健太「けんた」て → Check if 健太て is a word → NO → Use furigana "けんた"
+/// 
+/// @param text Input text with potential furigana hints (e.g., 健太「けんた」)
+/// @param segmenter Optional word segmenter for compound word detection
+fn parse_furigana_segments(text: &str, segmenter: Option<&WordSegmenter>) -> Vec<TextSegment> {
+    let mut segments = Vec::new();
+    
+    // Pre-decode UTF-8 to chars for blazing speed
+    let chars: Vec<char> = text.chars().collect();
+    let mut byte_positions = Vec::new();
+    let mut byte_pos = 0;
+    
+    for ch in &chars {
+        byte_positions.push(byte_pos);
+        byte_pos += ch.len_utf8();
+    }
+    byte_positions.push(byte_pos);
+    
+    let mut pos = 0;
+    
+    while pos < chars.len() {
+        // Look for opening bracket 「 (U+300C)
+        let bracket_open = find_unescaped(&chars, pos, '「');
+        
+        // Escape sequences before it are split off as literal segments
+        let escape = (pos..bracket_open.unwrap_or(chars.len()))
+            .find(|&i| chars[i] == '\\' && i + 1 < chars.len() && is_escapable_marker(chars[i + 1]));
+        if let Some(escape) = escape {
+            if escape > pos {
+                let text_str: String = chars[pos..escape].iter().collect();
+                segments.push(TextSegment::new_normal(text_str, byte_positions[pos]));
+            }
+            segments.push(TextSegment::new_literal(chars[escape + 1].to_string(), byte_positions[escape + 1]));
+            pos = escape + 2;
+            continue;
+        }
+        
+        if bracket_open.is_none() {
+            // No more furigana hints, add rest of text as normal segment
+            if pos < chars.len() {
+                let text_str: String = chars[pos..].iter().collect();
+                segments.push(TextSegment::new_normal(text_str, byte_positions[pos]));
+            }
+            break;
+        }
+        
+        let bracket_open = bracket_open.unwrap();
+        
+        // Look for closing bracket 」 (U+300D)
+        let bracket_close = find_unescaped(&chars, bracket_open + 1, '」');
+        
+        if bracket_close.is_none() {
+            // No closing bracket, add rest as normal segment
+            let text_str: String = chars[pos..].iter().collect();
+            segments.push(TextSegment::new_normal(text_str, byte_positions[pos]));
+            break;
+        }
+        
+        let bracket_close = bracket_close.unwrap();
+        
+        // Find where the "word" (kanji) starts before the opening bracket
+        // Search backwards to find the start of the kanji/word that has furigana
+        let mut last_kanji_pos = bracket_open;
+        while last_kanji_pos > pos && is_kana(chars[last_kanji_pos - 1]) {
+            last_kanji_pos -= 1;
+        }
+        
+        if last_kanji_pos > pos {
+            last_kanji_pos -= 1; // Now pointing at the last kanji
+        }
+        
+        // Second pass: From last kanji, search backward for word boundary
+        let mut word_start = last_kanji_pos;
+        let mut search_pos = last_kanji_pos;
+        
+        while search_pos > pos {
+            search_pos -= 1;
+            let ch = chars[search_pos];
+            let cp = ch as u32;
+            
+            // Check for punctuation boundaries
+            if matches!(ch, '」' | '、' | '。' | '！' | '？' | '）' | '］') ||
+               (cp < 0x80 && matches!(ch, '.' | ',' | '!' | '?' | ';' | ':' | '(' | ')' | '[' | ']' | 
+                                      '{' | '}' | '"' | '\'' | '-' | '/' | '\\' | '|' | ' ' | '\t' | '\n' | '\r')) {
+                word_start = search_pos + 1;
+                break;
+            }
+            
+            // Check if this is kana
+            if is_kana(ch) {
+                // Check if there's ANY kanji before this position
+                let has_kanji_before = chars[pos..search_pos].iter().any(|&c| {
+                    let code = c as u32;
+                    code >= 0x4E00 || (code >= 0x3400 && code <= 0x9FFF)
+                });
+                
+                if !has_kanji_before {
+                    // This kana is not sandwiched - it's a prefix word → stop here
+                    word_start = search_pos + 1;
+                    break;
+                }
+                // Otherwise, this kana is sandwiched (okurigana) → continue
+            }
+            
+            // Update word_start to include this character
+            word_start = search_pos;
+        }
+        
+        // Add text from current position up to where the word/kanji starts
+        if word_start > pos {
+            let text_str: String = chars[pos..word_start].iter().collect();
+            segments.push(TextSegment::new_normal(text_str, byte_positions[pos]));
+        }
+        
+        // Extract the kanji and reading
+        let kanji: String = chars[word_start..bracket_open].iter().collect();
+        let reading: String = chars[bracket_open + 1..bracket_close].iter().collect();
+        let reading = reading.trim().to_string();
+        
+        if reading.is_empty() {
+            // Empty reading - skip the entire furigana hint
+            pos = bracket_close + 1;
+            continue;
+        }
+        
+        // 🔥 SMART COMPOUND WORD DETECTION USING TRIE'S LONGEST-MATCH
+        let after_bracket = bracket_close + 1;
+        let mut used_compound = false;
+        
+        if let Some(seg) = segmenter {
+            if after_bracket < chars.len() {
+                // Use trie to find longest match starting from word_start position
+                let mut match_length = 0;
+                let mut current = seg.get_root();
+                
+                // Walk trie through kanji characters first
+                for i in word_start..bracket_open {
+                    if let Some(child) = current.children.get(&chars[i]) {
+                        current = child;
+                    } else {
+                        break;
+                    }
+                }
+                
+                // Continue walking through characters after the bracket
+                for i in after_bracket..chars.len() {
+                    if let Some(child) = current.children.get(&chars[i]) {
+                        current = child;
+                        
+                        // Check if this position marks a valid word ending
+                        if current.is_terminal {
+                            // Found a compound! Track it as the longest so far
+                            match_length = i - after_bracket + 1;
+                        }
+                    } else {
+                        break;
+                    }
+                }
+                
+                // If we found a compound word, use it with the furigana reading
+                if match_length > 0 {
+                    let suffix: String = chars[after_bracket..after_bracket + match_length].iter().collect();
+                    let compound = format!("{}{}", reading, suffix);
+                    segments.push(TextSegment::new_normal(compound, byte_positions[word_start]));
+                    pos = after_bracket + match_length;
+                    used_compound = true;
+                }
+            }
+        }
+        
+        if !used_compound {
+            // No compound found, use the furigana hint
+            segments.push(TextSegment::new_furigana(kanji, reading, byte_positions[word_start]));
+            pos = bracket_close + 1;
+        }
+    }
+    
+    segments
+}
+
+/// Particles read differently from their kana when the segmenter leaves them
+/// as a word of their own: topic は, direction へ, object を. The same kana
+/// inside a word keep their dictionary reading (はな → hana, へや → heja).
+const PARTICLE_READINGS: [(&str, &str); 3] = [("は", "wa"), ("へ", "e"), ("を", "o")];
+
+/// Convert with word segmentation support
+/// OPTIMIZED: Uses furigana-aware segmentation and particle handling (は → wa)
+/// 
+/// Example: 健太「けんた」はバカ → kẽ̞ɴta wa baka
+pub fn convert_with_segmentation(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter) -> String {
+    // Pure kana: one trie walk, no words to space (kana_fast_path)
+    if segmenter.uses_kana_fast_path(text, &converter.options) {
+        return converter.convert(text);
+    }
+    
+    // Words mapped to "" (silent entries) are dropped so no double spaces appear
+    // (the pause token of explicit_space_token is paired with " " and not cased)
+    let phonemes: Vec<String> = convert_aligned(converter, text, segmenter).into_iter()
+        .filter(|(_, phonemes)| !phonemes.is_empty())
+        .map(|(word, phonemes)| if word == " " { phonemes } else { converter.options.word_case.apply(phonemes) })
+        .collect();
+    
+    if converter.options.reverse_morae {
+        return reverse_morae(&phonemes.join(" "));
+    }
+    
+    phonemes.join(" ")  // Space-separated!
+}
+
+/// Script class of a char for finding the token an IPA override applies to
+/// 0 = boundary (whitespace, punctuation, ...)
+fn script_class(ch: char) -> u8 {
+    let cp = ch as u32;
+    if (cp >= 0x4E00 && cp <= 0x9FFF) || (cp >= 0x3400 && cp <= 0x4DBF) || ch == '々' {
+        1  // Kanji
+    } else if cp >= 0x3040 && cp <= 0x309F {
+        2  // Hiragana
+    } else if cp >= 0x30A0 && cp <= 0x30FF && ch != '・' {
+        3  // Katakana (with ー)
+    } else if ch.is_alphanumeric() {
+        4  // Latin letters, digits
+    } else {
+        0
+    }
+}
+
+/// Split interlinear base|reading annotations out of normal-text segments
+/// 
+/// `日本語|にほんご` (with delimiter '|') becomes a FuriganaHint segment, the
+/// same as 日本語「にほんご」. The base is the run of same-script characters
+/// right before the delimiter (see split_ipa_overrides()), the reading the
+/// run of kana right after it - up to whitespace or a non-kana char, so a
+/// following particle needs a space (日本語|にほんご を). A delimiter without
+/// both is left as text.
+fn split_pipe_readings(segments: Vec<TextSegment>, delimiter: char) -> Vec<TextSegment> {
+    let mut result = Vec::with_capacity(segments.len());
+    
+    for segment in segments {
+        if !matches!(segment.segment_type, SegmentType::NormalText) || !segment.text.contains(delimiter) {
+            result.push(segment);
+            continue;
+        }
+        
+        let chars: Vec<char> = segment.text.chars().collect();
+        let byte_at = |index: usize| -> usize {
+            segment.original_pos + chars[..index].iter().map(|c| c.len_utf8()).sum::<usize>()
+        };
+        
+        let mut start = 0;  // Start of text not yet emitted
+        let mut pos = 0;
+        
+        while pos < chars.len() {
+            if chars[pos] != delimiter {
+                pos += 1;
+                continue;
+            }
+            
+            let class = if pos > start { script_class(chars[pos - 1]) } else { 0 };
+            let reading_end = chars[pos + 1..].iter().position(|&ch| !is_kana(ch) || ch == '・')
+                .map_or(chars.len(), |p| pos + 1 + p);
+            
+            if class == 0 || reading_end == pos + 1 {
+                pos += 1;
+                continue;
+            }
+            
+            let mut base_start = pos;
+            while base_start > start && script_class(chars[base_start - 1]) == class {
+                base_start -= 1;
+            }
+            
+            if base_start > start {
+                let text: String = chars[start..base_start].iter().collect();
+                result.push(TextSegment::new_normal(text, byte_at(start)));
+            }
+            
+            let base: String = chars[base_start..pos].iter().collect();
+            let reading: String = chars[pos + 1..reading_end].iter().collect();
+            result.push(TextSegment::new_furigana(base, reading, byte_at(base_start)));
+            
+            pos = reading_end;
+            start = pos;
+        }
+        
+        if start < chars.len() {
+            let text: String = chars[start..].iter().collect();
+            result.push(TextSegment::new_normal(text, byte_at(start)));
+        }
+    }
+    
+    result
+}
+
+/// Split inline IPA overrides out of normal-text segments
+/// 
+/// `東京/toːkʲoː/` (with delimiter '/') becomes a PhonemeOverride segment for
+/// 東京 whose IPA is written verbatim. The override applies to the run of
+/// same-script characters right before the opening delimiter (kanji run,
+/// hiragana run, katakana run or latin/digit run), so 私は東京/toːkʲoː/ only
+/// overrides 東京. A delimiter with no token before it or no closing
+/// delimiter after it is left as text.
+fn split_ipa_overrides(segments: Vec<TextSegment>, delimiter: char) -> Vec<TextSegment> {
+    let mut result = Vec::with_capacity(segments.len());
+    
+    for segment in segments {
+        if !matches!(segment.segment_type, SegmentType::NormalText) || !segment.text.contains(delimiter) {
+            result.push(segment);
+            continue;
+        }
+        
+        let chars: Vec<char> = segment.text.chars().collect();
+        let byte_at = |index: usize| -> usize {
+            segment.original_pos + chars[..index].iter().map(|c| c.len_utf8()).sum::<usize>()
+        };
+        
+        let mut start = 0;  // Start of text not yet emitted
+        let mut pos = 0;
+        
+        while pos < chars.len() {
+            if chars[pos] != delimiter {
+                pos += 1;
+                continue;
+            }
+            
+            let close = chars[pos + 1..].iter().position(|&ch| ch == delimiter).map(|p| pos + 1 + p);
+            let class = if pos > start { script_class(chars[pos - 1]) } else { 0 };
+            
+            let close = match close {
+                Some(close) if class != 0 && close > pos + 1 => close,
+                _ => {
+                    pos += 1;
+                    continue;
+                }
+            };
+            
+            let mut token_start = pos;
+            while token_start > start && script_class(chars[token_start - 1]) == class {
+                token_start -= 1;
+            }
+            
+            if token_start > start {
+                let text: String = chars[start..token_start].iter().collect();
+                result.push(TextSegment::new_normal(text, byte_at(start)));
+            }
+            
+            let token: String = chars[token_start..pos].iter().collect();
+            let phonemes: String = chars[pos + 1..close].iter().collect();
+            result.push(TextSegment::new_override(token, phonemes.trim().to_string(), byte_at(token_start)));
+            
+            pos = close + 1;
+            start = pos;
+        }
+        
+        if start < chars.len() {
+            let text: String = chars[start..].iter().collect();
+            result.push(TextSegment::new_normal(text, byte_at(start)));
+        }
+    }
+    
+    result
+}
+
+/// The word tokens segmented conversion converts, in order
+/// Runs the input passes, furigana/IPA-override parsing and segmentation
+/// with the converter's phoneme trie as fallback, then glues ー/っ tokens
+/// and hands the words to the segmenter's boundary adjuster, if any.
+fn segment_prepared(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter) -> Vec<Token> {
+    // 🔥 STEP 0: Input passes (number expansion, ...) run once on the whole text
+    let text = converter.prepare_input(text);
+    
+    // 🔥 STEP 1: Parse furigana hints (and inline IPA overrides) into structured segments
+    let mut segments = parse_furigana_segments(&text, Some(segmenter));
+    if let Some(delimiter) = converter.options.reading_delimiter {
+        segments = split_pipe_readings(segments, delimiter);
+    }
+    if let Some(delimiter) = converter.options.ipa_override_delimiter {
+        segments = split_ipa_overrides(segments, delimiter);
+    }
+    
+    // 🔥 STEP 2: Segment into words using structured segments with phoneme fallback
+    let mut words = segmenter.segment_from_segments(&segments, Some(converter.get_root()));
+    words = glue_small_kana_tokens(words);
+    words = glue_elongation_tokens(words);
+    words = glue_sokuon_tokens(words);
+    
+    // 🔥 STEP 2b: Caller's boundary adjustments (WordSegmenter::boundary_adjuster)
+    segmenter.adjust_token_boundaries(words)
+}
+
+/// Segmentation of `text` with word boundaries marked, for eyeballing it
+/// before trusting the phonemes: 私|は|リンゴ|が|すき|です. Same tokens as
+/// segmented conversion; a furigana hint shows as base「reading」.
+fn show_segments(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter, delimiter: &str) -> String {
+    segment_prepared(converter, text, segmenter).into_iter()
+        .map(|token| match token.source {
+            Some(source) => format!("{}「{}」", source, token.text),
+            None => token.text,
+        })
+        .collect::<Vec<_>>()
+        .join(delimiter)
+}
+
+/// Convert with word segmentation, keeping each source word next to its phonemes
+/// Returns (word, phonemes) pairs in order, including particles and grammar tokens.
+/// For furigana hints the word is the reading that was converted.
+/// 
+/// Example: 私はリンゴ → [(私, ɰᵝatai), (は, wa), (リンゴ, ɾiɴgo)]
+pub fn convert_aligned(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter) -> Vec<(String, String)> {
+    // 🔥 STEPS 0-2: Input passes, furigana parsing and word segmentation
+    let words = segment_prepared(converter, text, segmenter);
+    
+    // 🔥 STEP 3: Convert each word to phonemes with particle handling
+    let mut pairs = Vec::with_capacity(words.len());
+    for token in words {
+        let word = token.text;
+        
+        // Standalone particles read as particles (は → wa, へ → e)
+        let phonemes = if let Some(phonemes) = token.phonemes {
+            phonemes  // Inline IPA override, verbatim
+        } else if let Some(reading) = converter.particle_reading(&word) {
+            reading.to_string()
+        } else {
+            converter.finish_phonemes(&word, converter.convert_word(&word))
+        };
+        pairs.push((word, phonemes));
+        
+        // Input whitespace as an explicit pause token
+        if let (true, Some(space_token)) = (token.space_after, converter.options.explicit_space_token.as_ref()) {
+            pairs.push((" ".to_string(), space_token.clone()));
+        }
+    }
+    
+    pairs
+}
+
+/// Convert with word segmentation into one "word<TAB>phonemes" line per word
+/// 
+/// Made for diffing two conversions (before/after a dictionary edit): with a
+/// line per word, `diff old.txt new.txt` shows exactly the words that changed
+/// instead of whole sentences. Same pairs as convert_aligned().
+pub fn convert_aligned_lines(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter) -> Vec<String> {
+    convert_aligned(converter, text, segmenter).into_iter()
+        .map(|(word, phonemes)| format!("{}\t{}", word, phonemes))
+        .collect()
+}
+
+/// Output layout for segmented conversion (convert_formatted)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,  // Space-separated phonemes, same as convert_with_segmentation()
+    Ssml,  // <speak> document with a <phoneme alphabet="ipa"> tag per word
+    Mfa,   // Montreal Forced Aligner phones: "ɰᵝ a t a i | w a | sp | ..." (see format_mfa)
+}
+
+/// Convert with word segmentation into (word, phonemes, romaji) rows
+/// Same words and phonemes as convert_aligned(); the romaji column is read
+/// off the phonemes (see simplified_to_romaji()), so it shows exactly what
+/// the IPA says.
+fn convert_bilingual(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter) -> Vec<(String, String, String)> {
+    let simplified = converter.options.phoneme_inventory == PhonemeInventory::Simplified;
+    
+    convert_aligned(converter, text, segmenter).into_iter()
+        .map(|(word, phonemes)| {
+            let romaji = if simplified {
+                simplified_to_romaji(&phonemes)
+            } else {
+                simplified_to_romaji(&simplify_phonemes(&phonemes))
+            };
+            (word, phonemes, romaji)
+        })
+        .collect()
+}
+
+/// Escape text for XML content and attribute values (& < > " ')
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    
+    escaped
+}
+
+/// Short pause between words in Montreal Forced Aligner transcripts
+const MFA_PAUSE: &str = "sp";
+
+/// Spoken noise: MFA's label for a word it has no pronunciation for
+const MFA_UNKNOWN: &str = "spn";
+
+/// Lay out (word, phonemes) pairs for Montreal Forced Aligner (OutputFormat::Mfa)
+/// 
+/// - Phones of a word are separated by one space; modifiers, length marks
+///   and diacritics stay on their phone (ɰᵝ, oː, t͡ɕ) - see phoneme_units()
+/// - Words are separated by " | "
+/// - Punctuation and input whitespace become the pause word `sp`; runs of
+///   pauses collapse into one and pauses at either end are dropped (MFA
+///   adds its own leading/trailing silence)
+/// - Text the dictionary couldn't read (iPhone, unknown kanji) becomes `spn`
+/// 
+/// 私は、リンゴ → `ɰᵝ a t a i | w a | sp | ɾ i ɴ g o`
+fn format_mfa(pairs: Vec<(String, String)>) -> String {
+    let mut words: Vec<String> = Vec::new();
+    let push_word = |words: &mut Vec<String>, word: String| {
+        let repeated_pause = word == MFA_PAUSE && words.last().is_none_or(|last| last == MFA_PAUSE);
+        if !repeated_pause {
+            words.push(word);
+        }
+    };
+    
+    for (word, phonemes) in pairs {
+        let lowercase_word = word.to_lowercase();
+        if word == " " {
+            push_word(&mut words, MFA_PAUSE.to_string());  // explicit_space_token pair
+            continue;
+        }
+        if phonemes == word && !word.chars().all(is_punctuation) {
+            push_word(&mut words, MFA_UNKNOWN.to_string());
+            continue;
+        }
+        
+        // Punctuation passed through inside a token (、リンゴ。) splits it
+        let mut chunk = String::new();
+        for ch in phonemes.chars().chain(std::iter::once(' ')) {
+            if !is_punctuation(ch) && !ch.is_whitespace() {
+                chunk.push(ch);
+                continue;
+            }
+            if !chunk.is_empty() {
+                // Passed-through text: still kana/kanji, or copied from the word (iPhone)
+                let readable = !chunk.chars().any(|c| is_kanji(c) || is_kana(c)) &&
+                    !lowercase_word.contains(&chunk.to_lowercase());
+                push_word(&mut words, if readable { phoneme_units(&chunk).join(" ") } else { MFA_UNKNOWN.to_string() });
+                chunk.clear();
+            }
+            if is_punctuation(ch) {
+                push_word(&mut words, MFA_PAUSE.to_string());
+            }
+        }
+    }
+    
+    if words.last().is_some_and(|last| last == MFA_PAUSE) {
+        words.pop();
+    }
+    words.join(" | ")
+}
+
+/// Convert with word segmentation and lay the result out as `format`
+/// 
+/// Ssml wraps each converted word for TTS engines with per-word phoneme control:
+/// `<speak><phoneme alphabet="ipa" ph="ɰᵝatai">私</phoneme>...</speak>`
+/// Silent words (、) and words the dictionary left unchanged are written as
+/// plain text. Both the text and the ph attribute are XML-escaped.
+/// Mfa writes space-separated phones for forced alignment - see format_mfa().
+pub fn convert_formatted(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Text => convert_with_segmentation(converter, text, segmenter),
+        OutputFormat::Ssml => {
+            let mut ssml = String::from("<speak>");
+            
+            for (word, phonemes) in convert_aligned(converter, text, segmenter) {
+                if phonemes.is_empty() || phonemes == word {
+                    ssml.push_str(&escape_xml(&word));
+                } else {
+                    ssml.push_str(&format!("<phoneme alphabet=\"ipa\" ph=\"{}\">{}</phoneme>",
+                                           escape_xml(&phonemes), escape_xml(&word)));
+                }
+            }
+            
+            ssml.push_str("</speak>");
+            ssml
+        }
+        OutputFormat::Mfa => format_mfa(convert_aligned(converter, text, segmenter)),
+    }
+}
+
+/// Every reading of a sentence, at most `max_alternatives` of them
+/// 
+/// Words are segmented as in convert_with_segmentation(), whose output is
+/// always the first variant. Each word's other readings follow its primary
+/// one: a standalone particle's kana reading (は → wa, then ha) and the
+/// readings added with add_reading(), in the order given (most frequent
+/// first). Variants come in order of how far they stray from the first - the
+/// sum of their words' reading ranks - with ties broken by changing earlier
+/// words first, so 3 two-way words give 000, 100, 010, 001, 110, ...
+/// The product of several ambiguous words grows fast, so enumeration stops
+/// at the cap instead of building all of it.
+pub fn convert_all(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter, max_alternatives: usize) -> Vec<String> {
+    let words = segment_prepared(converter, text, segmenter);
+    let word_case = converter.options.word_case;
+    
+    // Readings of each word, primary first, and the pause token after it
+    let mut slots: Vec<(Vec<String>, Option<&String>)> = Vec::with_capacity(words.len());
+    for token in &words {
+        let word = &token.text;
+        let mut readings: Vec<String> = Vec::new();
+        
+        if let Some(ref phonemes) = token.phonemes {
+            readings.push(word_case.apply(phonemes.clone()));
+        } else {
+            let finish = |phonemes: String| {
+                if phonemes.is_empty() { phonemes } else { word_case.apply(converter.finish_phonemes(word, phonemes)) }
+            };
+            
+            // Same primary reading as convert_tokens_detailed()
+            let mut candidates = Vec::new();
+            if let Some(reading) = converter.particle_reading(word) {
+                candidates.push(word_case.apply(reading.to_string()));
+            }
+            candidates.push(finish(converter.convert_word_detailed(word).phonemes));
+            if token.source.is_none() {
+                candidates.extend(converter.extra_readings.get(word).into_iter().flatten().map(|reading| finish(reading.clone())));
+            }
+            
+            for candidate in candidates {
+                if !readings.contains(&candidate) {
+                    readings.push(candidate);
+                }
+            }
+        }
+        
+        let pause = converter.options.explicit_space_token.as_ref().filter(|_| token.space_after);
+        slots.push((readings, pause));
+    }
+    
+    // Choice vectors with rank sum 0, 1, 2, ... each in descending lexicographic order
+    fn choose(slots: &[(Vec<String>, Option<&String>)], word: usize, rank_left: usize,
+              choice: &mut Vec<usize>, out: &mut Vec<Vec<usize>>, cap: usize) {
+        if out.len() >= cap {
+            return;
+        }
+        if word == slots.len() {
+            if rank_left == 0 {
+                out.push(choice.clone());
+            }
+            return;
+        }
+        
+        for index in (0..slots[word].0.len().min(rank_left + 1)).rev() {
+            choice.push(index);
+            choose(slots, word + 1, rank_left - index, choice, out, cap);
+            choice.pop();
+        }
+    }
+    
+    let max_rank: usize = slots.iter().map(|(readings, _)| readings.len().saturating_sub(1)).sum();
+    let mut choices = Vec::new();
+    for rank in 0..=max_rank {
+        choose(&slots, 0, rank, &mut Vec::new(), &mut choices, max_alternatives);
+        if choices.len() >= max_alternatives {
+            break;
+        }
+    }
+    
+    choices.into_iter()
+        .map(|choice| {
+            let mut parts: Vec<&str> = Vec::new();
+            for ((readings, pause), index) in slots.iter().zip(choice) {
+                if !readings[index].is_empty() {
+                    parts.push(&readings[index]);
+                }
+                if let Some(pause) = pause {
+                    parts.push(pause);
+                }
+            }
+            parts.join(" ")
+        })
+        .collect()
+}
+
+/// Convert with word segmentation and detailed information
+/// OPTIMIZED: Uses furigana-aware segmentation and particle handling (は → wa)
+pub fn convert_detailed_with_segmentation(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter) -> ConversionResult {
+    // Bracket diagnostics use positions in the caller's text
+    let warnings = check_furigana_brackets(text);
+    
+    // 🔥 STEPS 0-2: Input passes, furigana parsing and word segmentation
+    let words = segment_prepared(converter, text, segmenter);
+    
+    // 🔥 STEP 3: Convert each word to phonemes with particle handling
+    convert_tokens_detailed(converter, &words, warnings)
+}
+
+/// Convert word tokens one by one with particle handling (は → wa), collecting
+/// matches and coverage - step 3 of convert_detailed_with_segmentation(),
+/// shared with convert_presegmented() for externally segmented words
+fn convert_tokens_detailed(converter: &PhonemeConverter, words: &[Token], warnings: Vec<BracketWarning>) -> ConversionResult {
+    let mut all_matches = Vec::new();
+    let mut all_unmatched = Vec::new();
+    let mut all_unmatched_runs = Vec::new();
+    let mut word_scores = Vec::new();
+    let mut phoneme_parts = Vec::new();
+    let mut byte_offset = 0;
+    
+    for token in words {
+        let word = &token.text;
+        
+        if let Some(ref phonemes) = token.phonemes {
+            // Inline IPA override, verbatim
+            phoneme_parts.push(converter.options.word_case.apply(phonemes.clone()));
+            all_matches.push(Match {
+                original: word.clone(),
+                phoneme: phonemes.clone(),
+                start_index: byte_offset,
+            });
+            word_scores.push((word.clone(), 1.0));
+        } else if let Some(reading) = converter.particle_reading(word) {
+            // Standalone particles read as particles (は → wa, へ → e)
+            phoneme_parts.push(converter.options.word_case.apply(reading.to_string()));
+            // Add to matches for consistency
+            all_matches.push(Match {
+                original: word.clone(),
+                phoneme: reading.to_string(),
+                start_index: byte_offset,
+            });
+            word_scores.push((word.clone(), 1.0));
+        } else {
+            let mut word_result = converter.convert_word_detailed(word);
+            word_scores.push((token.source.clone().unwrap_or_else(|| word.clone()), word_result.coverage()));
+            
+            if let Some(ref base) = token.source {
+                // Furigana: one match from the base kanji to the reading's phonemes
+                all_matches.push(Match {
+                    original: base.clone(),
+                    phoneme: word_result.phonemes.clone(),
+                    start_index: byte_offset,
+                });
+            } else {
+                // Adjust match positions to account for original text position
+                for match_item in &mut word_result.matches {
+                    match_item.start_index += byte_offset;
+                    all_matches.push(match_item.clone());
+                }
+            }
+            
+            // Silent entries ("" phoneme) produce no output word
+            if !word_result.phonemes.is_empty() {
+                phoneme_parts.push(converter.options.word_case.apply(converter.finish_phonemes(word, word_result.phonemes)));
+            }
+            all_unmatched.extend(word_result.unmatched);
+            for (start, run) in &word_result.unmatched_runs {
+                push_unmatched_run(&mut all_unmatched_runs, start + byte_offset, run);
+            }
+        }
+        
+        // Input whitespace as an explicit pause token
+        if let (true, Some(space_token)) = (token.space_after, converter.options.explicit_space_token.as_ref()) {
+            phoneme_parts.push(space_token.clone());
+        }
+        
+        byte_offset += word.len();
+    }
+    
+    ConversionResult {
+        phonemes: phoneme_parts.join(" "),
+        matches: all_matches,
+        unmatched: all_unmatched,
+        unmatched_runs: all_unmatched_runs,
+        word_scores,
+        warnings,
+    }
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// LONG VOWELS
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+/// Words whose doubled vowels belong to separate morphemes (湖 = みず + うみ,
+/// 吸う = す + う), so merge_long_vowels leaves them as two vowels.
+/// Matched against the whole segmented word; verbs are listed in kanji only
+/// since their kana spellings collide with real long vowels (すう = 数).
+/// Extend with ConversionOptions::long_vowel_exceptions.
+const LONG_VOWEL_EXCEPTIONS: [&str; 9] = [
+    "湖", "みずうみ", "鳥居", "とりい", "里親", "さとおや",
+    "吸う", "縫う", "食う",
+];
+
+/// Vowels of the dictionary's phoneme alphabet
+fn is_phoneme_vowel(ch: char) -> bool {
+    matches!(ch, 'a' | 'i' | 'ɯ' | 'u' | 'e' | 'o')
+}
+
+/// True if a ー after `phonemes` lengthens its last sound: a vowel, an
+/// earlier length mark (ーー → ːː), or with `hold_nasal` the moraic nasal
+/// (ɴː - only under the long-vowel options, like merge_long_vowels())
+fn ends_with_lengthenable(phonemes: &str, hold_nasal: bool) -> bool {
+    phonemes.chars().last().is_some_and(|last| is_phoneme_vowel(last) || last == 'ː' || (hold_nasal && last == 'ɴ'))
+}
+
+/// Merge long vowels in one word's phonemes
+/// 
+/// - ー after a vowel becomes ː (ラー → ɾaː); the dictionary already spells
+///   katakana words this way, so this only catches words it doesn't know.
+///   Every ー of a run adds one ː (goーー → goːː)
+/// - ー after the moraic nasal holds it (ふんー: ɸɯɴー → ɸɯɴː)
+/// - A doubled vowel becomes vowel + ː (ookii → oːkiː), pairing from the left
+///   so a tripled vowel keeps its third mora (aaa → aːa)
+/// 
+/// Different-vowel sequences (ei, ou) are left alone.
+fn merge_long_vowels(phonemes: &str) -> String {
+    let mut result = String::with_capacity(phonemes.len());
+    let mut previous: Option<char> = None;  // Last output char, if a vowel, ɴ or ː
+    
+    for ch in phonemes.chars() {
+        match previous {
+            Some(_) if ch == 'ー' => {
+                result.push('ː');
+                previous = Some('ː');
+            }
+            Some(vowel) if ch == vowel && is_phoneme_vowel(vowel) => {
+                result.push('ː');
+                previous = None;
+            }
+            _ => {
+                result.push(ch);
+                previous = if is_phoneme_vowel(ch) || ch == 'ɴ' || ch == 'ː' { Some(ch) } else { None };
+            }
+        }
+    }
+    
+    result
+}
+
+/// Vowel with a macron (LongVowelStyle::Macron); ɯ has no precomposed form
+fn macron_vowel(vowel: char) -> String {
+    match vowel {
+        'a' => "ā".to_string(),
+        'i' => "ī".to_string(),
+        'u' => "ū".to_string(),
+        'e' => "ē".to_string(),
+        'o' => "ō".to_string(),
+        other => format!("{}\u{304}", other),
+    }
+}
+
+/// Rewrite the length marks ː of converted phonemes in the given styles
+/// 
+/// ː after a vowel (or ɴ) is a long vowel: oː → oo / ō. ː after a consonant
+/// and its modifier letters is a geminate: kː → kk, pːj → ppj, ɕː → ɕɕ,
+/// with affricates doubling their stop: ʨː → tʨ, t͡ɕː → tt͡ɕ. Each ː of a
+/// run is rewritten on its own (goːː → gooo; with a macron the first one
+/// becomes ō and the rest stay ː).
+fn apply_length_styles(phonemes: &str, geminate: GeminateStyle, long_vowel: LongVowelStyle) -> String {
+    let mut result = String::with_capacity(phonemes.len() + 8);
+    let mut vowel: Option<char> = None;  // Vowel or ɴ the last output belongs to
+    let mut just_vowel = false;          // Last output char is that vowel itself
+    
+    for ch in phonemes.chars() {
+        if ch != 'ː' {
+            result.push(ch);
+            vowel = if is_phoneme_vowel(ch) || ch == 'ɴ' { Some(ch) } else { None };
+            just_vowel = vowel.is_some();
+            continue;
+        }
+        
+        if let Some(v) = vowel {
+            match long_vowel {
+                LongVowelStyle::Doubled => result.push(v),
+                LongVowelStyle::Macron if just_vowel && v != 'ɴ' => {
+                    result.pop();
+                    result.push_str(&macron_vowel(v));
+                }
+                _ => result.push('ː'),
+            }
+            just_vowel = false;
+            continue;
+        }
+        
+        match geminate_start(&result) {
+            Some((start, doubled)) if geminate == GeminateStyle::Doubled => result.insert(start, doubled),
+            _ => result.push('ː'),
+        }
+    }
+    
+    result
+}
+
+/// Where a geminate's consonant starts at the end of `output`, and the letter
+/// that doubles it (its stop for an affricate); None if no consonant ends it
+fn geminate_start(output: &str) -> Option<(usize, char)> {
+    let mut chars = output.char_indices().rev()
+        .skip_while(|&(_, ch)| matches!(ch, 'ʲ' | 'ᵝ' | 'ʷ' | 'ʰ'));
+    let (base_start, base) = chars.next()?;
+    if is_phoneme_vowel(base) || !base.is_alphabetic() {
+        return None;
+    }
+    
+    // Tied affricate: t͡ɕ doubles its t
+    if let (Some((_, '\u{361}')), Some((stop_start, stop))) = (chars.next(), chars.next()) {
+        return Some((stop_start, stop));
+    }
+    
+    let stop = match base {
+        'ʨ' | 'ʦ' => 't',
+        'ʥ' | 'ʣ' => 'd',
+        other => other,
+    };
+    Some((base_start, stop))
+}
+
+/// Kana grouped by vowel, for finding the vowel of the preceding mora
+const KANA_BY_VOWEL: [(char, &str); 5] = [
+    ('a', "あかさたなはまやらわがざだばぱぁゃゎアカサタナハマヤラワガザダバパァャヮヷ"),
+    ('i', "いきしちにひみりぎじぢびぴぃイキシチニヒミリギジヂビピィヸ"),
+    ('u', "うくすつぬふむゆるぐずづぶぷぅゅゔウクスツヌフムユルグズヅブプゥュヴ"),
+    ('e', "えけせてねへめれげぜでべぺぇエケセテネヘメレゲゼデベペェヹ"),
+    ('o', "おこそとのほもよろをごぞどぼぽぉょオコソトノホモヨロヲゴゾドボポォョヺ"),
+];
+
+/// Vowel of a kana mora (a/i/u/e/o), None for ん, っ, ー and non-kana
+fn kana_vowel(ch: char) -> Option<char> {
+    KANA_BY_VOWEL.iter()
+        .find(|&&(_, kana)| kana.contains(ch))
+        .map(|&(vowel, _)| vowel)
+}
+
+/// Bare vowel kana (あ, ぁ, ア, ...) - the ones repeated to stretch a sound
+fn is_vowel_kana(ch: char) -> bool {
+    "あいうえおぁぃぅぇぉアイウエオァィゥェォ".contains(ch)
+}
+
+/// Voiced form of a kana followed by a stray dakuten (か゛ → が)
+/// None when the kana has no voiced form (あ゛), so the mark is dropped.
+fn voiced_kana(ch: char) -> Option<char> {
+    match ch {
+        'う' => Some('ゔ'),
+        'ウ' => Some('ヴ'),
+        // Voiced forms directly follow their base in both kana blocks
+        _ if "かきくけこさしすせそたちつてとはひふへほカキクケコサシスセソタチツテトハヒフヘホ".contains(ch) => {
+            std::char::from_u32(ch as u32 + 1)
+        }
+        _ => None,
+    }
+}
+
+/// Normalize casual elongation before segmentation (elongation option)
+/// 
+/// - Dakuten marks (゛ or combining ゙) join a kana that has a voiced form and
+///   are dropped otherwise (あ゛ー → あー)
+/// - Two or more repeated vowel kana stretching the previous mora become ー
+///   (ながあああい → ながーーーい); a single one is left alone (おかあさん)
+/// - ー runs are kept (Proportional) or reduced to one ー (Collapse)
+/// 
+/// The remaining ー are turned into ː by merge_long_vowels().
+fn normalize_elongation(text: &str, mode: ElongationMode) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    let mut last_vowel: Option<char> = None;  // Vowel of the last kana mora written
+    let mut pos = 0;
+    
+    while pos < chars.len() {
+        let ch = chars[pos];
+        
+        if ch == '゛' || ch == '\u{3099}' {
+            if let Some(base) = result.pop() {
+                result.push(voiced_kana(base).unwrap_or(base));
+            }
+            pos += 1;
+            continue;
+        }
+        
+        // Length of a stretching run: ー marks, or repeats of the last vowel
+        let mut run = 0;
+        if ch == 'ー' {
+            while pos + run < chars.len() && chars[pos + run] == 'ー' {
+                run += 1;
+            }
+        } else if is_vowel_kana(ch) && kana_vowel(ch) == last_vowel {
+            while pos + run < chars.len() && chars[pos + run] == ch {
+                run += 1;
+            }
+            if run < 2 {
+                run = 0;
+            }
+        }
+        
+        if run > 0 {
+            // Runs continuing a previous run (ーああ) collapse into it
+            let marks = match mode {
+                ElongationMode::Collapse if result.ends_with('ー') => 0,
+                ElongationMode::Collapse => 1,
+                ElongationMode::Proportional => run,
+            };
+            for _ in 0..marks {
+                result.push('ー');
+            }
+            pos += run;
+            continue;
+        }
+        
+        last_vowel = kana_vowel(ch);
+        result.push(ch);
+        pos += 1;
+    }
+    
+    result
+}
+
+/// Double the onset consonant of a phoneme string (pa → pːa, pja → pːja)
+/// Modifier letters (ʲ, ᵝ) stay with the consonant. None when the phonemes
+/// start with a vowel, ɴ or ʔ - there's nothing to geminate.
+fn geminate_onset(phonemes: &str) -> Option<String> {
+    let onset = phonemes.chars().next()?;
+    if is_phoneme_vowel(onset) || matches!(onset, 'ɴ' | 'ʔ' | 'ː') || !onset.is_alphabetic() {
+        return None;
+    }
+    
+    let split = phonemes.char_indices()
+        .skip(1)
+        .find(|&(_, ch)| !matches!(ch, 'ʲ' | 'ᵝ' | 'ʷ' | 'ʰ'))
+        .map_or(phonemes.len(), |(i, _)| i);
+    
+    Some(format!("{}ː{}", &phonemes[..split], &phonemes[split..]))
+}
+
+/// True for katakana normalize_kana() folds: ァ (U+30A1) through ヶ (U+30F6)
+fn is_foldable_katakana(ch: char) -> bool {
+    ('\u{30A1}'..='\u{30F6}').contains(&ch)
+}
+
+/// Fold katakana to the matching hiragana (fold_katakana option)
+/// 
+/// ァ-ヶ shift down to ぁ-ゖ, so small kana stay small (ャ → ゃ, ッ → っ) and
+/// ヴ becomes ゔ. ー is left as it is, since hiragana has no mark of its own
+/// and the walk lengthens the vowel before it either way. ヷ-ヺ and the
+/// iteration marks ヽヾ have no single hiragana and pass through.
+fn normalize_kana(text: &str) -> String {
+    text.chars()
+        .map(|c| if is_foldable_katakana(c) { char::from_u32(c as u32 - 0x60).unwrap_or(c) } else { c })
+        .collect()
+}
+
+/// Drop ー and repeated ッ right after a katakana ッ (アッー → アッ, ッッア → ッア)
+/// 
+/// Loanwords and shouts sometimes put ッ before a mark with no consonant to
+/// double. The lone ッ left behind reads as a glottal stop ʔ, like あっ, and
+/// the marks no longer end up as unmatched characters in the output.
+fn collapse_katakana_sokuon(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut after_sokuon = false;
+    
+    for ch in text.chars() {
+        if after_sokuon && matches!(ch, 'ー' | 'ッ') {
+            continue;
+        }
+        
+        after_sokuon = ch == 'ッ';
+        result.push(ch);
+    }
+    
+    result
+}
+
+/// Attach sokuon-only tokens (っ, ッ) to the following token
+/// The segmenter has no words starting with っ, so ざっし arrives as
+/// [ざ, っ, し]; joined as っし the sokuon can double the し onset.
+fn glue_sokuon_tokens(tokens: Vec<Token>) -> Vec<Token> {
+    let mut glued: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut pending = String::new();  // Sokuon waiting for the next token
+    
+    for mut token in tokens {
+        if token.phonemes.is_none() && token.text.chars().all(|c| matches!(c, 'っ' | 'ッ')) {
+            pending.push_str(&token.text);
+            continue;
+        }
+        
+        if !pending.is_empty() && token.phonemes.is_none() {
+            token.text.insert_str(0, &pending);
+            token.kind = TokenKind::Word;
+            pending.clear();
+        }
+        
+        if !pending.is_empty() {
+            // An override can't absorb it - keep the sokuon as its own token
+            glued.push(Token::unmatched(pending.clone()));
+            pending.clear();
+        }
+        glued.push(token);
+    }
+    
+    if !pending.is_empty() {
+        glued.push(Token::unmatched(pending));
+    }
+    
+    glued
+}
+
+/// Attach tokens starting with a small kana (ゃ, ゅ, ょ, ぁ, ...) to the previous token
+/// No word starts with one, so an unknown しゅう arrives as [し, ゅう]; glued
+/// back together the youon reads as one mora (see youon_match()).
+fn glue_small_kana_tokens(tokens: Vec<Token>) -> Vec<Token> {
+    let mut glued: Vec<Token> = Vec::with_capacity(tokens.len());
+    
+    for token in tokens {
+        let small = token.phonemes.is_none() && token.text.starts_with(|c| "ゃゅょぁぃぅぇぉャュョァィゥェォ".contains(c));
+        match glued.last_mut() {
+            Some(previous) if small && previous.phonemes.is_none() && !previous.space_after => {
+                previous.text.push_str(&token.text);
+                previous.space_after = token.space_after;
+            }
+            _ => glued.push(token),
+        }
+    }
+    
+    glued
+}
+
+/// Attach tokens starting with ー to the previous token
+/// The segmenter has no words starting with ー, so ながーい arrives as
+/// [なが, ーい]; glued back together the ー can lengthen the preceding vowel.
+/// A token with an IPA override keeps its phonemes and is left alone.
+fn glue_elongation_tokens(tokens: Vec<Token>) -> Vec<Token> {
+    let mut glued: Vec<Token> = Vec::with_capacity(tokens.len());
+    
+    for token in tokens {
+        match glued.last_mut() {
+            Some(previous) if token.text.starts_with('ー') && previous.phonemes.is_none() => {
+                previous.text.push_str(&token.text);
+                previous.space_after = token.space_after;
+            }
+            _ => glued.push(token),
+        }
+    }
+    
+    glued
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// COLLOQUIAL CONTRACTIONS
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+/// Written form → spoken contraction (colloquial_contractions option)
+/// 
+/// - te-iru:   ている → てる (and ていた/ていて/ていない/ています, voiced で-)
+/// - te-oku:   ておく → とく, ておいた → といた (voiced でおく → どく)
+/// - te-shimau: てしまう → ちゃう, てしまった → ちゃった (voiced → じゃう)
+/// - to-iu:    という → っていう
+/// 
+/// Longer forms are listed first; the first match at a position wins.
+/// Only kana spellings are rewritten - 居る / 置く / 仕舞う in kanji are kept.
+const CONTRACTIONS: [(&str, &str); 23] = [
+    ("てしまった", "ちゃった"), ("でしまった", "じゃった"),
+    ("てしまう", "ちゃう"), ("でしまう", "じゃう"),
+    ("ていない", "てない"), ("でいない", "でない"),
+    ("ています", "てます"), ("でいます", "でます"),
+    ("ておいた", "といた"), ("でおいた", "どいた"),
+    ("ておいて", "といて"), ("でおいて", "どいて"),
+    ("ている", "てる"), ("でいる", "でる"),
+    ("ていた", "てた"), ("でいた", "でた"),
+    ("ていて", "てて"), ("でいて", "でて"),
+    ("ておく", "とく"), ("でおく", "どく"),
+    ("ておけ", "とけ"), ("でおけ", "どけ"),
+    ("という", "っていう"),
+];
+
+/// Replace written forms with their spoken contractions (see CONTRACTIONS)
+fn contract_colloquial(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    
+    while let Some(ch) = rest.chars().next() {
+        match CONTRACTIONS.iter().find(|&&(written, _)| rest.starts_with(written)) {
+            Some(&(written, spoken)) => {
+                result.push_str(spoken);
+                rest = &rest[written.len()..];
+            }
+            None => {
+                result.push(ch);
+                rest = &rest[ch.len_utf8()..];
+            }
+        }
+    }
+    
+    result
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// LOANWORD FINAL VOWELS
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+/// Morae in a katakana run: every char except small glide kana (ャ, ィ, ...)
+/// ッ and ー are morae of their own.
+fn katakana_morae(run: &[char]) -> usize {
+    run.iter().filter(|&&c| !matches!(c, 'ァ' | 'ィ' | 'ゥ' | 'ェ' | 'ォ' | 'ャ' | 'ュ' | 'ョ' | 'ヮ')).count()
+}
+
+/// Apply a LoanwordVowelPolicy to the end of every katakana run
+/// 
+/// - Short drops the trailing ー (all of them) when 3+ morae remain
+///   (コンピューター → コンピュータ, サーバー → サーバ, but カー and キー stay)
+/// - Long appends ー to a run ending in a vowel when `is_key(run + "ー")`
+///   (コンピュータ → コンピューター); without a dictionary entry the run is
+///   left alone, since most katakana words (カメラ) have no long form
+fn normalize_loanword_vowels<F: Fn(&str) -> bool>(text: &str, policy: LoanwordVowelPolicy, is_key: F) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len() + 3);
+    let mut pos = 0;
+    
+    while pos < chars.len() {
+        if script_class(chars[pos]) != 3 {
+            result.push(chars[pos]);
+            pos += 1;
+            continue;
+        }
+        
+        let start = pos;
+        while pos < chars.len() && script_class(chars[pos]) == 3 {
+            pos += 1;
+        }
+        let run = &chars[start..pos];
+        
+        let mut stem = run.len();
+        while stem > 0 && run[stem - 1] == 'ー' {
+            stem -= 1;
+        }
+        
+        match policy {
+            LoanwordVowelPolicy::Short if stem < run.len() && katakana_morae(&run[..stem]) >= 3 => {
+                result.extend(&run[..stem]);
+            }
+            LoanwordVowelPolicy::Long if stem == run.len() && !matches!(run[stem - 1], 'ン' | 'ッ') => {
+                let word: String = run.iter().collect();
+                result.push_str(&word);
+                if is_key(&format!("{}ー", word)) {
+                    result.push('ー');
+                }
+            }
+            _ => result.extend(run),
+        }
+    }
+    
+    result
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// CONTEXT READINGS
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+/// Reading rule for a kanji whose pronunciation depends on what follows
+struct ContextRule {
+    kanji: char,
+    default_reading: &'static str,  // 何 → nani (何色, 何も)
+    nasal_reading: &'static str,    // 何 → naɴ before t/d/n sounds and counters
+    // Counters read with the nasal form, with their reading after it
+    // (何本 → naɴ + boɴ: the counter's sound change is part of the entry)
+    counters: &'static [(&'static str, &'static str)],
+}
+
+/// Onsets of the following phonemes that select the nasal reading
+const NASAL_TRIGGER_ONSETS: [char; 7] = ['t', 'd', 'n', 'ʦ', 'ʨ', 'ʣ', 'ɲ'];
+
+/// Context-sensitive kanji (context_readings option)
+/// Longer entries come first so 時間 wins over 時.
+const CONTEXT_RULES: [ContextRule; 1] = [
+    ContextRule {
+        kanji: '何',
+        default_reading: "nani",
+        nasal_reading: "naɴ",
+        counters: &[
+            ("時間", "ʥikaɴ"), ("曜日", "joːbi"),
+            ("時", "ʥi"), ("人", "niɴ"), ("本", "boɴ"), ("枚", "mai"),
+            ("個", "ko"), ("回", "kai"), ("年", "neɴ"), ("度", "do"),
+            ("歳", "sai"), ("階", "gai"), ("冊", "saʦɯ"), ("杯", "bai"),
+            ("匹", "biki"), ("分", "pɯɴ"), ("台", "dai"), ("日", "niʨi"),
+            ("月", "gaʦɯ"), ("番", "baɴ"), ("倍", "bai"),
+        ],
+    },
+];
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// MORA TIMING
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+/// Timing class of a mora
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoraKind {
+    Light,      // (Consonant +) vowel: ga, ko, i
+    LongVowel,  // Second half of a long vowel: ː, or ɯ/i after o/e (koɯ, seː)
+    Sokuon,     // First half of a geminate (the k of kːo) or a glottal ʔ
+    Nasal,      // Moraic nasal ɴ
+}
+
+/// One mora of converted phonemes
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoraInfo {
+    pub phonemes: String,
+    pub kind: MoraKind,
+}
+
+/// Split a phoneme string (dictionary IPA) into morae
+/// 
+/// がっこう → gakːoɯ → ga (Light), k (Sokuon), ko (Light), ɯ (LongVowel)
+/// 
+/// - Onset consonants and glides (j, ɰ, ʲ, ...) join the next vowel
+/// - Consonant + ː (or a doubled consonant, tt) is a geminate: the consonant
+///   is a Sokuon mora and also the onset of the following mora
+/// - ː after a vowel, a repeated vowel, and ɯ after o / i after e (おう, えい)
+///   are LongVowel morae
+/// - ɴ is a Nasal mora, ʔ a Sokuon mora
+/// - Whitespace and punctuation separate words and produce no mora; a
+///   consonant left at the end of a word becomes a Light mora of its own
+fn split_morae(phonemes: &str) -> Vec<MoraInfo> {
+    let mut morae: Vec<MoraInfo> = Vec::new();
+    let mut onset = String::new();
+    let mut last_vowel: Option<char> = None;  // Vowel ending the previous mora in this word
+    
+    fn push(morae: &mut Vec<MoraInfo>, phonemes: String, kind: MoraKind) {
+        morae.push(MoraInfo { phonemes, kind });
+    }
+    
+    for ch in phonemes.chars() {
+        if ch.is_whitespace() || is_punctuation(ch) {
+            if !onset.is_empty() {
+                push(&mut morae, onset.clone(), MoraKind::Light);
+                onset.clear();
+            }
+            last_vowel = None;
+            continue;
+        }
+        
+        // Diacritics after a vowel (nasalized e\u{0303}) belong to the mora it ended
+        if onset.is_empty() && matches!(ch, '\u{0300}'..='\u{036F}') {
+            if let Some(last) = morae.last_mut() {
+                last.phonemes.push(ch);
+                continue;
+            }
+        }
+        
+        match ch {
+            'ː' if !onset.is_empty() => {
+                // Geminate: the held consonant is its own mora, then starts the next
+                push(&mut morae, onset.clone(), MoraKind::Sokuon);
+                last_vowel = None;
+            }
+            'ː' => {
+                if last_vowel.is_some() {
+                    push(&mut morae, ch.to_string(), MoraKind::LongVowel);
+                }
+            }
+            'ɴ' if onset.is_empty() => {
+                push(&mut morae, ch.to_string(), MoraKind::Nasal);
+                last_vowel = None;
+            }
+            'ʔ' if onset.is_empty() => {
+                push(&mut morae, ch.to_string(), MoraKind::Sokuon);
+                last_vowel = None;
+            }
+            _ if is_phoneme_vowel(ch) => {
+                let lengthens = onset.is_empty() && match (last_vowel, ch) {
+                    (Some(previous), _) if previous == ch => true,
+                    (Some('o'), 'ɯ') | (Some('o'), 'u') | (Some('e'), 'i') => true,
+                    _ => false,
+                };
+                
+                onset.push(ch);
+                let kind = if lengthens { MoraKind::LongVowel } else { MoraKind::Light };
+                push(&mut morae, onset.clone(), kind);
+                onset.clear();
+                last_vowel = Some(ch);
+            }
+            // Doubled letter (tt) is a geminate written without ː
+            _ if onset.chars().eq(std::iter::once(ch)) => {
+                push(&mut morae, onset.clone(), MoraKind::Sokuon);
+                last_vowel = None;
+            }
+            _ => onset.push(ch),
+        }
+    }
+    
+    if !onset.is_empty() {
+        push(&mut morae, onset, MoraKind::Light);
+    }
+    
+    morae
+}
+
+/// Split phonemes into single sounds: a base letter plus its modifiers
+/// (ʲ ᵝ ʷ ʰ ː, combining diacritics) and anything joined by a tie bar
+/// (kʲ, ɰᵝ, t͡ɕ, aː, ẽ̞)
+fn phoneme_units(phonemes: &str) -> Vec<String> {
+    let mut units: Vec<String> = Vec::new();
+    let mut joined = false;  // Previous char was a tie bar
+    
+    for ch in phonemes.chars() {
+        let attaches = joined || matches!(ch, 'ʲ' | 'ᵝ' | 'ʷ' | 'ʰ' | 'ː' | '\u{0300}'..='\u{036F}');
+        match units.last_mut() {
+            Some(last) if attaches => last.push(ch),
+            _ => units.push(ch.to_string()),
+        }
+        joined = ch == '\u{0361}';
+    }
+    
+    units
+}
+
+/// Phoneme units of each mora, tagged with the mora index (see convert_annotated)
+fn annotate_morae(morae: &[MoraInfo]) -> Vec<(usize, String)> {
+    let mut annotated = Vec::new();
+    let mut held = String::new();  // Geminate consonant already given to a Sokuon mora
+    
+    for (index, mora) in morae.iter().enumerate() {
+        let phonemes = match mora.phonemes.strip_prefix(held.as_str()) {
+            Some(rest) if !held.is_empty() => rest,
+            _ => mora.phonemes.as_str(),
+        };
+        held.clear();
+        
+        let mut units = phoneme_units(phonemes);
+        if mora.kind == MoraKind::Sokuon && phonemes != "ʔ" {
+            held = phonemes.to_string();
+            if let Some(last) = units.last_mut() {
+                last.push('ː');
+            }
+        }
+        
+        annotated.extend(units.into_iter().map(|unit| (index, unit)));
+    }
+    
+    annotated
+}
+
+/// Reverse phonemes mora by mora (reverse_morae option), for palindrome checks
+/// 
+/// Works on whole morae from annotate_morae(), never on bytes or chars, so
+/// modifiers and tie bars stay on their base (kʲa, t͡ɕi) and a geminate kː
+/// moves as one mora. A long-vowel ː is written as the vowel it lengthens,
+/// since a leading ː means nothing (toː → oto, like とお → おと).
+/// Whitespace and punctuation runs keep their content; the order of all runs
+/// is reversed too (ɾiɴgo、mikaɴ → ɴkami、goɴɾi).
+fn reverse_morae(phonemes: &str) -> String {
+    // Split into alternating word / separator runs
+    let mut runs: Vec<(bool, String)> = Vec::new();
+    for ch in phonemes.chars() {
+        let separator = ch.is_whitespace() || is_punctuation(ch);
+        match runs.last_mut() {
+            Some((is_separator, run)) if *is_separator == separator => run.push(ch),
+            _ => runs.push((separator, ch.to_string())),
+        }
+    }
+    
+    let mut reversed = String::with_capacity(phonemes.len());
+    for (separator, run) in runs.iter().rev() {
+        if *separator {
+            reversed.push_str(run);
+            continue;
+        }
+        
+        // Group units back into morae, spelling out long-vowel ː
+        let mut morae: Vec<String> = Vec::new();
+        let mut current_index = None;
+        let mut last_vowel: Option<String> = None;
+        for (index, unit) in annotate_morae(&split_morae(run)) {
+            let unit = match (unit.as_str(), &last_vowel) {
+                ("ː", Some(vowel)) => vowel.clone(),
+                _ => unit,
+            };
+            if let Some(vowel) = unit.chars().filter(|&c| is_phoneme_vowel(c)).last() {
+                last_vowel = Some(vowel.to_string());
+            }
+            
+            if current_index == Some(index) {
+                if let Some(mora) = morae.last_mut() {
+                    mora.push_str(&unit);
+                }
+            } else {
+                morae.push(unit);
+                current_index = Some(index);
+            }
+        }
+        
+        for mora in morae.iter().rev() {
+            reversed.push_str(mora);
+        }
+    }
+    
+    reversed
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// SENTENCE SPLITTING
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+/// Sentence-final punctuation
+pub fn is_sentence_end(ch: char) -> bool {
+    matches!(ch, '。' | '！' | '？' | '!' | '?' | '．')
+}
+
+/// Closing quotes/brackets that stay attached to the sentence they close
+pub fn is_closing_quote(ch: char) -> bool {
+    matches!(ch, '」' | '』' | '）' | ')' | '】' | '"' | '”' | '’')
+}
+
+/// Iterator over sentences of a text (see sentences())
+pub struct Sentences<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Sentences<'a> {
+    type Item = &'a str;
+    
+    fn next(&mut self) -> Option<&'a str> {
+        loop {
+            if self.rest.is_empty() {
+                return None;
+            }
+            
+            let mut depth = 0usize;  // Open 「『（ quotes - no sentence breaks inside
+            let mut end = self.rest.len();
+            let mut skip = 0;  // Bytes after `end` that belong to no sentence (newline)
+            let mut iter = self.rest.char_indices().peekable();
+            
+            while let Some((i, ch)) = iter.next() {
+                match ch {
+                    '\n' | '\r' => {
+                        end = i;
+                        skip = ch.len_utf8();
+                        break;
+                    }
+                    '「' | '『' | '（' | '(' => depth += 1,
+                    '」' | '』' | '）' | ')' => depth = depth.saturating_sub(1),
+                    _ if depth == 0 && is_sentence_end(ch) => {
+                        // Keep runs like ！？ and trailing closing quotes with this sentence
+                        end = i + ch.len_utf8();
+                        while let Some(&(j, next)) = iter.peek() {
+                            if is_sentence_end(next) || is_closing_quote(next) {
+                                end = j + next.len_utf8();
+                                iter.next();
+                            } else {
+                                break;
+                            }
+                        }
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            
+            let sentence = self.rest[..end].trim();
+            self.rest = &self.rest[end + skip..];
+            
+            // Blank lines and whitespace between sentences yield nothing
+            if !sentence.is_empty() {
+                return Some(sentence);
+            }
+        }
+    }
+}
+
+/// Split text into sentences without converting it
+/// 
+/// Breaks after 。！？!?． (keeping runs like ！？ and closing quotes 」』） with
+/// the sentence) and at newlines. Terminators inside 「」『』（） don't split,
+/// so quoted speech stays within its sentence. Sentences are trimmed and
+/// empty ones are skipped.
+pub fn sentences(text: &str) -> Sentences<'_> {
+    Sentences { rest: text }
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// NUMBER EXPANSION
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+/// Kana readings for single digits 0-9
+const DIGIT_KANA: [&str; 10] = ["ぜろ", "いち", "に", "さん", "よん", "ご", "ろく", "なな", "はち", "きゅう"];
+
+/// Large number units (every 4 digits): 万, 億, 兆, 京
+const LARGE_UNIT_KANA: [&str; 5] = ["", "まん", "おく", "ちょう", "けい"];
+
+/// Currency symbols written before the number, read after it (¥1000 → せんえん)
+const CURRENCY_PREFIXES: [(char, &str); 5] = [
+    ('¥', "えん"), ('￥', "えん"), ('$', "ドル"), ('＄', "ドル"), ('€', "ユーロ"),
+];
+
+/// Symbols read as words next to numbers (numeric_patterns option):
+/// (symbol, reading, needs a digit after it)
+/// 
+/// - Percent: 50% / 50％ → 50パーセント
+/// - Ratio:   3:2 / 3：2 → 3たい2 (so clock times like 10:30 read as ratios too)
+/// - Range:   5〜10 / 5～10 / 5~10 → 5から10
+/// 
+/// Every symbol needs a digit right before it; ratio and range symbols also
+/// need one right after. Hyphens are left alone (phone numbers: 03-1234).
+const NUMERIC_OPERATORS: [(char, &str, bool); 7] = [
+    ('%', "パーセント", false), ('％', "パーセント", false),
+    (':', "たい", true), ('：', "たい", true),
+    ('〜', "から", true), ('～', "から", true), ('~', "から", true),
+];
+
+/// Check for ASCII or fullwidth digits
+fn is_digit(ch: char) -> bool {
+    ch.is_ascii_digit() || ('０'..='９').contains(&ch)
+}
+
+/// Numeric value of an ASCII or fullwidth digit
+fn digit_value(ch: char) -> usize {
+    if ch.is_ascii_digit() {
+        ch as usize - '0' as usize
+    } else {
+        ch as usize - '０' as usize
+    }
+}
+
+/// Read 1-9999 in kana with the usual sound changes (さんびゃく, はっせん, ...)
+fn four_digit_kana(value: u64) -> String {
+    let thousands = (value / 1000 % 10) as usize;
+    let hundreds = (value / 100 % 10) as usize;
+    let tens = (value / 10 % 10) as usize;
+    let ones = (value % 10) as usize;
+    let mut result = String::new();
+    
+    match thousands {
+        0 => {}
+        1 => result.push_str("せん"),
+        3 => result.push_str("さんぜん"),
+        8 => result.push_str("はっせん"),
+        d => { result.push_str(DIGIT_KANA[d]); result.push_str("せん"); }
+    }
+    
+    match hundreds {
+        0 => {}
+        1 => result.push_str("ひゃく"),
+        3 => result.push_str("さんびゃく"),
+        6 => result.push_str("ろっぴゃく"),
+        8 => result.push_str("はっぴゃく"),
+        d => { result.push_str(DIGIT_KANA[d]); result.push_str("ひゃく"); }
+    }
+    
+    match tens {
+        0 => {}
+        1 => result.push_str("じゅう"),
+        d => { result.push_str(DIGIT_KANA[d]); result.push_str("じゅう"); }
+    }
+    
+    if ones > 0 {
+        result.push_str(DIGIT_KANA[ones]);
+    }
+    
+    result
+}
+
+/// Read a whole number as Japanese kana (1000 → せん, 10000 → いちまん)
+fn number_to_kana(value: u64) -> String {
+    if value == 0 {
+        return DIGIT_KANA[0].to_string();
+    }
+    
+    // Split into 4-digit groups, lowest first
+    let mut groups = Vec::new();
+    let mut rest = value;
+    while rest > 0 {
+        groups.push(rest % 10000);
+        rest /= 10000;
+    }
+    
+    let mut result = String::new();
+    for unit in (0..groups.len()).rev() {
+        let group = groups[unit];
+        if group == 0 {
+            continue;
+        }
+        
+        let mut reading = four_digit_kana(group);
+        
+        // 1000 directly before a large unit is いっせん (いっせんまん)
+        if unit > 0 && group / 1000 == 1 {
+            reading = format!("いっ{}", reading);
+        }
+        
+        // Gemination before ちょう/けい (いっちょう, はっけい, じゅっちょう)
+        if unit >= 3 {
+            for &(full, short) in &[("いち", "いっ"), ("はち", "はっ"), ("じゅう", "じゅっ"), ("ろく", "ろっ")] {
+                if reading.ends_with(full) && (full != "ろく" || unit == 4) {
+                    reading.truncate(reading.len() - full.len());
+                    reading.push_str(short);
+                    break;
+                }
+            }
+        }
+        
+        result.push_str(&reading);
+        result.push_str(LARGE_UNIT_KANA[unit]);
+    }
+    
+    result
+}
+
+/// Read a run of digits digit by digit (used for leading zeros and overlong numbers)
+fn digits_to_kana(digits: &[char]) -> String {
+    digits.iter().map(|&ch| DIGIT_KANA[digit_value(ch)]).collect()
+}
+
+/// Read a digit run as a cardinal number, falling back to digit-by-digit
+fn digit_run_to_kana(digits: &[char]) -> String {
+    // "007" and numbers past 京 are read one digit at a time
+    if (digits.len() > 1 && digit_value(digits[0]) == 0) || digits.len() > 19 {
+        return digits_to_kana(digits);
+    }
+    
+    let mut value: u64 = 0;
+    for &ch in digits {
+        value = match value.checked_mul(10).and_then(|v| v.checked_add(digit_value(ch) as u64)) {
+            Some(v) => v,
+            None => return digits_to_kana(digits),
+        };
+    }
+    
+    number_to_kana(value)
+}
+
+/// Replace digit runs with their kana reading so the dictionary can convert them
+/// 
+/// Handles integers (1000 → せん), decimals (3.5 → さんてんご) and currency:
+/// ¥/￥/$/＄/€ before a number is read after it (¥1000 → せんえん),
+/// while suffixes like 円/ドル are left for the dictionary (1000円 → せん円).
+/// 
+/// Commas grouping thousands belong to the number (1,000,000 → ひゃくまん) and
+/// never reach punctuation handling as a pause. Only well-formed grouping
+/// counts - a lead group of 1-3 digits, then groups of exactly 3 after , or ，
+/// - so lists and decimal commas (1,2,3 / 3,14) keep their comma.
+fn expand_numbers(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len() * 2);
+    let mut pos = 0;
+    
+    while pos < chars.len() {
+        // Currency prefix immediately followed by a number
+        let currency = CURRENCY_PREFIXES.iter()
+            .find(|&&(symbol, _)| symbol == chars[pos])
+            .map(|&(_, reading)| reading);
+        let number_start = if currency.is_some() { pos + 1 } else { pos };
+        
+        if number_start >= chars.len() || !is_digit(chars[number_start]) {
+            result.push(chars[pos]);
+            pos += 1;
+            continue;
+        }
+        
+        // Integer part
+        let mut end = number_start;
+        while end < chars.len() && is_digit(chars[end]) {
+            end += 1;
+        }
+        let mut digits = chars[number_start..end].to_vec();
+        
+        // Thousands separators: 1,000,000 reads as one number
+        if digits.len() <= 3 && digit_value(digits[0]) != 0 {
+            while end + 3 < chars.len() && matches!(chars[end], ',' | '，') &&
+                chars[end + 1..end + 4].iter().all(|&ch| is_digit(ch)) &&
+                chars.get(end + 4).is_none_or(|&ch| !is_digit(ch))
+            {
+                digits.extend_from_slice(&chars[end + 1..end + 4]);
+                end += 4;
+            }
+        }
+        result.push_str(&digit_run_to_kana(&digits));
+        
+        // Decimal part: digits after the point are read one by one
+        if end + 1 < chars.len() && matches!(chars[end], '.' | '．') && is_digit(chars[end + 1]) {
+            let fraction_start = end + 1;
+            end = fraction_start;
+            while end < chars.len() && is_digit(chars[end]) {
+                end += 1;
+            }
+            result.push_str("てん");
+            result.push_str(&digits_to_kana(&chars[fraction_start..end]));
+        }
+        
+        if let Some(reading) = currency {
+            result.push_str(reading);
+        }
+        
+        pos = end;
+    }
+    
+    result
+}
+
+/// Replace symbols in numeric patterns with their readings (see NUMERIC_OPERATORS)
+fn read_numeric_operators(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len() + 8);
+    
+    for (i, &ch) in chars.iter().enumerate() {
+        let digit_before = i > 0 && is_digit(chars[i - 1]);
+        let digit_after = chars.get(i + 1).is_some_and(|&c| is_digit(c));
+        
+        match NUMERIC_OPERATORS.iter().find(|&&(symbol, _, _)| symbol == ch) {
+            Some(&(_, reading, needs_after)) if digit_before && (digit_after || !needs_after) => {
+                result.push_str(reading);
+            }
+            _ => result.push(ch),
+        }
+    }
+    
+    result
+}
+
+/// Unicode superscript digits ⁰-⁹ (exponents: x², 10³)
+const SUPERSCRIPT_DIGITS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+
+/// Unicode subscript digits ₀-₉ (chemical formulas: H₂O, CO₂)
+const SUBSCRIPT_DIGITS: [char; 10] = ['₀', '₁', '₂', '₃', '₄', '₅', '₆', '₇', '₈', '₉'];
+
+/// Digits as read in chemical formulas, which Japanese reads in English (CO₂ → シーオーツー)
+const ENGLISH_DIGIT_KANA: [&str; 10] = [
+    "ゼロ", "ワン", "ツー", "スリー", "フォー", "ファイブ", "シックス", "セブン", "エイト", "ナイン",
+];
+
+/// Characters read_scientific_notation() may rewrite
+fn is_scientific_char(ch: char) -> bool {
+    SUPERSCRIPT_DIGITS.contains(&ch) || SUBSCRIPT_DIGITS.contains(&ch) || matches!(ch, '^' | '＾')
+}
+
+/// Exponent reading: の + number + 乗 (3 → のさん乗, -3 → のマイナスさん乗)
+fn exponent_kana(negative: bool, digits: &[char]) -> String {
+    let sign = if negative { "マイナス" } else { "" };
+    format!("の{}{}乗", sign, digit_run_to_kana(digits))
+}
+
+/// Read exponents and subscripts (scientific_notation option)
+/// 
+/// - Superscripts: x² → xのに乗, 10⁻³ → 10のマイナスさん乗
+/// - Caret:        10^3 / 10^-3 → 10のさん乗 / 10のマイナスさん乗
+/// - Subscripts:   H₂O → HツーO, C₁₂ → Cワンツー (digit by digit, in English)
+/// 
+/// An exponent needs something other than whitespace right before it, and a
+/// caret needs digits after it (^_^ stays). Subscripts are only read after a
+/// Latin letter or a closing parenthesis, as in formulas: Ca(OH)₂.
+fn read_scientific_notation(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len() * 2);
+    let mut pos = 0;
+    
+    while pos < chars.len() {
+        let ch = chars[pos];
+        let has_base = pos > 0 && !chars[pos - 1].is_whitespace();
+        let superscript = |c: char| SUPERSCRIPT_DIGITS.iter().position(|&d| d == c);
+        
+        // Superscript exponent, with an optional superscript minus
+        let negative = ch == '⁻';
+        let start = pos + negative as usize;
+        if has_base && chars.get(start).is_some_and(|&c| superscript(c).is_some()) {
+            let mut digits = Vec::new();
+            let mut end = start;
+            while let Some(value) = chars.get(end).and_then(|&c| superscript(c)) {
+                digits.push((b'0' + value as u8) as char);
+                end += 1;
+            }
+            result.push_str(&exponent_kana(negative, &digits));
+            pos = end;
+            continue;
+        }
+        
+        // Caret exponent: ^3, ^-3, ＾３
+        if has_base && matches!(ch, '^' | '＾') {
+            let negative = chars.get(pos + 1).is_some_and(|&c| matches!(c, '-' | '−' | '－'));
+            let start = pos + 1 + negative as usize;
+            let end = start + chars[start.min(chars.len())..].iter().take_while(|&&c| is_digit(c)).count();
+            if end > start {
+                result.push_str(&exponent_kana(negative, &chars[start..end]));
+                pos = end;
+                continue;
+            }
+        }
+        
+        // Subscripts in a formula, read one digit at a time
+        if let Some(value) = SUBSCRIPT_DIGITS.iter().position(|&d| d == ch) {
+            let in_formula = result.chars().last().is_some_and(|c| c.is_ascii_alphabetic() || c == ')' || c == '）')
+                || (pos > 0 && SUBSCRIPT_DIGITS.contains(&chars[pos - 1]));
+            if in_formula {
+                result.push_str(ENGLISH_DIGIT_KANA[value]);
+                pos += 1;
+                continue;
+            }
+        }
+        
+        result.push(ch);
+        pos += 1;
+    }
+    
+    result
+}
+
+/// Bullets dropped at the start of a line (list_markers option)
+/// ・ only counts at the start of a line - inside a line it joins names (ジョン・スミス).
+const LIST_BULLETS: [char; 12] = ['・', '•', '●', '○', '◆', '◇', '■', '□', '▪', '※', '-', '*'];
+
+/// Read the list marker starting a line, if any: (chars consumed, reading)
+/// 
+/// - Enumerators: 1) / １） / 2. / ３． / 4、 / (5) / （６） → number + 、
+///   ("2.5" is a decimal, not an enumerator)
+/// - Circled numbers ①-⑳ → number + 、
+/// - Bullets (LIST_BULLETS) → nothing
+fn list_marker(chars: &[char]) -> Option<(usize, String)> {
+    let first = *chars.first()?;
+    
+    if LIST_BULLETS.contains(&first) {
+        return Some((1, String::new()));
+    }
+    
+    if ('①'..='⑳').contains(&first) {
+        return Some((1, format!("{}、", number_to_kana(first as u64 - '①' as u64 + 1))));
+    }
+    
+    let open = matches!(first, '(' | '（') as usize;
+    let digits = chars[open..].iter().take_while(|&&c| is_digit(c)).count();
+    if digits == 0 || digits > 3 {
+        return None;
+    }
+    
+    let close = *chars.get(open + digits)?;
+    let closes = if open == 1 {
+        matches!(close, ')' | '）')
+    } else {
+        matches!(close, ')' | '）' | '.' | '．' | '、') && !chars.get(open + digits + 1).is_some_and(|&c| is_digit(c))
+    };
+    
+    if closes {
+        Some((open + digits + 1, format!("{}、", digit_run_to_kana(&chars[open..open + digits]))))
+    } else {
+        None
+    }
+}
+
+/// Apply list_marker() at the start of every line (after its indentation)
+fn read_list_markers(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_start();
+        let indent = &line[..line.len() - body.len()];
+        let chars: Vec<char> = body.chars().collect();
+        
+        match list_marker(&chars) {
+            Some((length, reading)) => {
+                result.push_str(indent);
+                result.push_str(&reading);
+                let rest: String = chars[length..].iter().collect();
+                result.push_str(rest.trim_start());
+            }
+            None => result.push_str(line),
+        }
+    }
+    
+    result
+}
+
+/// Replace each digit with its kana reading, space-separated within a run
+/// so readings never merge with their neighbours (0120 → ぜろ いち に ぜろ)
+fn expand_digits(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() * 3);
+    let mut previous_digit = false;
+    
+    for ch in text.chars() {
+        if is_digit(ch) {
+            if previous_digit {
+                result.push(' ');
+            }
+            result.push_str(DIGIT_KANA[digit_value(ch)]);
+            previous_digit = true;
+        } else {
+            result.push(ch);
+            previous_digit = false;
+        }
+    }
+    
+    result
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// INPUT ENCODING
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+/// Guess the encoding of raw input bytes
+/// UTF-8 (with or without BOM) wins if the bytes are valid UTF-8.
+/// With the "encoding" feature, Shift-JIS and EUC-JP are tried next.
+fn detect_encoding(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) || std::str::from_utf8(bytes).is_ok() {
+        return "utf-8";
+    }
+    
+    #[cfg(feature = "encoding")]
+    {
+        // Legacy corpora are mostly Shift-JIS, so it gets checked first
+        for &(label, encoding) in &[("shift_jis", encoding_rs::SHIFT_JIS), ("euc-jp", encoding_rs::EUC_JP)] {
+            if encoding.decode_without_bom_handling_and_without_replacement(bytes).is_some() {
+                return label;
+            }
+        }
+    }
+    
+    "utf-8"
+}
+
+/// Decode bytes as UTF-8; the Utf8 error names the byte offset of the first invalid sequence
+fn decode_utf8(mut bytes: Vec<u8>) -> Result<String, ConverterError> {
+    // Strip the UTF-8 BOM if present
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        bytes.drain(..3);
+    }
+    
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Decode bytes with a legacy encoding, reporting the byte offset of the first malformed sequence
+/// Decode failures are Io errors of kind InvalidData.
+#[cfg(feature = "encoding")]
+fn decode_legacy(bytes: &[u8], encoding: &'static encoding_rs::Encoding) -> Result<String, ConverterError> {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let capacity = decoder.max_utf8_buffer_length_without_replacement(bytes.len())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "input too large to decode"))?;
+    let mut output = String::with_capacity(capacity);
+    
+    let (result, read) = decoder.decode_to_string_without_replacement(bytes, &mut output, true);
+    match result {
+        encoding_rs::DecoderResult::InputEmpty => Ok(output),
+        encoding_rs::DecoderResult::Malformed(bad_len, consumed_after) => {
+            let offset = read - bad_len as usize - consumed_after as usize;
+            Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid {} at byte offset {}", encoding.name(), offset)).into())
+        }
+        encoding_rs::DecoderResult::OutputFull => Err(io::Error::new(io::ErrorKind::InvalidData, "decoder output buffer too small").into()),
+    }
+}
+
+/// Load a text file and transcode it to UTF-8
+/// 
+/// @param file_path Text file to read
+/// @param encoding Encoding label ("utf-8", "sjis", "shift_jis", "euc-jp", ...),
+///                 or None to detect it with detect_encoding()
+/// 
+/// Legacy encodings require the "encoding" feature (encoding_rs); without it,
+/// or for an unknown label, the error is Io of kind Unsupported.
+/// Decode errors name the offending byte offset.
+fn load_text_with_encoding(file_path: &str, encoding: Option<&str>) -> Result<String, ConverterError> {
+    let bytes = fs::read(file_path)?;
+    let label = match encoding {
+        Some(label) => label.trim().to_ascii_lowercase(),
+        None => detect_encoding(&bytes).to_string(),
+    };
+    
+    if label == "utf-8" || label == "utf8" {
+        return decode_utf8(bytes);
+    }
+    
+    #[cfg(feature = "encoding")]
+    {
+        let encoding = encoding_rs::Encoding::for_label(label.as_bytes())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, format!("unknown encoding: {}", label)))?;
+        decode_legacy(&bytes, encoding)
+    }
+    
+    #[cfg(not(feature = "encoding"))]
+    {
+        Err(io::Error::new(io::ErrorKind::Unsupported, format!("encoding '{}' requires building with --cfg 'feature=\"encoding\"' (encoding_rs)", label)).into())
+    }
+}
+
+/// Build a binary trie file from a JSON dictionary (and optional word list)
+/// 
+/// Usage: jpn_to_phoneme build-trie ja_phonemes.json japanese.trie [ja_words.txt]
+/// 
+/// Words not present in the phoneme dictionary are stored with an empty value,
+/// so the binary file carries both phonemes and segmentation words.
+/// The written file is reloaded afterwards to validate the entry count.
+fn build_binary_trie(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.len() < 2 {
+        eprintln!("💡 Usage: ./jpn_to_phoneme build-trie <input.json> <output.trie> [words.txt]");
+        std::process::exit(1);
+    }
+    
+    let json_path = &args[0];
+    let trie_path = &args[1];
+    let words_path = args.get(2);
+    
+    println!("🔨 Building binary trie: {} → {}", json_path, trie_path);
+    let start_time = Instant::now();
+    
+    // Parse the JSON dictionary (same parser as load_from_json)
+    let contents = fs::read_to_string(json_path)?;
+    let data = PhonemeConverter::new().parse_json(&contents)?;
+    println!("   Phoneme entries: {}", data.len());
+    
+    // Sort by key so the output is deterministic
+    let mut entries: Vec<(String, String)> = data.into_iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    
+    // Add word-only entries (not in phoneme dict) with empty marker values
+    if let Some(words_path) = words_path {
+        let existing: std::collections::HashSet<String> = entries.iter().map(|(k, _)| k.clone()).collect();
+        let mut word_entries = Vec::new();
+        
+        let reader = BufReader::new(fs::File::open(words_path)?);
+        for line in reader.lines() {
+            let line = line?;
+            let word = line.trim();
+            
+            if !word.is_empty() && !existing.contains(word) {
+                word_entries.push((word.to_string(), String::new()));
+            }
+        }
+        
+        word_entries.sort_by(|a, b| a.0.cmp(&b.0));
+        word_entries.dedup_by(|a, b| a.0 == b.0);
+        println!("   Word-only entries: {}", word_entries.len());
+        entries.extend(word_entries);
+    }
+    
+    write_binary_format(trie_path, &entries)?;
+    
+    let elapsed = start_time.elapsed();
+    println!("✅ Wrote {} entries in {}ms", entries.len(), elapsed.as_millis());
+    
+    // Validate by reloading the file we just wrote
+    println!("\n🔍 Validating {}...", trie_path);
+    let mut check = PhonemeConverter::new();
+    if !check.try_load_binary_format(trie_path)? {
+        return Err(format!("could not reload {}", trie_path).into());
+    }
+    
+    if check.entry_count != entries.len() {
+        return Err(format!("entry count mismatch: wrote {}, reloaded {}",
+                           entries.len(), check.entry_count).into());
+    }
+    
+    println!("✅ Validation passed: {} entries", check.entry_count);
+    Ok(())
+}
+
+/// Command-line flags for the conversion modes
+#[derive(Debug, Default)]
+struct CliOptions {
+    texts: Vec<String>,          // Positional text arguments
+    input_path: Option<String>,  // --input <file>: convert each line of a file
+    encoding: Option<String>,    // --encoding <label>: input file encoding (auto-detect if unset)
+    dialect: Option<String>,     // --dialect <name>: load dialects/<name>.json as a variant overlay
+    substitutions: Option<String>, // --substitutions <file>: variant kanji map (國 → 国) applied first
+    interleave: Option<InterleaveMode>, // --interleave[=word|line]: print word/phoneme pairs for review
+    tsv: bool,                   // --tsv: one "original<TAB>phonemes" line per input, nothing else
+    bilingual: bool,             // --bilingual: "word<TAB>ipa<TAB>romaji" per word, nothing else
+    jsonl: bool,                 // --jsonl: stream --input (or stdin) to one JSON object per line
+    stdin: bool,                 // --stdin: one phoneme line per stdin line, nothing else
+    output_format: OutputFormat, // --ssml: one <speak> document per input, a <phoneme> tag per word; --mfa: phones for forced alignment
+    number_mode: NumberMode,     // --numbers[=cardinal|digits]: ¥1000 → せんえん, 0120 → ぜろ いち に ぜろ
+    add_tie_bars: bool,          // --tie-bars: write affricates as t͡ɕ, t͡s, d͡ʑ
+    classical_kana: bool,        // --classical-kana: read ゐ/ゑ as modern i/e, くゎ as kʷa
+    merge_long_vowels: bool,     // --long-vowels: write ー and doubled vowels as ː
+    context_readings: bool,      // --context-readings: 何時 → naɴʥi, 何色 → naniiɾo
+    elongation: Option<ElongationMode>, // --elongation[=collapse|proportional]: すごーーい → sɯgoːi
+    ipa_override_delimiter: Option<char>, // --ipa-overrides[=<char>]: 東京/toːkʲoː/ written verbatim
+    wo_reading: WoReading,       // --wo=wo: read を/ヲ as ɰᵝo instead of o
+    word_case: WordCase,         // --word-case=lower|upper-initial: casing of each segmented word
+    unknown_kanji_phoneme: Option<String>, // --unknown-kanji=<token>: placeholder for unmatched kanji
+    reading_delimiter: Option<char>, // --pipe-readings[=<char>]: 日本語|にほんご reads にほんご
+    colloquial_contractions: bool, // --colloquial: 食べている → 食べてる, という → っていう
+    strip_control_chars: bool,   // --strip-control: drop zero-width/control characters
+    normalize_ideographic_space: bool, // --ascii-spaces: write U+3000 as an ASCII space
+    numeric_patterns: bool,      // --numeric-patterns: 50% → パーセント, 3:2 → たい, 5〜10 → から
+    scientific_notation: bool,   // --scientific: 10^3 → 10のさん乗, H₂O → HツーO
+    list_markers: bool,          // --list-markers: drop ・/• bullets, read １）/② enumerators
+    explicit_space_token: Option<String>, // --space-token[=<token>]: input spaces → <pause>
+    ascii_case: AsciiCase,       // --ascii-case=lower|upper: case of unconverted ASCII letters
+    reverse_morae: bool,         // --reverse-morae: がっこう → ɯokːga (with --tsv)
+    loanword_final_vowel: LoanwordVowelPolicy, // --loanword-vowels=long|short: コンピュータ ↔ コンピューター
+    fold_katakana: bool,         // --fold-katakana: read katakana as hiragana (hiragana-only dictionaries)
+    phoneme_inventory: PhonemeInventory, // --inventory=simplified: ɕiɴbɯɴ → shiNbuN
+    geminate_style: GeminateStyle, // --geminates=doubled: gakːoɯ → gakkoɯ
+    long_vowel_style: LongVowelStyle, // --long-vowel-style=doubled|macron: koːçiː → kooçii / kōçī
+    show_segments: Option<String>, // --show-segments[=<delim>]: 私|は|リンゴ instead of phonemes
+    dedup_consecutive_words: bool, // --dedup-words: これ これ は → これ は (needs word segmentation)
+    kana_fast_path: bool,        // --kana-fast-path: pure-kana input skips segmentation (no word spaces)
+    viterbi: bool,               // --viterbi: lattice segmentation (fewest unmatched chars, then words)
+    max_word_len: Option<usize>, // --max-word-len=<n>: no segmentation match longer than n chars
+}
+
+/// Escape a --tsv field: backslash → \\, tab → \t, newline → \n, CR → \r
+/// Every row has exactly one real tab, between the original and the phonemes.
+fn escape_tsv(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    
+    for ch in field.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    
+    escaped
+}
+
+/// Layout for --interleave review output (word<TAB>phonemes)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InterleaveMode {
+    PerWord,  // One word/phoneme pair per line, blank line between inputs
+    PerLine,  // All pairs of an input on one tab-separated line
+}
+
+/// Parse conversion flags, leaving everything else as text to convert
+fn parse_cli_args(args: &[String]) -> Result<CliOptions, String> {
+    let mut options = CliOptions::default();
+    let mut iter = args.iter();
+    
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--input" => {
+                options.input_path = Some(iter.next().ok_or("--input requires a file path")?.clone());
+            }
+            "--encoding" => {
+                options.encoding = Some(iter.next().ok_or("--encoding requires a label (e.g. sjis)")?.clone());
+            }
+            "--dialect" => {
+                options.dialect = Some(iter.next().ok_or("--dialect requires a name (e.g. kansai)")?.clone());
+            }
+            "--substitutions" => {
+                options.substitutions = Some(iter.next().ok_or("--substitutions requires a file path")?.clone());
+            }
+            "--numbers" | "--numbers=cardinal" => options.number_mode = NumberMode::Cardinal,
+            "--numbers=digits" => options.number_mode = NumberMode::DigitByDigit,
+            "--tie-bars" => options.add_tie_bars = true,
+            "--classical-kana" => options.classical_kana = true,
+            "--long-vowels" => options.merge_long_vowels = true,
+            "--context-readings" => options.context_readings = true,
+            "--elongation" | "--elongation=collapse" => options.elongation = Some(ElongationMode::Collapse),
+            "--elongation=proportional" => options.elongation = Some(ElongationMode::Proportional),
+            "--ipa-overrides" => options.ipa_override_delimiter = Some('/'),
+            "--pipe-readings" => options.reading_delimiter = Some('|'),
+            "--wo=wo" => options.wo_reading = WoReading::Wo,
+            "--wo=o" => options.wo_reading = WoReading::O,
+            "--word-case=lower" => options.word_case = WordCase::Lower,
+            "--word-case=upper-initial" => options.word_case = WordCase::UpperInitial,
+            "--colloquial" => options.colloquial_contractions = true,
+            "--strip-control" => options.strip_control_chars = true,
+            "--ascii-spaces" => options.normalize_ideographic_space = true,
+            "--numeric-patterns" => options.numeric_patterns = true,
+            "--scientific" => options.scientific_notation = true,
+            "--list-markers" => options.list_markers = true,
+            "--loanword-vowels=long" => options.loanword_final_vowel = LoanwordVowelPolicy::Long,
+            "--loanword-vowels=short" => options.loanword_final_vowel = LoanwordVowelPolicy::Short,
+            "--fold-katakana" => options.fold_katakana = true,
+            "--space-token" => options.explicit_space_token = Some("<pause>".to_string()),
+            "--reverse-morae" => options.reverse_morae = true,
+            "--dedup-words" => options.dedup_consecutive_words = true,
+            "--kana-fast-path" => options.kana_fast_path = true,
+            "--viterbi" => options.viterbi = true,
+            "--inventory=simplified" => options.phoneme_inventory = PhonemeInventory::Simplified,
+            "--geminates=doubled" => options.geminate_style = GeminateStyle::Doubled,
+            "--geminates=length" => options.geminate_style = GeminateStyle::Length,
+            "--long-vowel-style=doubled" => options.long_vowel_style = LongVowelStyle::Doubled,
+            "--long-vowel-style=macron" => options.long_vowel_style = LongVowelStyle::Macron,
+            "--long-vowel-style=length" => options.long_vowel_style = LongVowelStyle::Length,
+            "--ascii-case=lower" => options.ascii_case = AsciiCase::Lower,
+            "--ascii-case=upper" => options.ascii_case = AsciiCase::Upper,
+            "--show-segments" => options.show_segments = Some("|".to_string()),
+            _ if arg.starts_with("--show-segments=") => {
+                options.show_segments = Some(arg["--show-segments=".len()..].to_string());
+            }
+            _ if arg.starts_with("--max-word-len=") => {
+                let value = &arg["--max-word-len=".len()..];
+                match value.parse() {
+                    Ok(n) if n > 0 => options.max_word_len = Some(n),
+                    _ => return Err(format!("--max-word-len= takes a positive number, got \"{}\"", value)),
+                }
+            }
+            _ if arg.starts_with("--unknown-kanji=") => {
+                options.unknown_kanji_phoneme = Some(arg["--unknown-kanji=".len()..].to_string());
+            }
+            _ if arg.starts_with("--space-token=") => {
+                options.explicit_space_token = Some(arg["--space-token=".len()..].to_string());
+            }
+            _ if arg.starts_with("--ipa-overrides=") => {
+                let mut delimiter = arg["--ipa-overrides=".len()..].chars();
+                match (delimiter.next(), delimiter.next()) {
+                    (Some(ch), None) => options.ipa_override_delimiter = Some(ch),
+                    _ => return Err("--ipa-overrides= takes a single delimiter character".to_string()),
+                }
+            }
+            _ if arg.starts_with("--pipe-readings=") => {
+                let mut delimiter = arg["--pipe-readings=".len()..].chars();
+                match (delimiter.next(), delimiter.next()) {
+                    (Some(ch), None) => options.reading_delimiter = Some(ch),
+                    _ => return Err("--pipe-readings= takes a single delimiter character".to_string()),
+                }
+            }
+            "--interleave" | "--interleave=word" => options.interleave = Some(InterleaveMode::PerWord),
+            "--interleave=line" => options.interleave = Some(InterleaveMode::PerLine),
+            "--tsv" => options.tsv = true,
+            "--bilingual" => options.bilingual = true,
+            "--jsonl" => options.jsonl = true,
+            "--stdin" => options.stdin = true,
+            "--ssml" => options.output_format = OutputFormat::Ssml,
+            "--mfa" => options.output_format = OutputFormat::Mfa,
+            _ => options.texts.push(arg.clone()),
+        }
+    }
+    
+    Ok(options)
+}
+
+/// Export a binary trie back to an editable JSON dictionary
+/// 
+/// Usage: jpn_to_phoneme export-json japanese.trie ja_phonemes.json
+/// 
+/// Word-only entries are not exported (they have no phoneme to write).
+fn export_json(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.len() < 2 {
+        eprintln!("💡 Usage: ./jpn_to_phoneme export-json <input.trie> <output.json>");
+        std::process::exit(1);
+    }
+    
+    let mut converter = PhonemeConverter::new();
+    if !converter.try_load_binary_format(&args[0])? {
+        return Err(format!("binary trie not found: {}", args[0]).into());
+    }
+    
+    let start_time = Instant::now();
+    converter.save_to_json(&args[1])?;
+    println!("✅ Exported {} phoneme entries to {} in {}ms",
+             converter.entries().len(), args[1], start_time.elapsed().as_millis());
+    
+    Ok(())
+}
+
+/// Show a dictionary entry next to its neighbouring keys, for dictionary authoring
+/// 
+/// Usage: jpn_to_phoneme lookup 東京 [neighbors]
+/// 
+/// Prints the key's phonemes (or its greedy conversion when it isn't a key)
+/// followed by nearby keys in sorted order with their phonemes (default 10),
+/// so inconsistencies between related entries stand out.
+fn lookup_entry(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let key = match args.get(0) {
+        Some(key) => key,
+        None => {
+            eprintln!("💡 Usage: ./jpn_to_phoneme lookup <word> [neighbors]");
+            std::process::exit(1);
+        }
+    };
+    let count: usize = match args.get(1) {
+        Some(n) => n.parse().map_err(|_| format!("invalid neighbor count: {}", n))?,
+        None => 10,
+    };
+    
+    let mut converter = PhonemeConverter::new();
+    if !load_default_trie(&mut converter)? {
+        converter.load_from_json("ja_phonemes.json")?;
+    }
+    
+    println!();
+    match converter.lookup(key) {
+        Some(phoneme) => println!("🔍 {} → {}", key, phoneme),
+        None => println!("🔍 {} is not in the dictionary (converts to: {})", key, converter.convert(key)),
+    }
+    
+    println!("\n   Neighbors:");
+    for neighbor in converter.neighbor_keys(key, count) {
+        // Word-only entries (binary format) have no phonemes of their own
+        let phoneme = converter.lookup(&neighbor).map_or("(word only)", |p| p.as_str());
+        println!("     {}\t{}", neighbor, phoneme);
+    }
+    
+    Ok(())
+}
+
+/// Benchmark trie lookups for the compiled child layout
+/// 
+/// Usage: jpn_to_phoneme bench-trie [sentences.txt] [iterations]
+/// 
+/// Reports load time, node count, approximate memory of the child tables and
+/// raw lookup throughput (convert_word over every line, no segmentation).
+/// Build once normally and once with --cfg 'feature="vec_trie"' and compare
+/// the two reports - benchmark_trie.bat does both.
+fn benchmark_trie(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let sentences_path = args.get(0).map(|s| s.as_str()).unwrap_or("test_sentences.txt");
+    let iterations: usize = match args.get(1) {
+        Some(n) => n.parse().map_err(|_| format!("invalid iteration count: {}", n))?,
+        None => 1000,
+    };
+    
+    let layout = if cfg!(feature = "vec_trie") {
+        "sorted Vec (vec_trie)"
+    } else if cfg!(feature = "pool_trie") {
+        "inline node pool (pool_trie)"
+    } else {
+        "HashMap"
+    };
+    println!("📊 Trie benchmark - child layout: {}", layout);
+    
+    let mut converter = PhonemeConverter::new();
+    let load_start = Instant::now();
+    let loaded_binary = load_default_trie(&mut converter)?;
+    if !loaded_binary {
+        converter.load_from_json("ja_phonemes.json")?;
+    }
+    let load_ms = load_start.elapsed().as_secs_f64() * 1000.0;
+    
+    // Walk every node for the memory estimate
+    let mut nodes = 0usize;
+    let mut bytes = std::mem::size_of::<TrieNode>();  // Root (children count their own nodes)
+    let mut stack = vec![&converter.root];
+    while let Some(node) = stack.pop() {
+        nodes += 1;
+        bytes += children_heap_bytes(&node.children);
+        bytes += node.phoneme.as_ref().map_or(0, |p| p.capacity());
+        for child in node.children.values() {
+            stack.push(child);
+        }
+    }
+    
+    let text = fs::read_to_string(sentences_path)?;
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    let chars_per_pass: usize = lines.iter().map(|line| line.chars().count()).sum();
+    
+    // Keep the output alive so the optimizer can't drop the walks
+    let mut checksum = 0usize;
+    let lookup_start = Instant::now();
+    for _ in 0..iterations {
+        for line in &lines {
+            checksum += converter.convert_word(line).len();
+        }
+    }
+    let lookup_secs = lookup_start.elapsed().as_secs_f64();
+    let total_chars = chars_per_pass * iterations;
+    
+    // Batch API: every line × iterations as one batch, plain loop vs convert_batch()
+    let batch: Vec<&str> = lines.iter().cycle().take(lines.len() * iterations).cloned().collect();
+    let sequential_start = Instant::now();
+    let sequential: Vec<String> = batch.iter().map(|line| converter.convert(line)).collect();
+    let sequential_secs = sequential_start.elapsed().as_secs_f64();
+    let batch_start = Instant::now();
+    let batched = converter.convert_batch(&batch);
+    let batch_secs = batch_start.elapsed().as_secs_f64();
+    if batched != sequential {
+        return Err("convert_batch() output differs from sequential convert()".into());
+    }
+    let batch_mode = if cfg!(feature = "parallel") { "parallel" } else { "sequential, no \"parallel\" feature" };
+    
+    // Kana fast path: the kana of every line, segmented vs one trie walk
+    let mut segmenter = if loaded_binary { WordSegmenter::new() } else { converter.derive_word_list_from_dict() };
+    let kana_lines: Vec<String> = lines.iter()
+        .map(|line| line.chars().filter(|&c| is_kana(c) && !matches!(c, 'は' | 'へ' | 'を')).collect::<String>())
+        .filter(|line| !line.is_empty())
+        .collect();
+    let mut time_kana = |segmenter: &WordSegmenter| {
+        let start = Instant::now();
+        for _ in 0..iterations {
+            for line in &kana_lines {
+                checksum += convert_with_segmentation(&converter, line, segmenter).len();
+            }
+        }
+        start.elapsed().as_secs_f64()
+    };
+    let segmented_secs = time_kana(&segmenter);
+    segmenter.kana_fast_path = true;
+    let fast_path_secs = time_kana(&segmenter);
+    
+    println!("   Load time:    {:.1} ms", load_ms);
+    println!("   Nodes:        {}", nodes);
+    println!("   Trie memory:  ~{:.1} MB", bytes as f64 / (1024.0 * 1024.0));
+    println!("   Lookups:      {} lines × {} iterations ({} chars)", lines.len(), iterations, total_chars);
+    println!("   Lookup time:  {:.1} ms ({:.2} M chars/s)", lookup_secs * 1000.0,
+             total_chars as f64 / lookup_secs / 1_000_000.0);
+    println!("   Batch:        {} inputs: loop {:.1} ms, convert_batch {:.1} ms ({:.1}x, {})",
+             batch.len(), sequential_secs * 1000.0, batch_secs * 1000.0, sequential_secs / batch_secs, batch_mode);
+    println!("   Kana input:   {} lines: segmented {:.1} ms, kana_fast_path {:.1} ms ({:.1}x)",
+             kana_lines.len(), segmented_secs * 1000.0, fast_path_secs * 1000.0, segmented_secs / fast_path_secs);
+    println!("   Checksum:     {}", checksum);
+    
+    Ok(())
+}
+
+/// Load japanese.trie for a tool that falls back to ja_phonemes.json
+/// Ok(false) if it is missing or not a trie this build reads (reported),
+/// so the caller loads JSON instead; other errors are returned.
+fn load_default_trie(converter: &mut PhonemeConverter) -> Result<bool, ConverterError> {
+    match converter.try_load_binary_format("japanese.trie") {
+        Err(e) if e.is_format_error() => {
+            eprintln!("❌ {}", e);
+            Ok(false)
+        }
+        result => result,
+    }
+}
+
+/// Cases of a regression corpus as (line number, input, expected)
+/// Blank lines and # comments are skipped; Err is the 1-based number of the
+/// first line without a tab.
+fn parse_corpus(contents: &str) -> Result<Vec<(usize, &str, &str)>, usize> {
+    contents.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(index, line)| line.split_once('\t').map(|(input, expected)| (index + 1, input, expected)).ok_or(index + 1))
+        .collect()
+}
+
+/// Regression check against committed corpora of input → expected phonemes
+/// Usage: jpn_to_phoneme check [--romaji] [--viterbi] [--fold-katakana] [--dict=<path>] tests/corpus/seed.tsv [more.tsv ...]
+/// 
+/// Each line is `input<TAB>expected`; blank lines and lines starting with #
+/// are skipped. Inputs go through segmented conversion with default options,
+/// loaded the same way as the CLI (japanese.trie, else ja_phonemes.json with
+/// ja_words.txt). With --romaji the expected column is Hepburn romaji from
+/// RomajiConverter (ja_romaji.json) instead; --viterbi segments with the
+/// lattice (WordSegmenter::lattice_segmentation) and --fold-katakana reads
+/// katakana as hiragana (fold_katakana). --dict= checks one dictionary
+/// file instead, binary trie or JSON (gzip-compressed or not), with words
+/// taken from the dictionary itself. Every mismatch is printed with both
+/// outputs and where they first differ; any mismatch exits with status 1.
+fn check_corpus(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let flags = args.iter().take_while(|a| a.starts_with("--")).count();
+    let (flags, args) = args.split_at(flags);
+    let romaji_mode = flags.iter().any(|a| a == "--romaji");
+    let viterbi = flags.iter().any(|a| a == "--viterbi");
+    let dictionary = flags.iter().find_map(|a| a.strip_prefix("--dict="));
+    if args.is_empty() {
+        eprintln!("💡 Usage: ./jpn_to_phoneme check [--romaji] [--viterbi] [--fold-katakana] [--dict=<path>] <corpus.tsv> [more.tsv ...]");
+        std::process::exit(1);
+    }
+    
+    let mut romaji = RomajiConverter::new();
+    if romaji_mode {
+        romaji.load_from_json("ja_romaji.json")?;
+    }
+    
+    let mut converter = PhonemeConverter::new();
+    let mut segmenter = if romaji_mode {
+        WordSegmenter::new()  // Romaji is read off the kana, no phoneme dictionary needed
+    } else if let Some(path) = dictionary {
+        match converter.try_load_binary_format(path) {
+            Ok(true) => WordSegmenter::new(),
+            Ok(false) => return Err(format!("dictionary not found: {}", path).into()),
+            Err(e) if e.is_format_error() => {
+                converter.load_from_json(path)?;
+                converter.derive_word_list_from_dict()
+            }
+            Err(e) => return Err(e.into()),
+        }
+    } else if load_default_trie(&mut converter)? {
+        WordSegmenter::new()  // Words are already in the binary trie
+    } else {
+        converter.load_from_json("ja_phonemes.json")?;
+        if std::path::Path::new("ja_words.txt").exists() {
+            let mut seg = WordSegmenter::new();
+            seg.load_from_file("ja_words.txt")?;
+            seg
+        } else {
+            converter.derive_word_list_from_dict()
+        }
+    };
+    segmenter.lattice_segmentation = viterbi;
+    converter.options.fold_katakana = flags.iter().any(|a| a == "--fold-katakana");
+    
+    let mut passed = 0;
+    let mut failed = 0;
+    
+    for path in args {
+        let contents = fs::read_to_string(path)?;
+        let cases = parse_corpus(&contents)
+            .map_err(|line| format!("{}:{}: expected input<TAB>phonemes", path, line))?;
+        
+        for (line, input, expected) in cases {
+            let actual = if romaji_mode {
+                romaji.convert(input)
+            } else {
+                convert_with_segmentation(&converter, input, &segmenter)
+            };
+            if actual == expected {
+                passed += 1;
+                continue;
+            }
+            
+            failed += 1;
+            let differ_at = expected.chars().zip(actual.chars())
+                .take_while(|(e, a)| e == a)
+                .count();
+            println!("❌ {}:{}  {}", path, line, input);
+            println!("     expected: {}", expected);
+            println!("     actual:   {}", actual);
+            println!("     first difference at char {}: {:?} vs {:?}", differ_at,
+                     expected.chars().nth(differ_at).map_or(String::from("(end)"), String::from),
+                     actual.chars().nth(differ_at).map_or(String::from("(end)"), String::from));
+        }
+    }
+    
+    println!("\n{} {} passed, {} failed", if failed == 0 { "✅" } else { "❌" }, passed, failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    
+    Ok(())
+}
+
+/// Convert each line of `reader` to one phoneme line in `writer` (--stdin)
+/// Lines are trimmed; a blank line comes out blank, so output lines up with input.
+fn convert_lines<R: BufRead, W: Write>(converter: &PhonemeConverter, segmenter: Option<&WordSegmenter>,
+                                        reader: R, mut writer: W) -> io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        let text = line.trim();
+        let phonemes = match segmenter {
+            Some(seg) => convert_with_segmentation(converter, text, seg),
+            None => converter.convert(text),
+        };
+        writeln!(writer, "{}", phonemes)?;
+    }
+    
+    writer.flush()
+}
+
+pub fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| QUIET_FLAGS.contains(&arg.as_str())) {
+        QUIET.store(true, Ordering::Relaxed);
+    }
+    
+    status!("╔══════════════════════════════════════════════════════════╗");
+    status!("║  Japanese → Phoneme Converter (Rust)                    ║");
+    status!("║  Blazing fast IPA phoneme conversion                    ║");
+    status!("╚══════════════════════════════════════════════════════════╝\n");
+    
+    // Subcommand: build the binary trie from JSON
+    if args.first().map(|a| a.as_str()) == Some("build-trie") {
+        return build_binary_trie(&args[1..]);
+    }
+    
+    // Subcommand: write a binary trie back out as JSON
+    if args.first().map(|a| a.as_str()) == Some("export-json") {
+        return export_json(&args[1..]);
+    }
+    
+    // Subcommand: show a dictionary entry and its neighbours
+    if args.first().map(|a| a.as_str()) == Some("lookup") {
+        return lookup_entry(&args[1..]);
+    }
+    
+    // Subcommand: compare conversions against a regression corpus
+    if args.first().map(|a| a.as_str()) == Some("check") {
+        return check_corpus(&args[1..]);
+    }
+    
+    // Subcommand: measure trie load/lookup speed and memory
+    if args.first().map(|a| a.as_str()) == Some("bench-trie") {
+        return benchmark_trie(&args[1..]);
+    }
+    
+    let mut cli = match parse_cli_args(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("❌ Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    
+    // Read input file up front so decode errors surface before loading dictionaries
+    // --jsonl streams the input later instead of reading it all up front
+    if let (Some(ref path), false) = (&cli.input_path, cli.jsonl) {
+        let content = match load_text_with_encoding(path, cli.encoding.as_ref().map(|e| e.as_str())) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("❌ Error reading input {}: {}", path, e);
+                std::process::exit(1);
+            }
+        };
+        
+        cli.texts.extend(content.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string()));
+    }
+    
+    // Check if JSON file exists
+    if !std::path::Path::new("ja_phonemes.json").exists() {
+        eprintln!("❌ Error: ja_phonemes.json not found in current directory");
+        eprintln!("   Please ensure the phoneme dictionary is present.");
+        std::process::exit(1);
+    }
+    
+    // Initialize converter and load dictionary
+    // 🚀 Try binary trie first (100x faster!), fallback to JSON
+    let mut converter = PhonemeConverter::new();
+    let mut loaded_binary = false;
+    
+    // Try simple binary format (direct load into TrieNode)
+    match converter.try_load_binary_format("japanese.trie") {
+        Ok(true) => {
+            loaded_binary = true;
+            status!("   💡 Binary format loaded directly into TrieNode");
+        }
+        Ok(false) => {
+            // Fallback to JSON
+            status!("   ⚠️  Binary trie not found, loading JSON...");
+        }
+        Err(e) if e.is_format_error() => {
+            eprintln!("⚠️  Error loading binary trie: {}", e);
+            eprintln!("   Falling back to JSON...");
+        }
+        // Read part way: the trie may hold some entries already, don't add JSON on top
+        Err(e) => return Err(e.into()),
+    }
+    
+    if !loaded_binary {
+        converter.load_from_json("ja_phonemes.json")?;
+    }
+    
+    converter.options.number_mode = cli.number_mode;
+    converter.options.add_tie_bars = cli.add_tie_bars;
+    converter.options.classical_kana = cli.classical_kana;
+    converter.options.merge_long_vowels = cli.merge_long_vowels;
+    converter.options.context_readings = cli.context_readings;
+    converter.options.elongation = cli.elongation;
+    converter.options.ipa_override_delimiter = cli.ipa_override_delimiter;
+    converter.options.reading_delimiter = cli.reading_delimiter;
+    converter.options.wo_reading = cli.wo_reading;
+    converter.options.word_case = cli.word_case;
+    converter.options.colloquial_contractions = cli.colloquial_contractions;
+    converter.options.strip_control_chars = cli.strip_control_chars;
+    converter.options.normalize_ideographic_space = cli.normalize_ideographic_space;
+    converter.options.numeric_patterns = cli.numeric_patterns;
+    converter.options.scientific_notation = cli.scientific_notation;
+    converter.options.list_markers = cli.list_markers;
+    converter.options.explicit_space_token = cli.explicit_space_token.clone();
+    converter.options.unknown_kanji_phoneme = cli.unknown_kanji_phoneme.clone();
+    converter.options.ascii_case = cli.ascii_case;
+    converter.options.reverse_morae = cli.reverse_morae;
+    converter.options.loanword_final_vowel = cli.loanword_final_vowel;
+    converter.options.fold_katakana = cli.fold_katakana;
+    converter.options.phoneme_inventory = cli.phoneme_inventory;
+    converter.options.geminate_style = cli.geminate_style;
+    converter.options.long_vowel_style = cli.long_vowel_style;
+    
+    // Optional dialect overlay (consulted before the standard dictionary)
+    if let Some(ref dialect) = cli.dialect {
+        status!("🗾 Loading dialect variant: {}", dialect);
+        if let Err(e) = converter.load_dialect(dialect) {
+            eprintln!("❌ Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+    
+    // Optional variant kanji map (國 → 国), applied before everything else
+    if let Some(ref path) = cli.substitutions {
+        if let Err(e) = converter.load_substitutions(path) {
+            eprintln!("❌ Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+    
+    // Streaming JSON lines (unsegmented): --input file, or stdin
+    if cli.jsonl {
+        let stdout = io::stdout();
+        let writer = BufWriter::new(stdout.lock());
+        match cli.input_path {
+            Some(ref path) => converter.convert_jsonl_stream(BufReader::new(fs::File::open(path)?), writer)?,
+            None => converter.convert_jsonl_stream(io::stdin().lock(), writer)?,
+        };
+        return Ok(());
+    }
+    
+    // Initialize word segmenter if enabled
+    let mut segmenter: Option<WordSegmenter> = None;
+    if USE_WORD_SEGMENTATION {
+        // If using binary format, words are already loaded in converter's trie!
+        // We still need to create a WordSegmenter that uses the converter's trie
+        if loaded_binary {
+            status!("   💡 Word segmentation: Words already in TrieNode from binary format");
+            // Create an empty WordSegmenter - it will use converter's trie as phoneme fallback
+            // The segmentation will work because segment_from_segments() uses phoneme_root fallback
+            segmenter = Some(WordSegmenter::new());
+            // Don't load ja_words.txt - words are already in converter's trie
+        } else {
+            // Load separate word file for JSON mode
+            if std::path::Path::new("ja_words.txt").exists() {
+                let mut seg = WordSegmenter::new();
+                match seg.load_from_file("ja_words.txt") {
+                    Ok(_) => {
+                        status!("   💡 Word segmentation: ENABLED (spaces will separate words)");
+                        segmenter = Some(seg);
+                    }
+                    Err(e) => {
+                        eprintln!("⚠️  Warning: Could not load word dictionary: {}", e);
+                        eprintln!("   Continuing without word segmentation...");
+                    }
+                }
+            } else {
+                // No word list: the dictionary's own keys serve as words
+                let seg = converter.derive_word_list_from_dict();
+                status!("   💡 Word segmentation: ENABLED ({} words derived from dictionary, ja_words.txt not found)", seg.word_count);
+                segmenter = Some(seg);
+            }
+        }
+    }
+    
+    // Elongated words (ながーい) must match through their ー marks
+    if let Some(ref mut seg) = segmenter {
+        seg.skip_elongation_marks = converter.options.elongation.is_some();
+        seg.dedup_consecutive_words = cli.dedup_consecutive_words;
+        seg.kana_fast_path = cli.kana_fast_path;
+        seg.lattice_segmentation = cli.viterbi;
+        if let Some(max_word_len) = cli.max_word_len {
+            seg.set_max_word_len(max_word_len);
+        }
+    }
+    
+    // Pipeline mode: stream stdin line by line, one phoneme line out per line
+    // in (blank lines stay blank so the output lines up with the input)
+    if cli.stdin {
+        let stdout = io::stdout();
+        convert_lines(&converter, segmenter.as_ref(), io::stdin().lock(), BufWriter::new(stdout.lock()))?;
+        return Ok(());
+    }
+    
+    status!("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    
+    // Handle command-line arguments
+    if cli.texts.is_empty() && cli.input_path.is_none() {
+        // Interactive mode
+        println!("💡 Usage: ./jpn_to_phoneme \"日本語テキスト\"");
+        println!("   Or enter Japanese text interactively:\n");
+        
+        let stdin = io::stdin();
+        loop {
+            print!("Japanese text (or \"quit\" to exit): ");
+            io::stdout().flush()?;
+            
+            let mut input = String::new();
+            stdin.read_line(&mut input)?;
+            let input = input.trim();
+            
+            if input.is_empty() {
+                continue;
+            }
+            
+            if input.eq_ignore_ascii_case("quit") || input.eq_ignore_ascii_case("exit") {
+                println!("\n👋 Goodbye!");
+                break;
+            }
+            
+            // Perform conversion with timing
+            let start_time = Instant::now();
+            let result = if let Some(ref seg) = segmenter {
+                convert_detailed_with_segmentation(&converter, input, seg)
+            } else {
+                converter.convert_detailed(input)
+            };
+            let elapsed = start_time.elapsed();
+            
+            // Display results
+            println!("\n┌─────────────────────────────────────────");
+            println!("│ Input:    {}", input);
+            println!("│ Phonemes: {}", result.phonemes);
+            println!("│ Time:     {}μs", elapsed.as_micros());
+            println!("└─────────────────────────────────────────");
+            
+            // Show detailed matches
+            if !result.matches.is_empty() {
+                println!("\n  Matches ({}):", result.matches.len());
+                for m in &result.matches {
+                    println!("    • {}", m);
+                }
+            }
+            
+            if !result.unmatched.is_empty() {
+                print!("\n  ⚠️  Unmatched characters: ");
+                for (i, ch) in result.unmatched.iter().enumerate() {
+                    if i > 0 {
+                        print!(", ");
+                    }
+                    print!("{}", ch);
+                }
+                println!();
+                
+                let runs: Vec<String> = result.unmatched_runs.iter()
+                    .map(|(start, run)| format!("\"{}\" (pos: {})", run, start))
+                    .collect();
+                println!("  ⚠️  Unmatched runs: {}", runs.join(", "));
+            }
+            
+            let unreliable: Vec<String> = result.word_scores.iter()
+                .filter(|(_, score)| *score < 1.0)
+                .map(|(word, score)| format!("{} ({:.0}%)", word, score * 100.0))
+                .collect();
+            if !unreliable.is_empty() {
+                println!("  ⚠️  Partially converted words: {}", unreliable.join(", "));
+            }
+            
+            for warning in &result.warnings {
+                println!("  ⚠️  Furigana: {}", warning);
+            }
+            
+            println!();
+        }
+    } else {
+        // Batch mode - convert all arguments
+        if cli.tsv {
+            // Corpus output: blank lines and # comments produce no row
+            for text in &cli.texts {
+                let text = text.trim();
+                if text.is_empty() || text.starts_with('#') {
+                    continue;
+                }
+                
+                let phonemes = match segmenter {
+                    Some(ref seg) => convert_with_segmentation(&converter, text, seg),
+                    None => converter.convert(text),
+                };
+                println!("{}\t{}", escape_tsv(text), escape_tsv(&phonemes));
+            }
+            
+            return Ok(());
+        }
+        
+        if cli.bilingual {
+            // Annotator QA: a row per word, a blank line between inputs
+            let fallback = WordSegmenter::new();
+            let seg = segmenter.as_ref().unwrap_or(&fallback);
+            
+            for text in &cli.texts {
+                for (word, phonemes, romaji) in convert_bilingual(&converter, text, seg) {
+                    println!("{}\t{}\t{}", escape_tsv(&word), escape_tsv(&phonemes), escape_tsv(&romaji));
+                }
+                println!();
+            }
+            
+            return Ok(());
+        }
+        
+        if let Some(ref delimiter) = cli.show_segments {
+            // Segmentation review: segment even when no word list is loaded (phoneme trie fallback)
+            let fallback = WordSegmenter::new();
+            let seg = segmenter.as_ref().unwrap_or(&fallback);
+            
+            for text in &cli.texts {
+                println!("{}", show_segments(&converter, text, seg, delimiter));
+            }
+            
+            return Ok(());
+        }
+        
+        if cli.output_format != OutputFormat::Text {
+            // Formatted output always segments (phoneme trie fallback without a word list)
+            let fallback = WordSegmenter::new();
+            let seg = segmenter.as_ref().unwrap_or(&fallback);
+            
+            for text in &cli.texts {
+                println!("{}", convert_formatted(&converter, text, seg, cli.output_format));
+            }
+            
+            return Ok(());
+        }
+        
+        if let Some(mode) = cli.interleave {
+            // Review output: segment even when no word list is loaded (phoneme trie fallback)
+            let fallback = WordSegmenter::new();
+            let seg = segmenter.as_ref().unwrap_or(&fallback);
+            
+            for text in &cli.texts {
+                let lines = convert_aligned_lines(&converter, text, seg);
+                
+                match mode {
+                    InterleaveMode::PerWord => {
+                        for line in &lines {
+                            println!("{}", line);
+                        }
+                        println!();
+                    }
+                    InterleaveMode::PerLine => println!("{}", lines.join("\t")),
+                }
+            }
+            
+            return Ok(());
+        }
+        
+        for text in &cli.texts {
+            // Perform conversion with timing
+            let start_time = Instant::now();
+            let result = if let Some(ref seg) = segmenter {
+                convert_detailed_with_segmentation(&converter, text, seg)
+            } else {
+                converter.convert_detailed(text)
+            };
+            let elapsed = start_time.elapsed();
+            
+            // Display results
+            println!("┌─────────────────────────────────────────");
+            println!("│ Input:    {}", text);
+            println!("│ Phonemes: {}", result.phonemes);
+            println!("│ Time:     {}μs ({}ms)", elapsed.as_micros(), elapsed.as_millis());
+            println!("└─────────────────────────────────────────");
+            
+            // Show detailed matches
+            if !result.matches.is_empty() {
+                println!("\n  ✅ Matches ({}):", result.matches.len());
+                for m in &result.matches {
+                    println!("    • {}", m);
+                }
+            }
+            
+            if !result.unmatched.is_empty() {
+                print!("\n  ⚠️  Unmatched characters: ");
+                for (i, ch) in result.unmatched.iter().enumerate() {
+                    if i > 0 {
+                        print!(", ");
+                    }
+                    print!("{}", ch);
+                }
+                println!();
+                
+                let runs: Vec<String> = result.unmatched_runs.iter()
+                    .map(|(start, run)| format!("\"{}\" (pos: {})", run, start))
+                    .collect();
+                println!("  ⚠️  Unmatched runs: {}", runs.join(", "));
+            }
+            
+            let unreliable: Vec<String> = result.word_scores.iter()
+                .filter(|(_, score)| *score < 1.0)
+                .map(|(word, score)| format!("{} ({:.0}%)", word, score * 100.0))
+                .collect();
+            if !unreliable.is_empty() {
+                println!("  ⚠️  Partially converted words: {}", unreliable.join(", "));
+            }
+            
+            for warning in &result.warnings {
+                println!("  ⚠️  Furigana: {}", warning);
+            }
+            
+            println!();
+        }
+        
+        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+        println!("✨ Conversion complete!");
+    }
+    
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Converter over a handful of entries, without the dictionary files
+    fn converter(entries: &[(&str, &str)]) -> PhonemeConverter {
+        let mut converter = PhonemeConverter::new();
+        for &(text, phoneme) in entries {
+            converter.insert(text, phoneme);
+            converter.entry_count += 1;
+        }
+        converter
+    }
+    
+    /// Segmented conversion with an empty word list (phoneme trie fallback,
+    /// as with the binary trie)
+    fn segmented(converter: &PhonemeConverter, text: &str) -> String {
+        convert_with_segmentation(converter, text, &WordSegmenter::new())
+    }
+    
+    #[test]
+    fn empty_value_converts_to_nothing() {
+        let converter = converter(&[("あ", "a"), ("ゝ", ""), ("い", "i")]);
+        assert_eq!(converter.convert("あゝい"), "ai");
+        assert_eq!(converter.lookup("ゝ").map(|p| p.as_str()), Some(""));
+        assert_eq!(segmented(&converter, "あゝい"), "a i");
+    }
+    
+    #[test]
+    fn empty_value_survives_json_parsing() {
+        let entries = parse_flat_json(r#"{"ゝ": "", "あ": "a"}"#).unwrap();
+        assert_eq!(entries.get("ゝ").map(|p| p.as_str()), Some(""));
+        assert_eq!(entries.get("あ").map(|p| p.as_str()), Some("a"));
+    }
+    
+    #[test]
+    fn wo_particle_is_not_swallowed_by_a_classical_key() {
+        let mut converter = converter(&[("ゲーム", "geːmɯ"), ("を", "o"), ("し", "ɕi"), ("ます", "masɯ")]);
+        converter.insert_word_marker("をし");
+        assert_eq!(segmented(&converter, "ゲームをします"), "geːmɯ o ɕi masɯ");
+    }
+    
+    /// Binary trie bytes (japanese.trie format) for the given entries
+    fn trie_bytes(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut bytes = b"JPHO".to_vec();
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for &(key, value) in entries {
+            write_varint(&mut bytes, key.len() as u32).unwrap();
+            bytes.extend_from_slice(key.as_bytes());
+            write_varint(&mut bytes, value.len() as u32).unwrap();
+            bytes.extend_from_slice(value.as_bytes());
+        }
+        bytes
+    }
+    
+    #[test]
+    fn word_marker_is_terminal_without_phonemes() {
+        let mut converter = converter(&[("日本", "nihoɴ"), ("語", "go")]);
+        converter.insert_word_marker("日本語");
+        
+        let chars: Vec<char> = "日本語".chars().collect();
+        assert_eq!(longest_terminal_match(converter.get_root(), &chars, 0, false, false, usize::MAX), 3);
+        assert_eq!(longest_phoneme_match(converter.get_root(), &chars, 0).map(|(length, _)| length), Some(2));
+        assert_eq!(converter.lookup("日本語"), None);
+        assert_eq!(converter.convert("日本語"), "nihoɴgo");
+    }
+    
+    #[test]
+    fn binary_empty_value_loads_as_word_marker() {
+        let mut converter = PhonemeConverter::new();
+        converter.load_binary_from_reader(&trie_bytes(&[("日本", "nihoɴ"), ("語", "go"), ("日本語", "")])[..]).unwrap();
+        
+        assert_eq!(converter.entry_count, 3);
+        assert_eq!(converter.lookup("日本語"), None);
+        assert_eq!(converter.convert("日本語"), "nihoɴgo");
+        // One word for the segmenter, converted by the shorter keys inside it
+        assert_eq!(segmented(&converter, "日本語"), "nihoɴgo");
+    }
+    
+    #[test]
+    fn currency_prefix_reads_after_the_number() {
+        assert_eq!(expand_numbers("¥1000"), "せんえん");
+        assert_eq!(expand_numbers("￥2500"), "にせんごひゃくえん");
+        assert_eq!(expand_numbers("$5"), "ごドル");
+        assert_eq!(expand_numbers("1000円"), "せん円");
+    }
+    
+    #[test]
+    fn currency_needs_number_expansion() {
+        let mut converter = converter(&[("せん", "seɴ"), ("えん", "eɴ"), ("円", "eɴ")]);
+        assert_eq!(converter.convert("¥1000"), "¥1000");
+        
+        converter.options.number_mode = NumberMode::Cardinal;
+        assert_eq!(converter.convert("¥1000"), "seɴeɴ");
+        assert_eq!(converter.convert("1000円"), "seɴeɴ");
+    }
+    
+    #[test]
+    fn keys_with_prefix_collects_terminal_descendants() {
+        let mut converter = converter(&[("東", "higaɕi"), ("東京", "toːkjoː"), ("東京都", "toːkjoːto"), ("東北", "toːhokɯ"), ("西", "niɕi")]);
+        converter.insert_word_marker("東京駅");
+        
+        assert_eq!(converter.keys_with_prefix("東京"), vec!["東京", "東京都", "東京駅"]);
+        assert_eq!(converter.keys_with_prefix("東"), vec!["東", "東京", "東京都", "東京駅", "東北"]);
+        assert!(converter.keys_with_prefix("南").is_empty());
+        assert_eq!(converter.keys_with_prefix("").len(), 6);
+    }
+    
+    #[test]
+    fn respect_input_spaces_keeps_upstream_boundaries() {
+        let converter = converter(&[("お 願い", "onegai"), ("お", "o"), ("願い", "negai"), ("日本", "nihoɴ"), ("語学", "gogakɯ")]);
+        let mut segmenter = WordSegmenter::new();
+        assert_eq!(convert_with_segmentation(&converter, "お 願い", &segmenter), "onegai");
+        assert_eq!(convert_with_segmentation(&converter, "日本 語学", &segmenter), "nihoɴ gogakɯ");
+        
+        segmenter.respect_input_spaces = true;
+        assert_eq!(convert_with_segmentation(&converter, "お 願い", &segmenter), "o negai");
+        assert_eq!(convert_with_segmentation(&converter, "日本 語学", &segmenter), "nihoɴ gogakɯ");
+    }
+    
+    /// Word tokens of segmented conversion, as text
+    fn words(converter: &PhonemeConverter, text: &str, segmenter: &WordSegmenter) -> Vec<String> {
+        segment_prepared(converter, text, segmenter).into_iter().map(|token| token.text).collect()
+    }
+    
+    #[test]
+    fn honorific_prefix_attaches_to_the_next_word() {
+        let converter = converter(&[("お", "o"), ("ご", "go"), ("水", "mizɯ"), ("飯", "haɴ"), ("を", "o"), ("おいしい", "oiɕiː")]);
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word("水");
+        segmenter.insert_word("飯");
+        
+        assert_eq!(words(&converter, "お水をください", &segmenter)[0], "お水");
+        assert_eq!(words(&converter, "ご飯", &segmenter), vec!["ご飯"]);
+        // No longer match than the plain word: おいしい stays one word
+        assert_eq!(words(&converter, "おいしい", &segmenter), vec!["おいしい"]);
+        
+        segmenter.attach_honorific_prefixes = false;
+        assert_eq!(words(&converter, "ご飯", &segmenter), vec!["ご", "飯"]);
+    }
+    
+    #[test]
+    fn tie_bars_join_affricates() {
+        let mut converter = converter(&[("ち", "ʨi"), ("つ", "ʦɯ"), ("じ", "ʥi"), ("か", "ka")]);
+        assert_eq!(converter.convert("ちつ"), "ʨiʦɯ");
+        
+        converter.options.add_tie_bars = true;
+        assert_eq!(converter.convert("ちつ"), "t͡ɕit͡sɯ");
+        assert_eq!(converter.convert("じか"), "d͡ʑika");
+        assert_eq!(converter.convert("か"), "ka");
+    }
+    
+    #[test]
+    fn stray_furigana_brackets_are_reported() {
+        let warnings = check_furigana_brackets("漢字「かんじ」と「ひらがな");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].issue, BracketIssue::UnmatchedOpen);
+        assert_eq!(warnings[0].position, "漢字「かんじ」と".len());
+        
+        let warnings = check_furigana_brackets("漢字」です");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].issue, BracketIssue::UnmatchedClose);
+        assert_eq!(warnings[0].position, "漢字".len());
+        
+        assert!(check_furigana_brackets("漢字「かんじ」\\「").is_empty());
+    }
+    
+    #[test]
+    fn bracket_warnings_come_with_the_conversion() {
+        let converter = converter(&[("か", "ka")]);
+        let result = convert_detailed_with_segmentation(&converter, "」か「", &WordSegmenter::new());
+        let issues: Vec<BracketIssue> = result.warnings.iter().map(|w| w.issue).collect();
+        assert_eq!(issues, vec![BracketIssue::UnmatchedClose, BracketIssue::UnmatchedOpen]);
+        assert!(converter.convert_detailed("か").warnings.is_empty());
+    }
+    
+    #[test]
+    fn max_word_len_caps_every_walk() {
+        let long_word = "字".repeat(100);
+        let mut converter = converter(&[("字", "ʥi")]);
+        converter.insert(&long_word, &"ʥi".repeat(100));
+        let capped = |segmenter: &WordSegmenter| {
+            let words = words(&converter, &long_word, segmenter);
+            assert_eq!(words.concat(), long_word);
+            words.iter().all(|word| word.chars().count() <= 8)
+        };
+        
+        // Phoneme dictionary fallback (no word list): no cap by default
+        let mut segmenter = WordSegmenter::new();
+        assert_eq!(words(&converter, &long_word, &segmenter), vec![long_word.clone()]);
+        segmenter.set_max_word_len(8);
+        assert!(capped(&segmenter));
+        segmenter.lattice_segmentation = true;
+        assert!(capped(&segmenter));
+        
+        // Word list and honorific prefix + word
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word(&long_word);
+        assert_eq!(segmenter.longest_word, 100);
+        segmenter.set_max_word_len(8);
+        assert!(capped(&segmenter));
+        
+        // Honorific prefix + word stays within the cap too
+        let text: Vec<char> = format!("お{}", "字".repeat(8)).chars().collect();
+        converter.insert(&"字".repeat(8), &"ʥi".repeat(8));
+        let mut segmenter = WordSegmenter::new();
+        assert_eq!(segmenter.match_honorific(&text, 0, Some(converter.get_root())), 9);
+        segmenter.set_max_word_len(8);
+        assert_eq!(segmenter.match_honorific(&text, 0, Some(converter.get_root())), 2);
+    }
+    
+    #[test]
+    fn max_word_len_flag_needs_a_positive_number() {
+        let args = |arg: &str| vec![arg.to_string()];
+        assert_eq!(parse_cli_args(&args("--max-word-len=12")).unwrap().max_word_len, Some(12));
+        assert!(parse_cli_args(&args("--max-word-len=0")).is_err());
+        assert!(parse_cli_args(&args("--max-word-len=x")).is_err());
+    }
+    
+    #[test]
+    fn sentences_split_a_paragraph() {
+        let paragraph = "今日は晴れ。明日は雨！本当？\n  \nはい";
+        assert_eq!(sentences(paragraph).collect::<Vec<_>>(), vec!["今日は晴れ。", "明日は雨！", "本当？", "はい"]);
+    }
+    
+    #[test]
+    fn sentences_keep_closing_quotes_and_punctuation_runs() {
+        assert_eq!(sentences("「行くよ。」次。").collect::<Vec<_>>(), vec!["「行くよ。」次。"]);
+        assert_eq!(sentences("彼は「本当？」と聞いた。").collect::<Vec<_>>(), vec!["彼は「本当？」と聞いた。"]);
+        assert_eq!(sentences("やった！？」そう。").collect::<Vec<_>>(), vec!["やった！？」", "そう。"]);
+        assert_eq!(sentences("終わり。』次").collect::<Vec<_>>(), vec!["終わり。』", "次"]);
+        assert!(is_sentence_end('？') && is_closing_quote('」') && !is_closing_quote('「'));
+    }
+    
+    #[test]
+    fn classical_kana_read_as_modern() {
+        let mut converter = converter(&[("い", "i"), ("る", "ɾɯ"), ("え", "e"), ("く", "kɯ"), ("ゎ", "ɰa"), ("し", "ɕi")]);
+        assert_eq!(converter.convert("ゐる"), "ゐɾɯ");
+        
+        converter.options.classical_kana = true;
+        assert_eq!(converter.convert("ゐる"), "iɾɯ");
+        assert_eq!(converter.convert("ゑ"), "e");
+        assert_eq!(converter.convert("くゎし"), "kʷaɕi");
+        assert_eq!(modernize_kana("ヰヱゟ"), "イエより");
+    }
+    
+    #[test]
+    fn doubled_vowels_merge_within_a_word() {
+        let mut converter = converter(&[("お", "o"), ("き", "ki"), ("い", "i"), ("か", "ka"), ("この", "kono"), ("おか", "oka"), ("湖", "mizɯɯmi")]);
+        converter.options.merge_long_vowels = true;
+        assert_eq!(converter.convert("おおきい"), "oːkiː");
+        assert_eq!(merge_long_vowels("aaa"), "aːa");
+        
+        // Segmented: この | おか is a word boundary, not a long vowel
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word("この");
+        segmenter.insert_word("おか");
+        assert_eq!(convert_with_segmentation(&converter, "このおか", &segmenter), "kono oka");
+        
+        // Exceptions keep separate morphemes apart
+        assert_eq!(converter.convert("湖"), "mizɯɯmi");
+        converter.options.long_vowel_exceptions.push("おおきい".to_string());
+        assert_eq!(converter.convert("おおきい"), "ookii");
+    }
+    
+    #[test]
+    fn convert_cow_borrows_unchanged_input() {
+        let converter = converter(&[("日本", "nihoɴ")]);
+        assert!(matches!(converter.convert_cow("plain ASCII, no match!"), Cow::Borrowed("plain ASCII, no match!")));
+        assert!(matches!(converter.convert_cow("日本"), Cow::Owned(ref p) if p == "nihoɴ"));
+        
+        // Unkeyed ー and unknown-kanji placeholders rewrite unmatched text
+        assert!(matches!(converter.convert_cow("ー"), Cow::Owned(_)));
+        let mut converter = converter;
+        converter.options.unknown_kanji_phoneme = Some("?".to_string());
+        assert!(matches!(converter.convert_cow("漢"), Cow::Owned(ref p) if p == "?"));
+        assert!(matches!(converter.convert_cow("ASCII"), Cow::Borrowed("ASCII")));
+    }
+    
+    #[test]
+    fn convert_cow_matches_convert_under_every_option() {
+        let base = converter(&[("日本", "nihoɴ"), ("ち", "ʨi"), ("こう", "koː"), ("が", "ga"), ("っ", "ʔ"), ("あ", "a")]);
+        let inputs = ["日本 ABC", "がっこう", "ちあ", "abc def", "漢", "ASCII 123"];
+        let options: Vec<fn(&mut ConversionOptions)> = vec![
+            |o| o.merge_long_vowels = true,
+            |o| o.add_tie_bars = true,
+            |o| o.explicit_space_token = Some("<pause>".to_string()),
+            |o| o.ascii_case = AsciiCase::Upper,
+            |o| o.unknown_kanji_phoneme = Some("?".to_string()),
+            |o| o.geminate_style = GeminateStyle::Doubled,
+            |o| o.long_vowel_style = LongVowelStyle::Macron,
+            |o| o.phoneme_inventory = PhonemeInventory::Simplified,
+            |o| o.reverse_morae = true,
+            |o| o.number_mode = NumberMode::Cardinal,
+        ];
+        
+        let mut converter = base;
+        for set in options {
+            converter.options = ConversionOptions::default();
+            set(&mut converter.options);
+            for input in &inputs {
+                assert_eq!(converter.convert_cow(input), converter.convert(input), "{}", input);
+            }
+        }
+    }
+    
+    #[test]
+    fn context_reading_of_nani() {
+        let mut converter = converter(&[("何", "nani"), ("時", "toki"), ("色", "iɾo"), ("と", "to"), ("何故", "naze"), ("本", "hoɴ")]);
+        assert_eq!(converter.convert("何時"), "nanitoki");
+        
+        converter.options.context_readings = true;
+        assert_eq!(converter.convert("何時"), "naɴʥi");
+        assert_eq!(converter.convert("何本"), "naɴboɴ");
+        assert_eq!(converter.convert("何色"), "naniiɾo");
+        assert_eq!(converter.convert("何と"), "naɴto");
+        assert_eq!(converter.convert("何故"), "naze");
+        assert_eq!(converter.convert("何"), "nani");
+    }
+    
+    #[test]
+    fn casual_elongation_collapses_or_keeps_length() {
+        assert_eq!(normalize_elongation("ながーーーい", ElongationMode::Collapse), "ながーい");
+        assert_eq!(normalize_elongation("ながあああい", ElongationMode::Proportional), "ながーーーい");
+        assert_eq!(normalize_elongation("おかあさん", ElongationMode::Collapse), "おかあさん");
+        assert_eq!(normalize_elongation("あ゛ー", ElongationMode::Collapse), "あー");
+        assert_eq!(normalize_elongation("か゛", ElongationMode::Collapse), "が");
+        
+        let mut converter = converter(&[("な", "na"), ("が", "ga"), ("い", "i"), ("す", "sɯ"), ("ご", "go"), ("ながい", "nagai")]);
+        converter.options.elongation = Some(ElongationMode::Collapse);
+        assert_eq!(converter.convert("すごーーい"), "sɯgoːi");
+        assert_eq!(converter.convert("ながーい"), "nagaːi");
+        
+        // Segmented: the word matches through its ー marks
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word("ながい");
+        segmenter.skip_elongation_marks = true;
+        assert_eq!(words(&converter, "ながーい", &segmenter), vec!["ながーい"]);
+        
+        converter.options.elongation = Some(ElongationMode::Proportional);
+        assert_eq!(converter.convert("すごーーい"), "sɯgoːːi");
+    }
+    
+    /// Path for a scratch file in the system temp dir, unique per test
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("jpn_to_phoneme_{}_{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+    
+    #[test]
+    fn json_export_round_trips_escapes() {
+        let mut converter = converter(&[("東京", "toːkjoː"), ("\"引用\"", "iɴ\\joː"), ("改\n行", "kai\tgjoː"), ("ゝ", "")]);
+        converter.insert_word_marker("東京都");
+        let path = temp_path("export.json");
+        converter.save_to_json(&path).unwrap();
+        
+        let mut reloaded = PhonemeConverter::new();
+        reloaded.load_from_json(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        
+        assert_eq!(reloaded.entries(), converter.entries());
+        assert_eq!(reloaded.lookup("\"引用\"").map(|p| p.as_str()), Some("iɴ\\joː"));
+        assert_eq!(reloaded.lookup("東京都"), None);
+    }
+    
+    #[test]
+    fn inline_ipa_override_is_used_verbatim() {
+        let mut converter = converter(&[("東京", "toɯkjoɯ"), ("に", "ni"), ("行く", "ikɯ")]);
+        assert!(segmented(&converter, "東京/toːkʲoː/に行く").starts_with("toɯkjoɯ"));
+        
+        converter.options.ipa_override_delimiter = Some('/');
+        assert_eq!(segmented(&converter, "東京/toːkʲoː/に行く"), "toːkʲoː ni ikɯ");
+        let result = convert_detailed_with_segmentation(&converter, "東京/toːkʲoː/に", &WordSegmenter::new());
+        assert_eq!((result.matches[0].original.as_str(), result.matches[0].phoneme.as_str()), ("東京", "toːkʲoː"));
+        
+        converter.options.ipa_override_delimiter = Some('#');
+        assert_eq!(segmented(&converter, "東京#tokyo#に"), "tokyo ni");
+    }
+    
+    #[test]
+    fn colloquial_contractions_follow_speech() {
+        assert_eq!(contract_colloquial("食べている"), "食べてる");
+        assert_eq!(contract_colloquial("猫という動物"), "猫っていう動物");
+        assert_eq!(contract_colloquial("読んでしまった"), "読んじゃった");
+        assert_eq!(contract_colloquial("居る"), "居る");
+        
+        let mut converter = converter(&[("食べ", "tabe"), ("て", "te"), ("る", "ɾɯ"), ("い", "i"), ("っ", "ʔ"), ("う", "ɯ")]);
+        assert_eq!(converter.convert("食べている"), "tabeteiɾɯ");
+        converter.options.colloquial_contractions = true;
+        assert_eq!(converter.convert("食べている"), "tabeteɾɯ");
+        assert_eq!(converter.convert("という"), "tːeiɯ");
+    }
+    
+    #[test]
+    fn quality_score_counts_covered_chars() {
+        let converter = converter(&[("日本", "nihoɴ"), ("語", "go")]);
+        assert_eq!(converter.quality_score("日本語"), 1.0);
+        assert_eq!(converter.quality_score("日本、語。"), 1.0);
+        assert_eq!(converter.quality_score("日本xy"), 0.5);
+        assert_eq!(converter.quality_score("日本 x"), 2.0 / 3.0);
+        assert_eq!(converter.quality_score("xyz"), 0.0);
+        assert_eq!(converter.quality_score(""), 1.0);
+        assert_eq!(converter.quality_score("、 。"), 1.0);
+    }
+    
+    #[test]
+    fn sokuon_doubles_handakuten_onsets() {
+        let converter = converter(&[("い", "i"), ("ぱ", "pa"), ("ら", "ɾa"), ("ぷ", "pɯ"), ("ひ", "çi"), ("っ", "ʔ")]);
+        assert_eq!(converter.convert("いっぱい"), "ipːai");
+        assert_eq!(converter.convert("らっぱ"), "ɾapːa");
+        assert_eq!(converter.convert("いっぷ"), "ipːɯ");
+        assert_eq!(converter.convert("いっひ"), "içːi");
+        assert_eq!(converter.convert("あっ"), "あʔ");
+    }
+    
+    #[test]
+    fn furigana_match_keeps_the_base_kanji() {
+        let converter = converter(&[("けんた", "kẽ̞ɴta"), ("健", "keɴ"), ("は", "ha")]);
+        let result = convert_detailed_with_segmentation(&converter, "健太「けんた」は", &WordSegmenter::new());
+        
+        assert_eq!(result.phonemes, "kẽ̞ɴta wa");
+        assert_eq!(result.matches[0].original, "健太");
+        assert_eq!(result.matches[0].phoneme, "kẽ̞ɴta");
+        assert_eq!(result.word_scores[0], ("健太".to_string(), 1.0));
+    }
+    
+    #[test]
+    fn mora_timing_tags_each_mora_kind() {
+        let converter = converter(&[("が", "ga"), ("っ", "ʔ"), ("こ", "ko"), ("う", "ɯ"), ("ん", "ɴ"), ("ー", "ː")]);
+        let kinds = |text: &str| -> Vec<(String, MoraKind)> {
+            converter.convert_mora_timing(text).into_iter().map(|mora| (mora.phonemes, mora.kind)).collect()
+        };
+        
+        assert_eq!(kinds("がっこう"), vec![
+            ("ga".to_string(), MoraKind::Light),
+            ("k".to_string(), MoraKind::Sokuon),
+            ("ko".to_string(), MoraKind::Light),
+            ("ɯ".to_string(), MoraKind::LongVowel),
+        ]);
+        assert_eq!(kinds("こん").last().map(|mora| mora.1), Some(MoraKind::Nasal));
+        assert_eq!(kinds("こー").last().map(|mora| mora.1), Some(MoraKind::LongVowel));
+        assert_eq!(kinds("がっ").last().map(|mora| mora.1), Some(MoraKind::Sokuon));
+    }
+    
+    #[test]
+    fn zero_width_space_is_stripped_so_the_word_matches() {
+        let mut converter = converter(&[("日本", "nihoɴ")]);
+        let text = "日\u{200B}本";
+        
+        assert!(!converter.convert_detailed(text).unmatched.is_empty());
+        
+        converter.options.strip_control_chars = true;
+        let result = converter.convert_detailed(text);
+        assert_eq!(result.phonemes, "nihoɴ");
+        assert!(result.unmatched.is_empty());
+        assert_eq!(converter.convert("日\u{FEFF}本\u{0007}"), "nihoɴ");
+    }
+    
+    #[test]
+    fn unmatched_chars_are_grouped_into_runs() {
+        let converter = converter(&[("日本", "nihoɴ"), ("語", "go")]);
+        let result = converter.convert_detailed("日本XYZ語Q");
+        
+        assert_eq!(result.unmatched, vec!['X', 'Y', 'Z', 'Q']);
+        assert_eq!(result.unmatched_runs, vec![(6, "XYZ".to_string()), (12, "Q".to_string())]);
+        assert_eq!(&"日本XYZ語Q"[6..9], "XYZ");
+    }
+    
+    #[test]
+    fn digit_by_digit_reads_each_digit() {
+        assert_eq!(expand_digits("0120"), "ぜろ いち に ぜろ");
+        assert_eq!(expand_digits("No.12-3"), "No.いち に-さん");
+        
+        let mut converter = converter(&[("ぜろ", "zeɾo"), ("いち", "itɕi"), ("に", "ni"), ("ひゃく", "çakɯ"), ("にじゅう", "nidʑɯː")]);
+        converter.options.number_mode = NumberMode::DigitByDigit;
+        assert_eq!(converter.convert("0120"), "zeɾo itɕi ni zeɾo");
+        
+        converter.options.number_mode = NumberMode::Cardinal;
+        assert_eq!(converter.convert("120"), "çakɯnidʑɯː");
+    }
+    
+    #[test]
+    fn annotated_phonemes_carry_contiguous_mora_indices() {
+        let converter = converter(&[("が", "ga"), ("っ", "ʔ"), ("こ", "ko"), ("う", "ɯ")]);
+        let annotated = converter.convert_annotated("がっこう");
+        
+        let indices: Vec<usize> = annotated.iter().map(|(mora, _)| *mora).collect();
+        assert_eq!(indices.first(), Some(&0));
+        assert!(indices.windows(2).all(|pair| pair[1] == pair[0] || pair[1] == pair[0] + 1));
+        assert_eq!(indices.last(), Some(&(converter.convert_mora_timing("がっこう").len() - 1)));
+        
+        let phonemes: String = annotated.iter().map(|(_, phoneme)| phoneme.as_str()).collect();
+        assert_eq!(phonemes, converter.convert("がっこう"));
+        assert_eq!(annotated[0], (0, "g".to_string()));
+        assert_eq!(annotated[1], (0, "a".to_string()));
+        assert_eq!(annotated[2], (1, "kː".to_string()));
+    }
+    
+    #[test]
+    fn derived_word_list_segments_like_a_word_file() {
+        let converter = converter(&[("私", "wataɕi"), ("は", "wa"), ("学生", "gakɯseː"), ("です", "desɯ")]);
+        let segmenter = converter.derive_word_list_from_dict();
+        
+        assert!(segmenter.contains_word("学生"));
+        assert!(!segmenter.contains_word("は"));
+        assert_eq!(words(&converter, "私は学生です", &segmenter), vec!["私", "は", "学生", "です"]);
+        assert_eq!(convert_with_segmentation(&converter, "私は学生です", &segmenter), "wataɕi wa gakɯseː desɯ");
+    }
+    
+    #[test]
+    fn converting_before_loading_is_caught() {
+        let empty = PhonemeConverter::new();
+        assert!(!empty.is_loaded());
+        assert_eq!(empty.convert("日本"), "日本");
+        assert!(empty.try_convert("日本").is_err());
+        
+        let loaded = converter(&[("日本", "nihoɴ")]);
+        assert!(loaded.is_loaded());
+        assert_eq!(loaded.try_convert("日本").unwrap(), "nihoɴ");
+    }
+    
+    #[test]
+    fn word_scores_flag_partially_unknown_words() {
+        let converter = converter(&[("東京", "toːkjoː"), ("は", "ha"), ("大", "dai")]);
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word("東京");
+        segmenter.insert_word("大X");
+        let result = convert_detailed_with_segmentation(&converter, "東京は大X", &segmenter);
+        
+        assert_eq!(result.word_scores, vec![
+            ("東京".to_string(), 1.0),
+            ("は".to_string(), 1.0),
+            ("大X".to_string(), 0.5),
+        ]);
+    }
+    
+    #[test]
+    fn loanword_final_vowel_policy() {
+        let never = |_: &str| false;
+        let known = |word: &str| word == "コンピューター";
+        
+        assert_eq!(normalize_loanword_vowels("コンピューター", LoanwordVowelPolicy::Short, never), "コンピュータ");
+        assert_eq!(normalize_loanword_vowels("サーバーです", LoanwordVowelPolicy::Short, never), "サーバです");
+        assert_eq!(normalize_loanword_vowels("キー", LoanwordVowelPolicy::Short, never), "キー");
+        assert_eq!(normalize_loanword_vowels("コンピュータ", LoanwordVowelPolicy::Long, known), "コンピューター");
+        assert_eq!(normalize_loanword_vowels("サーバ", LoanwordVowelPolicy::Long, known), "サーバ");
+        
+        let mut converter = converter(&[("コンピューター", "kompjɯːtaː"), ("コンピュータ", "kompjɯːta")]);
+        assert_eq!(converter.convert("コンピュータ"), "kompjɯːta");
+        converter.options.loanword_final_vowel = LoanwordVowelPolicy::Long;
+        assert_eq!(converter.convert("コンピュータ"), "kompjɯːtaː");
+        converter.options.loanword_final_vowel = LoanwordVowelPolicy::Short;
+        assert_eq!(converter.convert("コンピューター"), "kompjɯːta");
+    }
+    
+    #[test]
+    fn katakana_sokuon_before_a_vowel_is_a_glottal_stop() {
+        let converter = converter(&[("ア", "a"), ("ッ", "ʔ"), ("ー", "ː"), ("ハ", "ha"), ("ト", "to")]);
+        assert_eq!(converter.convert("ッア"), "ʔa");
+        assert_eq!(converter.convert("アッー"), "aʔ");
+        assert_eq!(converter.convert("ッッア"), "ʔa");
+        assert_eq!(converter.convert("ハット"), "hatːo");
+        assert_eq!(collapse_katakana_sokuon("アッーア"), "アッア");
+    }
+    
+    #[test]
+    fn ssml_output_escapes_text_and_attributes() {
+        assert_eq!(escape_xml("<a & \"b\">'"), "&lt;a &amp; &quot;b&quot;&gt;&apos;");
+        
+        let converter = converter(&[("私", "ɰᵝatai"), ("R&D", "a<ɾ>do")]);
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word("私");
+        segmenter.insert_word("R&D");
+        let ssml = convert_formatted(&converter, "私<R&D", &segmenter, OutputFormat::Ssml);
+        
+        assert_eq!(ssml, "<speak><phoneme alphabet=\"ipa\" ph=\"ɰᵝatai\">私</phoneme>&lt;\
+                          <phoneme alphabet=\"ipa\" ph=\"a&lt;ɾ&gt;do\">R&amp;D</phoneme></speak>");
+    }
+    
+    #[test]
+    fn numeric_patterns_read_their_operators() {
+        assert_eq!(read_numeric_operators("50%"), "50パーセント");
+        assert_eq!(read_numeric_operators("5〜10"), "5から10");
+        assert_eq!(read_numeric_operators("3:2"), "3たい2");
+        assert_eq!(read_numeric_operators("10:"), "10:");
+        assert_eq!(read_numeric_operators("〜10"), "〜10");
+        
+        let mut converter = converter(&[("ご", "go"), ("ごじゅう", "godʑɯː"), ("じゅう", "dʑɯː"), ("パーセント", "paːseɴto"), ("から", "kaɾa")]);
+        converter.options.number_mode = NumberMode::Cardinal;
+        converter.options.numeric_patterns = true;
+        assert_eq!(converter.convert("50%"), "godʑɯːpaːseɴto");
+        assert_eq!(converter.convert("5〜10"), "gokaɾadʑɯː");
+    }
+    
+    #[test]
+    fn input_spaces_become_the_pause_token() {
+        let mut converter = converter(&[("はい", "hai"), ("そう", "soɯ")]);
+        converter.options.explicit_space_token = Some("<sp>".to_string());
+        
+        assert_eq!(converter.convert("はい  そう"), "hai <sp> soɯ");
+        assert_eq!(segmented(&converter, "はい そう"), "hai <sp> soɯ");
+        assert_eq!(segmented(&converter, "はいそう"), "hai soɯ");
+        
+        converter.options.explicit_space_token = None;
+        assert_eq!(converter.convert("はい  そう"), "hai  soɯ");
+    }
+    
+    #[test]
+    fn reduplicated_onomatopoeia_stay_one_word() {
+        let converter = converter(&[("わ", "ɰᵝa"), ("ん", "ɴ"), ("き", "ki"), ("ら", "ɾa"), ("が", "ga"), ("犬", "inɯ")]);
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word("犬");
+        segmenter.insert_word("がわ");
+        
+        assert_eq!(words(&converter, "犬がわんわん", &segmenter), vec!["犬", "が", "わんわん"]);
+        assert_eq!(words(&converter, "きらきら", &segmenter), vec!["きらきら"]);
+        assert_eq!(convert_with_segmentation(&converter, "きらきら", &segmenter), "kiɾakiɾa");
+        assert_eq!(convert_with_segmentation(&converter, "わんわん", &segmenter), "ɰᵝaɴɰᵝaɴ");
+        
+        segmenter.group_reduplication = false;
+        assert_ne!(words(&converter, "犬がわんわん", &segmenter), vec!["犬", "が", "わんわん"]);
+    }
+    
+    #[test]
+    fn unmatched_ascii_case_is_normalized() {
+        let mut converter = converter(&[("の", "no"), ("画面", "gameɴ")]);
+        assert_eq!(converter.convert("iPhoneの画面"), "iPhonenogameɴ");
+        
+        converter.options.ascii_case = AsciiCase::Lower;
+        assert_eq!(converter.convert("iPhoneの画面"), "iphonenogameɴ");
+        
+        converter.options.ascii_case = AsciiCase::Upper;
+        assert_eq!(converter.convert("iPhoneの画面"), "IPHONEnogameɴ");
+    }
+    
+    #[test]
+    fn spans_alternate_between_convertible_and_untouched_text() {
+        let converter = converter(&[("日本", "nihoɴ"), ("は", "ha"), ("です", "desɯ")]);
+        let spans = converter.spans("日本はABCです");
+        
+        assert_eq!(spans, vec![
+            Span { range: 0..9, convertible: true, phonemes: Some("nihoɴha".to_string()) },
+            Span { range: 9..12, convertible: false, phonemes: None },
+            Span { range: 12..18, convertible: true, phonemes: Some("desɯ".to_string()) },
+        ]);
+        assert!(converter.spans("").is_empty());
+    }
+    
+    #[test]
+    fn list_markers_are_dropped_or_read() {
+        assert_eq!(read_list_markers("・りんご\n・みかん"), "りんご\nみかん");
+        assert_eq!(read_list_markers("１）りんご\n2. みかん"), "いち、りんご\nに、みかん");
+        assert_eq!(read_list_markers("①りんご"), "いち、りんご");
+        assert_eq!(read_list_markers("2.5キロ"), "2.5キロ");
+        assert_eq!(read_list_markers("ジョン・スミス"), "ジョン・スミス");
+        
+        let mut converter = converter(&[("りんご", "ɾiŋgo"), ("いち", "itɕi"), ("に", "ni")]);
+        converter.options.list_markers = true;
+        assert_eq!(converter.convert("・りんご"), "ɾiŋgo");
+        assert_eq!(converter.convert("1)りんご\n2)りんご"), "itɕi、ɾiŋgo\nni、ɾiŋgo");
+    }
+    
+    #[test]
+    fn reversed_morae_keep_marks_on_their_base() {
+        assert_eq!(reverse_morae("toː"), "oto");
+        assert_eq!(reverse_morae("ɾiɴgo、mikaɴ"), "ɴkami、goɴɾi");
+        assert_eq!(reverse_morae("kʲat͡ɕi"), "t͡ɕikʲa");
+        assert_eq!(reverse_morae("ke\u{0303}ɴta"), "taɴke\u{0303}");
+        assert_eq!(reverse_morae("ka\u{0325}ɕi"), "ɕika\u{0325}");
+        
+        let mut converter = converter(&[("し", "ɕi"), ("ん", "ɴ"), ("ぶ", "bɯ")]);
+        converter.options.reverse_morae = true;
+        assert_eq!(converter.convert("しんぶん"), "ɴbɯɴɕi");
+    }
+    
+    #[test]
+    fn trace_records_each_decision() {
+        let converter = converter(&[("ざ", "za"), ("し", "ɕi"), ("日", "hi"), ("日本", "nihoɴ")]);
+        let (phonemes, events) = converter.convert_traced("日本ざっしX");
+        
+        assert_eq!(phonemes, converter.convert("日本ざっしX"));
+        assert_eq!(events[0], TraceEvent::Candidates { pos: 0, matches: vec![(1, "hi".to_string()), (2, "nihoɴ".to_string())] });
+        assert!(events.contains(&TraceEvent::Longest { pos: 0, original: "日本".to_string(), phonemes: "nihoɴ".to_string(), variant: false }));
+        assert!(events.contains(&TraceEvent::Sokuon { pos: 3, original: "っし".to_string(), phonemes: "ɕːi".to_string() }));
+        assert_eq!(events.last(), Some(&TraceEvent::Unmatched { pos: 5, ch: 'X' }));
+        assert!(!events.iter().any(|event| matches!(event, TraceEvent::Prepared { .. } | TraceEvent::Finished { .. })));
+    }
+    
+    #[test]
+    fn small_wa_reads_as_a_labialized_onset() {
+        let mut converter = converter(&[("く", "kɯ"), ("ぐ", "gɯ"), ("し", "ɕi"), ("ゎ", "ɰᵝa"), ("ん", "ɴ")]);
+        assert_eq!(converter.convert("くゎし"), "kɯɰᵝaɕi");
+        
+        converter.options.classical_kana = true;
+        assert_eq!(converter.convert("くゎし"), "kʷaɕi");
+        assert_eq!(converter.convert("ぐゎん"), "gʷaɴ");
+        assert_eq!(converter.convert("クヮ"), "kʷa");
+    }
+    
+    #[test]
+    fn repeated_words_collapse_when_dedup_is_on() {
+        let converter = converter(&[("これ", "koɾe"), ("は", "ha"), ("ペン", "peɴ")]);
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word("これ");
+        segmenter.insert_word("ペン");
+        
+        assert_eq!(words(&converter, "これ これ は", &segmenter), vec!["これ", "これ", "は"]);
+        
+        segmenter.dedup_consecutive_words = true;
+        assert_eq!(words(&converter, "これ これ は", &segmenter), vec!["これ", "は"]);
+        assert_eq!(words(&converter, "これはこれ", &segmenter), vec!["これ", "は", "これ"]);
+        assert_eq!(convert_with_segmentation(&converter, "これ これはペン", &segmenter), "koɾe wa peɴ");
+    }
+    
+    #[test]
+    fn simplified_inventory_collapses_nasals() {
+        let mut converter = converter(&[("しんぶん", "ɕimbɯɴ"), ("ぎんこう", "giŋkoɯ"), ("を", "o"), ("よむ", "jomɯ")]);
+        let full = converter.convert("しんぶんをよむ");
+        assert_eq!(full, "ɕimbɯɴojomɯ");
+        
+        converter.options.phoneme_inventory = PhonemeInventory::Simplified;
+        assert_eq!(converter.convert("しんぶんをよむ"), "shimbuNoyomu");
+        assert_eq!(converter.convert("ぎんこう"), "giNkou");
+        assert_eq!(simplify_phonemes("ʨaɰᵝaɲ"), "chawan");
+    }
+    
+    #[test]
+    fn spaced_keys_match_any_whitespace_run() {
+        let converter = converter(&[("お  願い ", "onegai"), ("お", "o"), ("し", "ɕi")]);
+        
+        assert_eq!(converter.lookup("お 願い").map(|p| p.as_str()), Some("onegai"));
+        assert_eq!(converter.convert("お 願いし"), "onegaiɕi");
+        assert_eq!(converter.convert("お\t　願い"), "onegai");
+        assert_eq!(converter.convert("お願い"), "o願い");
+    }
+    
+    #[test]
+    fn show_segments_marks_word_boundaries() {
+        let converter = converter(&[("私", "ɰᵝatai"), ("は", "ha"), ("リンゴ", "ɾiŋgo"), ("が", "ga"), ("すき", "sɯki"), ("です", "desɯ")]);
+        let mut segmenter = WordSegmenter::new();
+        for word in ["私", "リンゴ", "すき", "です"] {
+            segmenter.insert_word(word);
+        }
+        
+        assert_eq!(show_segments(&converter, "私はリンゴがすきです", &segmenter, "|"), "私|は|リンゴ|が|すき|です");
+        assert_eq!(show_segments(&converter, "健太「けんた」はすき", &segmenter, " / "), "健太「けんた」 / は / すき");
+        
+        let args = |arg: &str| vec![arg.to_string()];
+        assert_eq!(parse_cli_args(&args("--show-segments")).unwrap().show_segments.as_deref(), Some("|"));
+        assert_eq!(parse_cli_args(&args("--show-segments=/")).unwrap().show_segments.as_deref(), Some("/"));
+    }
+    
+    #[test]
+    fn removing_an_entry_prunes_its_nodes_and_keeps_siblings() {
+        let mut converter = converter(&[("東", "higaɕi"), ("東京", "toːkjoː"), ("東京都", "toːkjoːto"), ("東北大", "toːhokɯdai")]);
+        
+        assert!(converter.remove_entry("東北大"));
+        assert!(!converter.remove_entry("東北大"));
+        assert!(!converter.remove_entry("東北"));
+        assert_eq!(converter.entry_count, 3);
+        assert!(converter.root.child_mut('東').unwrap().child_mut('北').is_none());
+        
+        assert!(converter.remove_entry("東京"));
+        assert_eq!(converter.lookup("東京"), None);
+        assert_eq!(converter.convert("東京都"), "toːkjoːto");
+        assert_eq!(converter.convert("東京"), "higaɕi京");
+        assert_eq!(converter.keys_with_prefix(""), vec!["東", "東京都"]);
+    }
+    
+    #[test]
+    fn bilingual_rows_pair_ipa_with_romaji() {
+        let converter = converter(&[("私", "ɰᵝataɕi"), ("は", "ha"), ("学校", "gakːoː"), ("へ", "he"), ("行く", "ikɯ")]);
+        let mut segmenter = WordSegmenter::new();
+        for word in ["私", "学校", "行く"] {
+            segmenter.insert_word(word);
+        }
+        
+        let row = |word: &str, ipa: &str, romaji: &str| (word.to_string(), ipa.to_string(), romaji.to_string());
+        assert_eq!(convert_bilingual(&converter, "私は学校へ行く", &segmenter), vec![
+            row("私", "ɰᵝataɕi", "watashi"),
+            row("は", "wa", "wa"),
+            row("学校", "gakːoː", "gakkō"),
+            row("へ", "e", "e"),
+            row("行く", "ikɯ", "iku"),
+        ]);
+        assert_eq!(simplified_to_romaji("kaNi"), "kan'i");
+    }
+    
+    #[test]
+    fn vertical_punctuation_reads_as_standard() {
+        assert_eq!(normalize_presentation_punctuation("はい\u{FE11}そう\u{FE19}"), "はい、そう…");
+        assert_eq!(normalize_presentation_punctuation("\u{FE41}はい\u{FE42}\u{FE45}"), "「はい」");
+        
+        let converter = converter(&[("はい", "hai"), ("そう", "soɯ")]);
+        assert_eq!(converter.convert("はい\u{FE11}そう…"), converter.convert("はい、そう…"));
+        assert_eq!(segmented(&converter, "はい\u{FE11}そう\u{FE12}"), segmented(&converter, "はい、そう。"));
+    }
+    
+    #[test]
+    fn longest_unmatched_run_separates_scattered_from_clustered() {
+        let converter = converter(&[("日本", "nihoɴ"), ("は", "ha"), ("です", "desɯ")]);
+        
+        assert_eq!(converter.longest_unmatched_run("日本はです"), 0);
+        assert_eq!(converter.longest_unmatched_run("X日本Yは Zです"), 1);
+        assert_eq!(converter.longest_unmatched_run("日本はhttps://x.jpです"), 8);
+        assert_eq!(converter.longest_unmatched_run("日本はab、cdです"), 4);
+        assert_eq!(converter.convert_detailed("日本XYZ").longest_unmatched_run(), 3);
+    }
+    
+    #[test]
+    fn substitutions_map_old_kanji_to_dictionary_forms() {
+        let mut converter = converter(&[("国語", "kokɯgo"), ("くに", "kɯni")]);
+        assert_eq!(converter.convert("國語"), "國語");
+        
+        let path = temp_path("substitutions.json");
+        fs::write(&path, "{\"國\": \"国\", \"學\": \"学\"}").unwrap();
+        converter.load_substitutions(&path).unwrap();
+        assert_eq!(converter.convert("國語"), "kokɯgo");
+        assert_eq!(segmented(&converter, "國「くに」"), "kɯni");
+        
+        fs::write(&path, "{\"國語\": \"国語\"}").unwrap();
+        assert!(converter.load_substitutions(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+    
+    #[test]
+    fn choonpu_after_youon_lengthens_the_small_kana_vowel() {
+        let converter = converter(&[("し", "ɕi"), ("き", "ki"), ("ゅ", "jɯ"), ("ょ", "jo"), ("ー", "ː"), ("ちゃ", "ʨa")]);
+        assert_eq!(converter.convert("しゅー"), "ɕɯː");
+        assert_eq!(converter.convert("きょー"), "kjoː");
+        assert_eq!(converter.convert("ちゃー"), "ʨaː");
+        assert_eq!(converter.convert("しゅ"), "ɕɯ");
+    }
+    
+    #[test]
+    fn jsonl_stream_writes_one_object_per_line() {
+        let converter = converter(&[("私", "ɰᵝatai"), ("は", "ha")]);
+        let input: &[u8] = b"\xe7\xa7\x81\xe3\x81\xafxyz\n\n\xff\xfe\r\n\"\xe3\x81\xaf\"\r\n";
+        let mut output = Vec::new();
+        
+        assert_eq!(converter.convert_jsonl_stream(input, &mut output).unwrap(), 3);
+        let lines: Vec<String> = String::from_utf8(output).unwrap().lines().map(String::from).collect();
+        assert_eq!(lines[0], "{\"line\": 1, \"input\": \"私はxyz\", \"phonemes\": \"ɰᵝataihaxyz\", \"coverage\": 0.4000, \
+                              \"unmatched_runs\": [{\"position\": 6, \"text\": \"xyz\"}]}");
+        assert!(lines[1].starts_with("{\"line\": 3, \"error\": \""));
+        assert_eq!(lines[2], "{\"line\": 4, \"input\": \"\\\"は\\\"\", \"phonemes\": \"\\\"ha\\\"\", \"coverage\": 1.0000, \
+                              \"unmatched_runs\": [{\"position\": 0, \"text\": \"\\\"\"}, {\"position\": 4, \"text\": \"\\\"\"}]}");
+    }
+    
+    #[test]
+    fn convert_all_caps_and_orders_alternatives() {
+        let mut converter = converter(&[("今日", "kʲoː"), ("は", "ha"), ("東京", "toːkʲoː"), ("へ", "he"), ("行く", "ikɯ")]);
+        converter.add_reading("今日", "konniʨi");
+        let mut segmenter = WordSegmenter::new();
+        for word in ["今日", "東京", "行く"] {
+            segmenter.insert_word(word);
+        }
+        
+        let text = "今日は東京へ行く";
+        assert_eq!(convert_all(&converter, text, &segmenter, 4), vec![
+            "kʲoː wa toːkʲoː e ikɯ",
+            "konniʨi wa toːkʲoː e ikɯ",
+            "kʲoː ha toːkʲoː e ikɯ",
+            "kʲoː wa toːkʲoː he ikɯ",
+        ]);
+        assert_eq!(convert_all(&converter, text, &segmenter, 4)[0], convert_with_segmentation(&converter, text, &segmenter));
+        
+        let every = convert_all(&converter, text, &segmenter, 100);
+        assert_eq!(every.len(), 8);
+        assert_eq!(every[7], "konniʨi ha toːkʲoː he ikɯ");
+        assert!(convert_all(&converter, text, &segmenter, 0).is_empty());
+    }
+    
+    #[test]
+    fn pipe_annotations_read_like_furigana() {
+        let mut converter = converter(&[("日本語", "nihoŋgo"), ("に", "ni"), ("ほ", "ho"), ("ん", "ɴ"), ("ご", "go"), ("を", "o"), ("日", "hi")]);
+        assert!(segmented(&converter, "日本語|にほ").starts_with("nihoŋgo"));
+        
+        converter.options.reading_delimiter = Some('|');
+        assert_eq!(segmented(&converter, "日本語|にほ を"), segmented(&converter, "日本語「にほ」 を"));
+        assert_eq!(segmented(&converter, "日本語|にほ を"), "niho o");
+        assert!(segmented(&converter, "日本語|").ends_with('|'));
+        
+        converter.options.reading_delimiter = Some('｜');
+        assert_eq!(segmented(&converter, "日本語｜にほ"), "niho");
+    }
+    
+    #[test]
+    fn homophones_differ_only_in_pitch_accent() {
+        let mut converter = converter(&[("橋", "haɕiꜜ"), ("箸", "haꜜɕi"), ("端", "haɕi"), ("雨", "ame"), ("飴", "ame"), ("おばさん", "obasaɴ"), ("おばあさん", "obaːsaɴ")]);
+        assert!(converter.are_homophones("橋", "箸"));
+        assert!(converter.are_homophones("橋", "端"));
+        assert!(converter.are_homophones("雨", "飴"));
+        assert!(!converter.are_homophones("おばさん", "おばあさん"));
+        
+        converter.options.phoneme_comparison.ignore_accent = false;
+        assert!(!converter.are_homophones("橋", "箸"));
+        assert!(converter.are_homophones("雨", "飴"));
+        
+        converter.options.phoneme_comparison.ignore_length = true;
+        assert!(converter.are_homophones("おばさん", "おばあさん"));
+        
+        let pitch_blind = PhonemeComparison::default();
+        assert_eq!(pitch_blind.normalize("haꜜɕi"), pitch_blind.normalize("haɕiꜜ"));
+        assert_eq!(pitch_blind.normalize("ha ɕi"), "haɕi");
+    }
+    
+    #[test]
+    fn wo_reads_as_o_or_wo_per_option() {
+        let mut converter = converter(&[("本", "hoɴ"), ("を", "o"), ("ヲ", "o"), ("ヲタク", "otakɯ"), ("読む", "jomɯ")]);
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word("本");
+        segmenter.insert_word("読む");
+        
+        assert_eq!(convert_with_segmentation(&converter, "本を読む", &segmenter), "hoɴ o jomɯ");
+        assert_eq!(converter.convert("ヲタク"), "otakɯ");
+        assert_eq!(converter.convert("ヲ"), "o");
+        
+        converter.options.wo_reading = WoReading::Wo;
+        assert_eq!(convert_with_segmentation(&converter, "本を読む", &segmenter), "hoɴ ɰᵝo jomɯ");
+        assert_eq!(converter.convert("ヲ"), "ɰᵝo");
+        assert_eq!(converter.convert("ヲタク"), "otakɯ");
+    }
+    
+    #[test]
+    fn word_case_applies_to_each_word() {
+        let mut converter = converter(&[("私", "ɰᵝatai"), ("は", "ha"), ("アップル", "appɯɾɯ")]);
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word("私");
+        segmenter.insert_word("アップル");
+        let text = "私はアップルiPhone";
+        
+        assert_eq!(convert_with_segmentation(&converter, text, &segmenter), "ɰᵝatai wa appɯɾɯ iPhone");
+        
+        converter.options.word_case = WordCase::UpperInitial;
+        assert_eq!(convert_with_segmentation(&converter, text, &segmenter), "ɰᵝatai Wa Appɯɾɯ IPhone");
+        
+        converter.options.word_case = WordCase::Lower;
+        assert_eq!(convert_with_segmentation(&converter, text, &segmenter), "ɰᵝatai wa appɯɾɯ iphone");
+    }
+    
+    #[test]
+    fn registry_converts_with_the_named_dictionary() {
+        let mut registry = ConverterRegistry::new();
+        assert!(registry.insert("general", converter(&[("頭", "atama")])).is_none());
+        registry.insert("medical", converter(&[("頭", "toɯ")]));
+        
+        assert_eq!(registry.names(), vec!["general", "medical"]);
+        assert_eq!(registry.convert("general", "頭"), Some("atama".to_string()));
+        assert_eq!(registry.convert("medical", "頭"), Some("toɯ".to_string()));
+        assert_eq!(registry.convert("legal", "頭"), None);
+        
+        let shared = registry.get_shared("medical").unwrap();
+        let replaced = registry.insert("medical", converter(&[("頭", "zɯ")])).unwrap();
+        assert!(Arc::ptr_eq(&shared, &replaced));
+        assert_eq!(shared.convert("頭"), "toɯ");
+        assert_eq!(registry.get("medical").map(|c| c.convert("頭")), Some("zɯ".to_string()));
+        
+        registry.insert_shared("default", shared);
+        assert!(registry.remove("general").is_some());
+        assert_eq!(registry.names(), vec!["default", "medical"]);
+        assert!(registry.load("legal", &temp_path("missing.json")).is_err());
+        
+        // Binary or JSON whatever the extension
+        let trie_path = temp_path("registry_dict.bin");
+        fs::write(&trie_path, trie_bytes(&[("頭", "kaɕiɾa")])).unwrap();
+        registry.load("binary", &trie_path).unwrap();
+        assert_eq!(registry.convert("binary", "頭"), Some("kaɕiɾa".to_string()));
+        registry.load("json", "tests/fixtures/small_dict.json").unwrap();
+        assert!(registry.get("json").unwrap().is_loaded());
+        fs::remove_file(&trie_path).unwrap();
+    }
+    
+    #[test]
+    fn nasal_before_choonpu_is_held_only_with_long_vowels() {
+        let mut converter = converter(&[("ふ", "ɸɯ"), ("ん", "ɴ"), ("う", "ɯ")]);
+        assert_eq!(converter.convert("ふんー"), "ɸɯɴー");
+        assert_eq!(converter.convert("うーん"), "ɯːɴ");
+        assert_eq!(converter.convert_detailed("ふんー").unmatched, vec!['ー']);
+        
+        converter.options.merge_long_vowels = true;
+        assert_eq!(converter.convert("ふんー"), "ɸɯɴː");
+        assert_eq!(converter.convert("うーん"), "ɯːɴ");
+        assert_eq!(converter.convert_detailed("ふんー").phonemes, "ɸɯɴː");
+        
+        converter.options.merge_long_vowels = false;
+        converter.options.elongation = Some(ElongationMode::Proportional);
+        assert_eq!(converter.convert("ふんー"), "ɸɯɴː");
+    }
+    
+    #[test]
+    fn unknown_kanji_become_the_placeholder() {
+        let mut converter = converter(&[("日本", "nihoɴ"), ("の", "no")]);
+        assert_eq!(converter.convert("日本の鬱、X"), "nihoɴno鬱、X");
+        
+        converter.options.unknown_kanji_phoneme = Some("<unk>".to_string());
+        assert_eq!(converter.convert("日本の鬱、X"), "nihoɴno<unk>、X");
+        assert_eq!(converter.convert("鬱々"), "<unk>々");
+        assert_eq!(converter.convert_detailed("鬱").unmatched, vec!['鬱']);
+    }
+    
+    #[test]
+    fn max_match_depth_reports_the_longest_key() {
+        let converter = converter(&[("東", "higaɕi"), ("東京", "toːkjoː"), ("東京特許許可局", "toːkjoːtokkjokjokakjokɯ"), ("は", "ha")]);
+        
+        assert_eq!(converter.max_match_depth("東京は東京特許許可局"), Some((7, "東京特許許可局".to_string())));
+        assert_eq!(converter.max_match_depth("東京は東"), Some((2, "東京".to_string())));
+        assert_eq!(converter.max_match_depth("XYZ"), None);
+        
+        let result = converter.convert_detailed("は東京");
+        let (depth, deepest) = result.deepest_match().unwrap();
+        assert_eq!((depth, deepest.start_index), (2, 3));
+    }
+    
+    #[test]
+    fn thousands_separators_stay_in_the_number() {
+        assert_eq!(expand_numbers("1,000,000"), expand_numbers("1000000"));
+        assert_eq!(expand_numbers("1,000"), "せん");
+        assert_eq!(expand_numbers("１，０００円"), "せん円");
+        assert_eq!(expand_numbers("1,2"), "いち,に");
+        
+        let mut converter = converter(&[("せん", "seɴ"), ("ひゃくまん", "çakɯmaɴ")]);
+        converter.options.number_mode = NumberMode::Cardinal;
+        assert_eq!(converter.convert("1,000,000"), "çakɯmaɴ");
+    }
+    
+    #[test]
+    fn presegmented_words_keep_their_boundaries() {
+        let converter = converter(&[("私", "ɰᵝatai"), ("は", "ha"), ("東京", "toːkjoː"), ("へ", "he"), ("東京へ", "toːkjoːhe"), ("行く", "ikɯ")]);
+        
+        assert_eq!(converter.convert_presegmented(&["私", "は", "東京", "へ", "行く"]), "ɰᵝatai wa toːkjoː e ikɯ");
+        assert_eq!(converter.convert_presegmented(&["東京へ", " ", "", "行く"]), "toːkjoːhe ikɯ");
+        assert!(converter.convert_presegmented(&[]).is_empty());
+    }
+    
+    #[test]
+    fn mfa_output_separates_phones_words_and_pauses() {
+        let pair = |word: &str, phonemes: &str| (word.to_string(), phonemes.to_string());
+        assert_eq!(format_mfa(vec![pair("私", "ɰᵝatai"), pair("は", "wa"), pair("、", "、"), pair("リンゴ", "ɾiɴgo"), pair("。", "。")]),
+                   "ɰᵝ a t a i | w a | sp | ɾ i ɴ g o");
+        assert_eq!(format_mfa(vec![pair("、", "、"), pair("鬱", "鬱"), pair(" ", "<sp>"), pair(" ", "<sp>"), pair("toːkʲoː", "toːkʲoː")]),
+                   "spn | sp | spn");
+        
+        let converter = converter(&[("私", "ɰᵝatai"), ("は", "ha"), ("学校", "gakːoː")]);
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word("私");
+        segmenter.insert_word("学校");
+        assert_eq!(convert_formatted(&converter, "私は、学校。", &segmenter, OutputFormat::Mfa), "ɰᵝ a t a i | w a | sp | g a kː oː");
+    }
+    
+    #[test]
+    fn escaped_markers_stay_literal() {
+        let mut converter = converter(&[("健太", "keɴta"), ("けんた", "keɴta"), ("日本", "nihoɴ"), ("に", "ni"), ("ほ", "ho")]);
+        converter.options.reading_delimiter = Some('|');
+        converter.options.ipa_override_delimiter = Some('/');
+        
+        assert_eq!(segmented(&converter, "健太「けんた」"), "keɴta");
+        assert_eq!(segmented(&converter, "健太\\「けんた\\」").matches('「').count(), 1);
+        assert!(segmented(&converter, "健太\\「けんた\\」").contains('」'));
+        assert_eq!(segmented(&converter, "日本|にほ"), "niho");
+        assert!(segmented(&converter, "日本\\|にほ").contains('|'));
+        assert_eq!(segmented(&converter, "日本/x/"), "x");
+        assert_eq!(segmented(&converter, "日本\\/x/").matches('/').count(), 2);
+        assert!(segmented(&converter, "C:\\Users").contains("C:\\Users"));
+        assert!(check_furigana_brackets("健太\\「けんた").is_empty());
+    }
+    
+    #[test]
+    fn corpus_lines_parse_into_cases() {
+        let corpus = "# header\n\n私は\tɰᵝatai wa\n  \nがっこう\tgakːoɯ\t\n";
+        assert_eq!(parse_corpus(corpus), Ok(vec![(3, "私は", "ɰᵝatai wa"), (5, "がっこう", "gakːoɯ\t")]));
+        assert_eq!(parse_corpus("私は\tɰᵝatai wa\n# ok\nno tab here\n"), Err(3));
+        assert_eq!(parse_corpus(""), Ok(vec![]));
+    }
+    
+    #[test]
+    fn boundary_adjuster_merges_two_tokens() {
+        let converter = converter(&[("東京", "toːkjoː"), ("都", "to"), ("東京都", "toːkjoːto"), ("に", "ni"), ("住む", "sɯmɯ")]);
+        let mut segmenter = WordSegmenter::new();
+        for word in ["東京", "都", "住む"] {
+            segmenter.insert_word(word);
+        }
+        let text = "東京都に住む";
+        assert_eq!(words(&converter, text, &segmenter), vec!["東京", "都", "に", "住む"]);
+        
+        segmenter.set_boundary_adjuster(|words: Vec<String>| {
+            let mut merged: Vec<String> = Vec::with_capacity(words.len());
+            for word in words {
+                match merged.last_mut() {
+                    Some(last) if last == "東京" && word == "都" => last.push_str(&word),
+                    _ => merged.push(word),
+                }
+            }
+            merged
+        });
+        assert_eq!(words(&converter, text, &segmenter), vec!["東京都", "に", "住む"]);
+        assert_eq!(convert_with_segmentation(&converter, text, &segmenter), "toːkjoːto ni sɯmɯ");
+        assert_eq!(words(&converter, "都に住む", &segmenter), vec!["都", "に", "住む"]);
+    }
+    
+    #[test]
+    fn ideographic_space_is_a_boundary_and_optionally_ascii() {
+        let mut converter = converter(&[("はい", "hai"), ("そう", "soɯ"), ("はいそう", "haisoɯ")]);
+        let segmenter = WordSegmenter::new();
+        
+        assert_eq!(words(&converter, "はい\u{3000}そう", &segmenter), vec!["はい", "そう"]);
+        assert_eq!(converter.convert("はい\u{3000}そう"), "hai\u{3000}soɯ");
+        
+        converter.options.normalize_ideographic_space = true;
+        assert_eq!(converter.convert("はい\u{3000}そう"), "hai soɯ");
+        assert_eq!(convert_with_segmentation(&converter, "はい\u{3000}そう", &segmenter), "hai soɯ");
+        
+        converter.options.explicit_space_token = Some("<sp>".to_string());
+        assert_eq!(converter.convert("はい\u{3000}そう"), "hai <sp> soɯ");
+    }
+    
+    #[test]
+    fn config_echo_round_trips_the_options() {
+        let mut conv = converter(&[("日本", "nihoɴ")]);
+        conv.options.add_tie_bars = true;
+        conv.options.unknown_kanji_phoneme = Some("?".to_string());
+        
+        let (result, config) = conv.convert_with_config_echo("日本");
+        assert_eq!(result.phonemes, conv.convert("日本"));
+        assert_eq!(config.entry_count, conv.config().entry_count);
+        assert_eq!(config.variant, None);
+        assert_eq!(config.substitution_count, 0);
+        
+        let json = config.to_json();
+        assert!(json.contains("\"add_tie_bars\": true"), "{}", json);
+        assert!(json.contains("\"unknown_kanji_phoneme\": \"?\""), "{}", json);
+        assert!(json.contains("\"elongation\": null"), "{}", json);
+        assert!(json.contains(&format!("\"entry_count\": {}", config.entry_count)), "{}", json);
+        
+        conv.options.add_tie_bars = false;
+        assert!(conv.config().to_json().contains("\"add_tie_bars\": false"));
+    }
+    
+    #[test]
+    fn scientific_notation_reads_exponents_and_subscripts() {
+        assert_eq!(read_scientific_notation("H₂O"), "HツーO");
+        assert_eq!(read_scientific_notation("2²"), "2のに乗");
+        assert_eq!(read_scientific_notation("10^-3"), "10のマイナスさん乗");
+        assert_eq!(read_scientific_notation("^_^"), "^_^");
+        assert_eq!(read_scientific_notation("₂"), "₂");
+        
+        let mut converter = converter(&[("に", "ɲi"), ("の", "no"), ("乗", "dʑoː"), ("ツー", "tsɯː")]);
+        converter.options.number_mode = NumberMode::Cardinal;
+        assert_eq!(converter.longest_unmatched_run("2²"), 1);
+        
+        converter.options.scientific_notation = true;
+        assert_eq!(converter.convert("2²"), "ɲinoɲidʑoː");
+        assert_eq!(converter.convert("H₂O"), "HtsɯːO");
+    }
+    
+    #[test]
+    fn length_styles_render_geminates_and_long_vowels() {
+        let mut converter = converter(&[("が", "ga"), ("っ", "ʔ"), ("こう", "koː"), ("コ", "ko"), ("ー", "ː"), ("ヒ", "çi")]);
+        assert_eq!(converter.convert("がっこう"), "gakːoː");
+        assert_eq!(converter.convert("コーヒー"), "koːçiː");
+        
+        converter.options.geminate_style = GeminateStyle::Doubled;
+        converter.options.long_vowel_style = LongVowelStyle::Doubled;
+        assert_eq!(converter.convert("がっこう"), "gakkoo");
+        assert_eq!(converter.convert("コーヒー"), "kooçii");
+        
+        converter.options.geminate_style = GeminateStyle::Length;
+        converter.options.long_vowel_style = LongVowelStyle::Macron;
+        assert_eq!(converter.convert("がっこう"), "gakːō");
+        assert_eq!(converter.convert("コーヒー"), "kōçī");
+        assert_eq!(apply_length_styles("tʨːa", GeminateStyle::Doubled, LongVowelStyle::Length), "ttʨa");
+    }
+    
+    #[test]
+    fn convert_with_reading_takes_kana_from_readings_and_furigana() {
+        let mut converter = converter(&[("日本語", "nihoŋgo"), ("にほんご", "nihoŋgo"), ("を", "o"), ("漢字", "kaɴʥi"), ("かんじ", "kaɴʥi")]);
+        assert_eq!(converter.convert_with_reading("日本語を"), ("nihoŋgoo".to_string(), "日本語を".to_string()));
+        
+        converter.add_kana_reading("日本語", "にほんご");
+        assert_eq!(converter.convert_with_reading("日本語を"), ("nihoŋgoo".to_string(), "にほんごを".to_string()));
+        assert_eq!(converter.convert_with_reading("漢字「かんじ」"), ("kaɴʥi".to_string(), "かんじ".to_string()));
+        
+        let path = temp_path("kana_readings.json");
+        fs::write(&path, r#"{"漢字": "かんじ"}"#).unwrap();
+        converter.load_kana_readings(&path).unwrap();
+        assert_eq!(converter.convert_with_reading("漢字を").1, "かんじを");
+        fs::remove_file(&path).unwrap();
+    }
+    
+    #[test]
+    fn options_and_segmenter_settings_are_settable() {
+        let mut converter = converter(&[("が", "ga"), ("っ", "ʔ"), ("こう", "koː")]);
+        converter.options_mut().long_vowel_style = LongVowelStyle::Doubled;
+        assert_eq!(converter.convert("がっこう"), "gakːoo");
+        
+        let options = ConversionOptions { geminate_style: GeminateStyle::Doubled, ..ConversionOptions::default() };
+        converter.set_options(options);
+        assert_eq!(converter.options().long_vowel_style, LongVowelStyle::Length);
+        assert_eq!(converter.convert("がっこう"), "gakkoː");
+        
+        let mut segmenter = WordSegmenter::new();
+        segmenter.set_dedup_consecutive_words(true);
+        segmenter.set_lattice_segmentation(true);
+        segmenter.set_group_reduplication(false);
+        assert!(segmenter.dedup_consecutive_words && segmenter.lattice_segmentation && !segmenter.group_reduplication);
+        
+        let result = convert_detailed_with_segmentation(&converter, "「がっこう", &segmenter);
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].issue, BracketIssue::UnmatchedOpen);
+    }
+    
+    #[test]
+    fn standalone_particles_take_their_particle_reading() {
+        let converter = converter(&[("学校", "gakːoː"), ("へ", "he"), ("行く", "ikɯ"), ("へや", "heja"), ("本", "hoɴ"), ("を", "ɰᵝo"), ("読む", "jomɯ")]);
+        let mut segmenter = WordSegmenter::new();
+        for word in ["学校", "行く", "へや", "本", "読む"] {
+            segmenter.insert_word(word);
+        }
+        
+        assert_eq!(convert_with_segmentation(&converter, "学校へ行く", &segmenter), "gakːoː e ikɯ");
+        assert_eq!(convert_with_segmentation(&converter, "本を読む", &segmenter), "hoɴ o jomɯ");
+        assert_eq!(convert_with_segmentation(&converter, "へやへ行く", &segmenter), "heja e ikɯ");
+        
+        let result = convert_detailed_with_segmentation(&converter, "学校へ行く", &segmenter);
+        assert_eq!(result.phonemes, "gakːoː e ikɯ");
+        assert_eq!(converter.particle_reading("へや"), None);
+    }
+    
+    #[test]
+    fn json_parsing_decodes_escapes_and_keeps_literals() {
+        let entries = parse_flat_json(r#"{"a\tb": "x\ny", "\u65e5": "\u0251", "\ud83d\ude00": "\ud83d\ude00", "n": 1.5, "t": true, "z": null}"#).unwrap();
+        assert_eq!(entries.get("a\tb").map(|p| p.as_str()), Some("x\ny"));
+        assert_eq!(entries.get("日").map(|p| p.as_str()), Some("ɑ"));
+        assert_eq!(entries.get("😀").map(|p| p.as_str()), Some("😀"));
+        assert_eq!(entries.get("n").map(|p| p.as_str()), Some("1.5"));
+        assert_eq!(entries.get("t").map(|p| p.as_str()), Some("true"));
+        assert!(!entries.contains_key("z"));
+        
+        for bad in [r#"{"a": "\q"}"#, r#"{"a": "\ud83d"}"#, r#"{"a": {"b": "c"}}"#, r#"{"a": "b"} x"#, r#"{"a": "b""#] {
+            assert!(matches!(parse_flat_json(bad), Err(ConverterError::JsonParse { .. })), "{}", bad);
+        }
+    }
+    
+    #[test]
+    fn unkeyed_long_mark_lengthens_the_previous_vowel() {
+        let mut converter = converter(&[("ラ", "ɾa"), ("メ", "me"), ("ン", "ɴ"), ("コ", "ko"), ("ヒ", "çi"), ("お", "o"), ("ね", "ne"), ("え", "e"), ("さ", "sa"), ("ん", "ɴ")]);
+        assert_eq!(converter.convert("ラーメン"), "ɾaːmeɴ");
+        assert_eq!(converter.convert("コーヒー"), "koːçiː");
+        assert_eq!(converter.convert("ーメ"), "ーme");
+        assert_eq!(converter.convert("aー"), "aー");
+        assert_eq!(converter.convert("おねえさん"), "oneesaɴ");
+        
+        let matches = converter.convert_detailed("ラーメン").matches;
+        assert_eq!((matches[1].original.as_str(), matches[1].phoneme.as_str(), matches[1].start_index), ("ー", "ː", 3));
+        
+        converter.options.merge_long_vowels = true;
+        assert_eq!(converter.convert("おねえさん"), "oneːsaɴ");
+        converter.options.long_vowel_style = LongVowelStyle::Doubled;
+        assert_eq!(converter.convert("コーヒー"), "kooçii");
+        assert_eq!(converter.convert("おねえさん"), "oneesaɴ");
+    }
+    
+    #[test]
+    fn sokuon_doubles_the_next_onset_without_a_dictionary_entry() {
+        let converter = converter(&[("が", "ga"), ("こう", "koː"), ("ま", "ma"), ("て", "te"), ("き", "ki"), ("あ", "a")]);
+        assert_eq!(converter.convert("がっこう"), "gakːoː");
+        assert_eq!(converter.convert("まって"), "matːe");
+        assert_eq!(converter.convert("きって"), "kitːe");
+        assert_eq!(converter.convert("まっ"), "maʔ");
+        assert_eq!(converter.convert("あっあ"), "aʔa");
+        
+        let matches = converter.convert_detailed("まって").matches;
+        assert_eq!((matches[1].original.as_str(), matches[1].phoneme.as_str()), ("って", "tːe"));
+    }
+    
+    #[test]
+    fn romaji_follows_hepburn_for_length_sokuon_and_n() {
+        let path = temp_path("romaji.json");
+        fs::write(&path, r#"{"が": "ga", "こ": "ko", "う": "u", "し": "shi", "ん": "n", "ぶ": "bu", "き": "ki", "え": "e", "ま": "ma", "ちゃ": "cha", "コ": "ko", "ヒ": "hi"}"#).unwrap();
+        let mut romaji = RomajiConverter::new();
+        romaji.load_from_json(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        
+        assert_eq!(romaji.convert("がっこう"), "gakkō");
+        assert_eq!(romaji.convert("コーヒー"), "kōhī");
+        assert_eq!(romaji.convert("まっちゃ"), "matcha");
+        assert_eq!(romaji.convert("しんぶん"), "shinbun");
+        assert_eq!(romaji.convert("きんえん"), "kin'en");
+        
+        romaji.long_vowels = RomajiLongVowels::Doubled;
+        romaji.labial_m = true;
+        assert_eq!(romaji.convert("がっこう"), "gakkoo");
+        assert_eq!(romaji.convert("しんぶん"), "shimbun");
+    }
+    
+    #[test]
+    fn grammar_runs_stop_at_phoneme_dictionary_matches() {
+        let converter = converter(&[("お腹", "onaka"), ("すいた", "sɯita"), ("ご", "go"), ("る", "ɾɯ")]);
+        let segmenter = WordSegmenter::new();
+        
+        assert_eq!(words(&converter, "？お腹すいた", &segmenter), vec!["？", "お腹", "すいた"]);
+        assert_eq!(words(&converter, "ごーる", &segmenter), vec!["ごーる"]);
+    }
+    
+    #[test]
+    fn lattice_segmentation_finds_fewer_unmatched_chars() {
+        let mut segmenter = WordSegmenter::new();
+        segmenter.insert_word("東京");
+        segmenter.insert_word("京都府");
+        
+        assert!(segmenter.contains_word("京都府"));
+        assert!(!segmenter.contains_word("京都"));
+        assert_eq!(segmenter.segment("東京都府"), vec!["東京", "都府"]);
+        assert_eq!(segmenter.segment_viterbi("東京都府"), vec!["東", "京都府"]);
+        
+        segmenter.set_lattice_segmentation(true);
+        assert_eq!(segmenter.segment("東京都府"), vec!["東", "京都府"]);
+    }
+    
+    #[test]
+    fn folded_katakana_matches_hiragana_keys() {
+        assert_eq!(normalize_kana("カタカナ"), "かたかな");
+        assert_eq!(normalize_kana("ジャーナル"), "じゃーなる");
+        assert_eq!(normalize_kana("ヴァッ"), "ゔぁっ");
+        assert_eq!(normalize_kana("ヷヽ漢a"), "ヷヽ漢a");
+        
+        let mut converter = converter(&[("かたかな", "katakana"), ("きょう", "kjoː")]);
+        assert_eq!(converter.convert("カタカナ"), "カタカナ");
+        
+        converter.options.fold_katakana = true;
+        assert_eq!(converter.convert("カタカナ"), "katakana");
+        assert_eq!(converter.convert("キョウ"), "kjoː");
+        assert_eq!(segmented(&converter, "カタカナ"), "katakana");
+    }
+    
+    #[test]
+    fn load_and_save_errors_are_structured() {
+        match parse_flat_json("{\n  \"a\": \"b\" \"c\"\n}") {
+            Err(ConverterError::JsonParse { position, line, .. }) => assert_eq!((position, line), (14, 2)),
+            other => panic!("{:?}", other),
+        }
+        
+        let mut converter = converter(&[("日本", "nihoɴ")]);
+        assert!(matches!(PhonemeConverter::new().try_convert("日本"), Err(ConverterError::NotLoaded)));
+        
+        let path = temp_path("bad_substitutions.json");
+        fs::write(&path, "{\"國語\": \"国\"}").unwrap();
+        match converter.load_substitutions(&path) {
+            Err(ConverterError::InvalidSubstitution { from, to }) => assert_eq!((from.as_str(), to.as_str()), ("國語", "国")),
+            other => panic!("{:?}", other),
+        }
+        fs::remove_file(&path).unwrap();
+        
+        let mut registry = ConverterRegistry::new();
+        match registry.load("general", &temp_path("missing.trie")) {
+            Err(ConverterError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(converter.save_binary_format(&temp_path("no/such/dir.trie")), Err(ConverterError::Io(_))));
+    }
+    
+    #[test]
+    fn corrupt_binary_lengths_are_rejected_without_allocating() {
+        let mut header = b"JPHO".to_vec();
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(&1u32.to_le_bytes());
+        
+        // Six continuation bytes: wider than any u32
+        let mut overlong = header.clone();
+        overlong.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]);
+        let result = PhonemeConverter::new().load_binary_from_reader(&overlong[..]);
+        assert!(matches!(result, Err(ConverterError::Corrupt { .. })), "{:?}", result);
+        
+        // A 4 GiB key length with two bytes behind it fails at the end of the input
+        let mut truncated = header.clone();
+        write_varint(&mut truncated, u32::MAX).unwrap();
+        truncated.extend_from_slice("日".as_bytes());
+        match PhonemeConverter::new().load_binary_from_reader(&truncated[..]) {
+            Err(ConverterError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            other => panic!("{:?}", other),
+        }
+        
+        let mut converter = PhonemeConverter::new();
+        converter.load_binary_from_reader(&trie_bytes(&[("日本", "nihoɴ")])[..]).unwrap();
+        assert_eq!(converter.convert("日本"), "nihoɴ");
+    }
+    
+    #[test]
+    fn kana_fast_path_skips_segmentation_for_pure_kana() {
+        let converter = converter(&[("ありがとう", "aɾigatoː"), ("ござい", "gozai"), ("ます", "masɯ"), ("は", "ha"), ("日本", "nihoɴ")]);
+        let mut segmenter = WordSegmenter::new();
+        assert_eq!(convert_with_segmentation(&converter, "ありがとうございます", &segmenter), "aɾigatoː gozai masɯ");
+        
+        segmenter.set_kana_fast_path(true);
+        assert!(segmenter.uses_kana_fast_path("ありがとうございます", &converter.options));
+        assert_eq!(convert_with_segmentation(&converter, "ありがとうございます", &segmenter), "aɾigatoːgozaimasɯ");
+        
+        // Kanji, particles and per-word settings still segment
+        assert!(!segmenter.uses_kana_fast_path("日本ます", &converter.options));
+        assert!(!segmenter.uses_kana_fast_path("ますは", &converter.options));
+        segmenter.set_dedup_consecutive_words(true);
+        assert!(!segmenter.uses_kana_fast_path("ありがとう", &converter.options));
+    }
+    
+    #[test]
+    fn binary_save_round_trips_every_entry() {
+        let mut converter = converter(&[("日本", "nihoɴ"), ("日本語", "nihoŋgo"), ("ゝ", ""), ("\"引用\"", "iɴjoː")]);
+        converter.insert_word_marker("東京都");
+        
+        let path = temp_path("round_trip.trie");
+        converter.save_binary_format(&path).unwrap();
+        let mut loaded = PhonemeConverter::new();
+        loaded.load_from_binary(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        
+        assert_eq!(loaded.collect_entries(true), converter.collect_entries(true));
+        assert_eq!(loaded.convert("日本語"), "nihoŋgo");
+        assert_eq!(loaded.lookup("東京都"), None);
+    }
+    
+    #[test]
+    fn stdin_mode_writes_one_line_per_input_line() {
+        let converter = converter(&[("私", "ɰᵝatai"), ("は", "ha"), ("猫", "neko")]);
+        let mut output = Vec::new();
+        convert_lines(&converter, Some(&WordSegmenter::new()), "私は猫\n\n  猫 \n".as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "ɰᵝatai wa neko\n\nneko\n");
+        
+        let mut output = Vec::new();
+        convert_lines(&converter, None, "私は猫".as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "ɰᵝataihaneko\n");
+        assert!(QUIET_FLAGS.contains(&"--stdin"));
+    }
+    
+    #[test]
+    fn iter_matches_yields_the_detailed_matches_lazily() {
+        let converter = converter(&[("日本", "nihoɴ"), ("語", "go"), ("が", "ga"), ("っ", "ʔ"), ("こう", "koː")]);
+        for text in ["日本語", "日本?語がっこう", "がっこう日本", "abc"] {
+            let lazy: Vec<(String, String, usize)> = converter.iter_matches(text)
+                .map(|m| (m.original, m.phoneme, m.start_index)).collect();
+            let detailed: Vec<(String, String, usize)> = converter.convert_detailed(text).matches.into_iter()
+                .map(|m| (m.original, m.phoneme, m.start_index)).collect();
+            assert_eq!(lazy, detailed, "{}", text);
+        }
+        
+        let first = converter.iter_matches("日本語日本語").next().unwrap();
+        assert_eq!((first.original.as_str(), first.start_index), ("日本", 0));
+        assert_eq!(converter.iter_matches("日本語日本語").take(3).count(), 3);
+    }
+    
+    #[test]
+    fn binary_trie_loads_from_in_memory_bytes() {
+        let bytes = trie_bytes(&[("私", "ɰᵝatai"), ("リンゴ", "ɾiɴgo")]);
+        let mut converter = PhonemeConverter::new();
+        converter.load_binary_from_reader(&bytes[..]).unwrap();
+        assert!(converter.is_loaded());
+        assert_eq!(convert_with_segmentation(&converter, "私はリンゴ", &WordSegmenter::new()), "ɰᵝatai wa ɾiɴgo");
+        
+        let error = PhonemeConverter::new().load_binary_from_reader(&b"{\"a\": \"b\"}"[..]).unwrap_err();
+        assert!(error.is_format_error());
+        assert_eq!(error.to_string(), "Invalid binary format: bad magic number");
+    }
+    
+    #[test]
+    #[cfg(not(feature = "gzip"))]
+    fn gzip_dictionary_needs_the_gzip_feature() {
+        let path = temp_path("compressed.json.gz");
+        fs::write(&path, [0x1f, 0x8b, 0x08, 0x00]).unwrap();
+        match PhonemeConverter::new().load_from_json(&path) {
+            Err(ConverterError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::Unsupported),
+            other => panic!("{:?}", other),
+        }
+        
+        fs::write(&path, "{\"日本\": \"nihoɴ\"}").unwrap();
+        assert_eq!(read_dictionary_text(&path).unwrap(), "{\"日本\": \"nihoɴ\"}");
+        fs::remove_file(&path).unwrap();
+    }
+    
+    #[test]
+    fn convert_batch_keeps_input_order() {
+        let converter = converter(&[("日本", "nihoɴ"), ("語", "go"), ("猫", "neko")]);
+        let inputs = ["猫", "日本語", "", "abc"];
+        let expected: Vec<String> = inputs.iter().map(|text| converter.convert(text)).collect();
+        assert_eq!(converter.convert_batch(&inputs), expected);
+        assert!(converter.convert_batch(&[]).is_empty());
+    }
+    
+    #[test]
+    fn convert_word_is_the_walk_without_passes() {
+        let mut converter = converter(&[("は", "ha"), ("が", "ga"), ("こう", "koː"), ("日本", "nihoɴ")]);
+        converter.options.number_mode = NumberMode::Cardinal;
+        converter.options.add_tie_bars = true;
+        
+        assert_eq!(converter.convert_word("は"), "ha");
+        assert_eq!(converter.convert_word("がっこう"), "gakːoː");
+        assert_eq!(converter.convert_word("日本12"), "nihoɴ12");
+        assert_eq!(converter.convert_word("日本x"), "nihoɴx");
+        
+        converter.options.unknown_kanji_phoneme = Some("?".to_string());
+        assert_eq!(converter.convert_word("日本語"), "nihoɴ?");
+    }
+    
+    #[test]
+    fn text_loaders_return_converter_errors() {
+        let mut seg = WordSegmenter::new();
+        assert!(matches!(seg.load_from_file("no/such/words.txt"), Err(ConverterError::Io(_))));
+        
+        assert_eq!(decode_utf8(vec![0xEF, 0xBB, 0xBF, b'a']).unwrap(), "a");
+        match decode_utf8(b"abc\xff".to_vec()) {
+            Err(ConverterError::Utf8(e)) => assert_eq!(e.utf8_error().valid_up_to(), 3),
+            other => panic!("expected Utf8, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+// Japanese to Phoneme Converter - Rust library crate
+// The converter lives in jpn_to_phoneme.rs, which still builds on its own as
+// the command-line tool; this crate root exposes its public API to other
+// Rust projects, and main.rs is the same tool built on top of it.
+// Compile: rustc -O --crate-type rlib --crate-name jpn_to_phoneme lib.rs
+// Then:    rustc -O main.rs --extern jpn_to_phoneme=libjpn_to_phoneme.rlib
+// Browser: wasm/build.sh builds it with the "wasm" feature (wasm.rs bindings)
+//
+// Usage:
+//     let mut converter = jpn_to_phoneme::PhonemeConverter::new();
+//     converter.load_from_binary("japanese.trie")?;
+//     let phonemes = converter.convert("日本語");
+
+#[path = "jpn_to_phoneme.rs"]
+mod converter;
+
+pub use converter::{convert_aligned, convert_aligned_lines, convert_all, convert_detailed_with_segmentation, convert_formatted, convert_with_segmentation, ConversionResult, ConverterConfig, ConverterError, ConverterRegistry, Match, MoraInfo, MoraKind, OutputFormat, PhonemeComparison, PhonemeConverter, RomajiConverter, RomajiLongVowels, Span, TraceEvent, WordSegmenter};
+pub use converter::{is_closing_quote, is_sentence_end, sentences, Sentences};
+pub use converter::{set_quiet, AsciiCase, BracketIssue, BracketWarning, ConversionOptions, ElongationMode, GeminateStyle, LoanwordVowelPolicy, LongVowelStyle, NumberMode, PhonemeInventory, WoReading, WordCase};
+
+// Optional browser bindings (WasmConverter)
+// Compile: wasm/build.sh (wasm-pack with the wasm-bindgen crate)
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+
+#[cfg(feature = "wasm")]
+mod wasm;
+
+#[cfg(feature = "wasm")]
+pub use wasm::WasmConverter;
+
+/// Run the command-line tool on the process arguments (main.rs)
+pub use converter::main as run_cli;
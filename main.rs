@@ -0,0 +1,9 @@
+// Japanese to Phoneme Converter - command-line tool on the library crate
+// Compile: see lib.rs (or build jpn_to_phoneme.rs directly as one file)
+// Usage: ./jpn_to_phoneme "日本語テキスト"
+
+extern crate jpn_to_phoneme;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    jpn_to_phoneme::run_cli()
+}
@@ -0,0 +1,46 @@
+// WebAssembly bindings (compiled with the "wasm" feature, see wasm/build.sh)
+// There is no filesystem in the browser, so the dictionary arrives as the
+// bytes of a binary trie (japanese.trie) fetched by the page.
+//
+// Usage (JavaScript):
+//     const bytes = new Uint8Array(await (await fetch("japanese.trie")).arrayBuffer());
+//     const converter = new WasmConverter(bytes);
+//     converter.convert("日本語");          // "nipːoɴgo"
+//     converter.convertWords("私はリンゴ");  // "ɰᵝatai wa ɾiɴgo"
+
+use wasm_bindgen::prelude::*;
+
+use crate::{convert_with_segmentation, PhonemeConverter, WordSegmenter};
+
+/// PhonemeConverter loaded from an in-memory binary trie
+#[wasm_bindgen]
+pub struct WasmConverter {
+    converter: PhonemeConverter,
+    segmenter: WordSegmenter,  // Empty: the binary trie already carries the words
+}
+
+#[wasm_bindgen]
+impl WasmConverter {
+    /// Load a binary trie (JPHO v1.0) from its bytes
+    /// Throws with the load error's message if the bytes aren't one.
+    #[wasm_bindgen(constructor)]
+    pub fn new(dictionary: &[u8]) -> Result<WasmConverter, JsError> {
+        let mut converter = PhonemeConverter::new();
+        converter.load_binary_from_reader(dictionary)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        
+        Ok(WasmConverter { converter, segmenter: WordSegmenter::new() })
+    }
+    
+    /// Greedy conversion of the whole text (PhonemeConverter::convert)
+    pub fn convert(&self, text: &str) -> String {
+        self.converter.convert(text)
+    }
+    
+    /// Segmented conversion with spaces between words and furigana hints
+    /// (健太「けんた」) honored, like the command-line tool
+    #[wasm_bindgen(js_name = convertWords)]
+    pub fn convert_words(&self, text: &str) -> String {
+        convert_with_segmentation(&self.converter, text, &self.segmenter)
+    }
+}
@@ -0,0 +1,67 @@
+#!/bin/bash
+# Japanese to Phoneme Converter - WebAssembly Build Script
+# Builds lib.rs with the "wasm" feature into a wasm-pack package (wasm/pkg)
+# Usage: ./build.sh [web|nodejs|bundler]   (default: web)
+
+set -e
+cd "$(dirname "$0")"
+
+TARGET="${1:-web}"
+
+echo ""
+echo "╔════════════════════════════════════════════════════════╗"
+echo "║  Japanese to Phoneme Converter - WebAssembly Build    ║"
+echo "╚════════════════════════════════════════════════════════╝"
+echo ""
+
+# Check if wasm-pack is installed
+if ! command -v wasm-pack &> /dev/null; then
+    echo "❌ wasm-pack not found! Please install it first."
+    echo "   cargo install wasm-pack"
+    echo "   rustup target add wasm32-unknown-unknown"
+    exit 1
+fi
+
+# wasm-pack builds a Cargo crate: generate one around lib.rs in build/
+# (the converter itself stays a plain rustc build, no manifest at the root).
+# Every cfg feature of jpn_to_phoneme.rs is declared with the crate it needs.
+mkdir -p build
+cat > build/Cargo.toml <<'TOML'
+[package]
+name = "jpn_to_phoneme"
+version = "1.0.0"
+edition = "2021"
+description = "Japanese to IPA phoneme converter (WebAssembly)"
+
+[lib]
+path = "../../lib.rs"
+crate-type = ["cdylib", "rlib"]
+
+[features]
+default = ["wasm"]
+wasm = ["wasm-bindgen"]
+encoding = ["encoding_rs"]
+parallel = ["rayon"]
+gzip = ["flate2"]
+vec_trie = []
+pool_trie = []
+
+[dependencies]
+wasm-bindgen = { version = "0.2", optional = true }
+encoding_rs = { version = "0.8", optional = true }
+rayon = { version = "1", optional = true }
+flate2 = { version = "1", optional = true }
+TOML
+
+echo "🔨 Building wasm package (target: $TARGET)..."
+wasm-pack build build --release --target "$TARGET" --out-dir ../pkg
+
+echo ""
+echo "✨ Build complete!"
+echo ""
+echo "🚀 Usage:"
+echo "   1. Serve wasm/pkg/ and japanese.trie with your page"
+echo "   2. const converter = new WasmConverter(new Uint8Array(trieBytes));"
+echo "   3. converter.convert(\"日本語\") → \"nipːoɴgo\""
+echo "   Smoke test (Node.js): ./build.sh nodejs && node smoke_test.js"
+echo ""
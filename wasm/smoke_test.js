@@ -0,0 +1,37 @@
+// WebAssembly smoke test - run after ./build.sh nodejs
+// Loads japanese.trie from the repo root and checks a few conversions.
+// Usage: node smoke_test.js [path/to/japanese.trie]
+
+const fs = require('fs');
+const path = require('path');
+const { WasmConverter } = require('./pkg/jpn_to_phoneme.js');
+
+const triePath = process.argv[2] || path.join(__dirname, '..', 'japanese.trie');
+const converter = new WasmConverter(new Uint8Array(fs.readFileSync(triePath)));
+
+const cases = [
+    ['convert', '日本語', 'nipːoɴgo'],
+    ['convertWords', '私はリンゴがすきです', 'ɰᵝatai wa ɾiɴgo ga sɯki desɯ'],
+    ['convertWords', '健太「けんた」はバカ', 'keɴta wa baka'],
+];
+
+let failed = 0;
+for (const [method, input, expected] of cases) {
+    const actual = converter[method](input);
+    const ok = actual === expected;
+    if (!ok) failed++;
+    console.log(`${ok ? '✅' : '❌'} ${method}("${input}") → "${actual}"${ok ? '' : ` (expected "${expected}")`}`);
+}
+
+// Bytes that aren't a trie must throw, not load an empty dictionary
+try {
+    new WasmConverter(new Uint8Array([1, 2, 3, 4, 5, 6, 7, 8]));
+    console.log('❌ invalid dictionary bytes were accepted');
+    failed++;
+} catch (e) {
+    console.log(`✅ invalid dictionary rejected: ${e.message}`);
+}
+
+converter.free();
+console.log(failed === 0 ? '\n✨ All passed' : `\n❌ ${failed} failed`);
+process.exit(failed === 0 ? 0 : 1);